@@ -0,0 +1,293 @@
+use crate::config::{app_dir, ConfigManager};
+use crate::csv_format;
+use crate::models::{AuditAction, AuditEntry, AuditOutcome, AuditQuery, Result, S3DeckError};
+use chrono::Utc;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, bucket_id: Option<&str>, keys: Vec<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action,
+            bucket_id: bucket_id.map(|id| id.to_string()),
+            bucket_name: None,
+            keys,
+            bytes: None,
+            outcome: AuditOutcome::Success,
+            message: None,
+            error: None,
+        }
+    }
+
+    pub fn bucket_name(mut self, name: &str) -> Self {
+        self.bucket_name = Some(name.to_string());
+        self
+    }
+
+    pub fn bytes(mut self, bytes: i64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn skipped(mut self) -> Self {
+        self.outcome = AuditOutcome::Skipped;
+        self
+    }
+
+    /// Mark the entry failed with this error
+    pub fn failed(mut self, error: impl ToString) -> Self {
+        self.outcome = AuditOutcome::Failed;
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// Take the outcome from the result of the audited operation
+    pub fn result<T>(self, result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => self,
+            Err(e) => self.failed(e),
+        }
+    }
+}
+
+/// Append-only JSONL log of every operation that changed a bucket or the app config
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new() -> Result<Self> {
+        Ok(Self::at(app_dir()?.join("audit.jsonl")))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn append(&self, entries: &[AuditEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+
+        // A single append-mode write keeps lines from concurrent commands intact
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    /// Entries matching the query, newest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let reader = BufReader::new(fs::File::open(&self.path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            // A line cut short by a crash mid-write shouldn't hide the rest of the log
+            if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) {
+                if matches_query(&entry, query) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.reverse();
+        if let Some(limit) = query.limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+
+    /// Write the matching entries to `destination`, as CSV when it ends in `.csv` and as
+    /// JSONL otherwise. Returns the number of entries written.
+    pub fn export(&self, query: &AuditQuery, destination: &Path) -> Result<usize> {
+        let entries = self.query(query)?;
+
+        let is_csv = destination
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let content = if is_csv {
+            to_csv(&entries)?
+        } else {
+            let mut lines = String::new();
+            for entry in &entries {
+                lines.push_str(&serde_json::to_string(entry)?);
+                lines.push('\n');
+            }
+            lines
+        };
+
+        fs::write(destination, content).map_err(|e| {
+            S3DeckError::Io(format!(
+                "Failed to write audit export '{}': {}",
+                destination.display(),
+                e
+            ))
+        })?;
+        Ok(entries.len())
+    }
+}
+
+/// Append entries to the audit log. Auditing never fails the operation it records, so
+/// problems are only reported on stderr.
+pub fn record(entries: Vec<AuditEntry>) {
+    let result = AuditLog::new().and_then(|log| log.append(&with_bucket_names(entries)));
+    if let Err(e) = result {
+        eprintln!("Failed to write audit log: {}", e);
+    }
+}
+
+/// Fill in bucket names from the config, so the log stays readable after a bucket is removed
+fn with_bucket_names(mut entries: Vec<AuditEntry>) -> Vec<AuditEntry> {
+    let Ok(buckets) = ConfigManager::new().and_then(|manager| manager.get_all_buckets()) else {
+        return entries;
+    };
+
+    for entry in entries.iter_mut().filter(|e| e.bucket_name.is_none()) {
+        entry.bucket_name = buckets
+            .iter()
+            .find(|b| Some(&b.id) == entry.bucket_id.as_ref())
+            .map(|b| b.name.clone());
+    }
+    entries
+}
+
+fn matches_query(entry: &AuditEntry, query: &AuditQuery) -> bool {
+    query
+        .bucket_id
+        .as_ref()
+        .is_none_or(|id| entry.bucket_id.as_ref() == Some(id))
+        && query.action.is_none_or(|action| entry.action == action)
+        && query.outcome.is_none_or(|outcome| entry.outcome == outcome)
+        && query
+            .key
+            .as_ref()
+            .is_none_or(|key| entry.keys.iter().any(|k| k.contains(key.as_str())))
+        && query.since.is_none_or(|since| entry.timestamp >= since)
+        && query.until.is_none_or(|until| entry.timestamp <= until)
+}
+
+fn to_csv(entries: &[AuditEntry]) -> Result<String> {
+    let mut csv =
+        String::from("timestamp,action,bucketId,bucketName,keys,bytes,outcome,message,error\n");
+    for entry in entries {
+        // Enums serialize as plain strings, e.g. "Upload"
+        let action = serde_json::to_value(entry.action)?;
+        let outcome = serde_json::to_value(entry.outcome)?;
+        let fields = [
+            entry.timestamp.to_rfc3339(),
+            action.as_str().unwrap_or_default().to_string(),
+            entry.bucket_id.clone().unwrap_or_default(),
+            entry.bucket_name.clone().unwrap_or_default(),
+            // Keys may contain any character, so keep them unambiguous as a JSON array
+            serde_json::to_string(&entry.keys)?,
+            entry.bytes.map(|b| b.to_string()).unwrap_or_default(),
+            outcome.as_str().unwrap_or_default().to_string(),
+            entry.message.clone().unwrap_or_default(),
+            entry.error.clone().unwrap_or_default(),
+        ];
+        csv.push_str(&csv_format::row(&fields));
+    }
+    Ok(csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log() -> (AuditLog, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("s3deck-audit-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        (AuditLog::at(dir.join("audit.jsonl")), dir)
+    }
+
+    #[test]
+    fn test_query_filters_newest_first() {
+        let (log, dir) = temp_log();
+        log.append(&[
+            AuditEntry::new(AuditAction::Upload, Some("b1"), vec!["a/report.pdf".into()]).bytes(42),
+            AuditEntry::new(AuditAction::Delete, Some("b1"), vec!["a/report.pdf".into()])
+                .failed("Access Denied"),
+            AuditEntry::new(AuditAction::Delete, Some("b2"), vec!["b/notes.txt".into()]),
+        ])
+        .unwrap();
+        // Torn lines are skipped rather than failing the whole query
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&log.path)
+            .unwrap()
+            .write_all(b"{\"timestamp\":")
+            .unwrap();
+
+        let all = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].keys, vec!["b/notes.txt"]);
+
+        let deletes_of_report = log
+            .query(&AuditQuery {
+                action: Some(AuditAction::Delete),
+                key: Some("report".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(deletes_of_report.len(), 1);
+        assert_eq!(deletes_of_report[0].outcome, AuditOutcome::Failed);
+        assert_eq!(deletes_of_report[0].error.as_deref(), Some("Access Denied"));
+
+        let limited = log
+            .query(&AuditQuery {
+                bucket_id: Some("b1".into()),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].action, AuditAction::Delete);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_as_csv_and_jsonl() {
+        let (log, dir) = temp_log();
+        log.append(&[AuditEntry::new(
+            AuditAction::Rename,
+            Some("b1"),
+            vec!["old, \"quoted\".txt".into(), "new.txt".into()],
+        )
+        .message("File renamed")])
+            .unwrap();
+
+        let csv_path = dir.join("audit.csv");
+        assert_eq!(log.export(&AuditQuery::default(), &csv_path).unwrap(), 1);
+        let csv = fs::read_to_string(&csv_path).unwrap();
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.contains(r#",Rename,b1,,"[""old, \""quoted\"".txt"",""new.txt""]",,Success,"#));
+
+        let jsonl_path = dir.join("audit.jsonl.export");
+        assert_eq!(log.export(&AuditQuery::default(), &jsonl_path).unwrap(), 1);
+        let exported = AuditLog::at(jsonl_path)
+            .query(&AuditQuery::default())
+            .unwrap();
+        assert_eq!(exported[0].message.as_deref(), Some("File renamed"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
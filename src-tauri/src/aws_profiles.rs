@@ -1,7 +1,13 @@
-use crate::models::{AwsProfile, ProfileBucket, ProfileStatus, Result, S3DeckError};
+use crate::config::ConfigManager;
+use crate::http_client::build_http_client;
+use crate::models::{
+    AwsProfile, CredentialSource, ProfileBucket, ProfileDetails, ProfileStatus, Result, S3DeckError,
+};
 use aws_config::BehaviorVersion;
+use aws_sdk_s3::config::ProvideCredentials;
 use aws_sdk_s3::Client;
-use chrono::DateTime;
+use aws_types::SdkConfig;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
@@ -43,18 +49,10 @@ impl AwsProfileManager {
                 // Try to list buckets to validate credentials
                 match client.list_buckets().send().await {
                     Ok(_) => Ok(ProfileStatus::Valid),
-                    Err(e) => {
-                        let error_msg = e.to_string().to_lowercase();
-                        if error_msg.contains("expired") || error_msg.contains("invalid") {
-                            Ok(ProfileStatus::Expired)
-                        } else if error_msg.contains("access denied")
-                            || error_msg.contains("forbidden")
-                        {
-                            Ok(ProfileStatus::Invalid)
-                        } else {
-                            Ok(ProfileStatus::Unknown)
-                        }
-                    }
+                    Err(e) => Ok(status_for_error(&S3DeckError::from_sdk(
+                        "Failed to list buckets",
+                        e,
+                    ))),
                 }
             }
             Err(_) => Ok(ProfileStatus::Invalid),
@@ -63,40 +61,22 @@ impl AwsProfileManager {
 
     /// Get buckets for a specific AWS profile
     pub async fn get_buckets_for_profile(&self, profile_name: &str) -> Result<Vec<ProfileBucket>> {
-        let client = self.create_s3_client_for_profile(profile_name).await
-            .map_err(|e| {
-                let error_msg = e.to_string().to_lowercase();
-                if error_msg.contains("no credentials") || error_msg.contains("credential") {
-                    S3DeckError::S3(format!("No valid credentials found for profile '{}'. Please configure your AWS credentials.", profile_name))
-                } else if error_msg.contains("profile") && error_msg.contains("not found") {
-                    S3DeckError::S3(format!("AWS profile '{}' not found. Please check your AWS configuration.", profile_name))
-                } else if error_msg.contains("region") {
-                    S3DeckError::S3(format!("Invalid or missing region for profile '{}'. Please check your AWS configuration.", profile_name))
-                } else {
-                    S3DeckError::S3(format!("Failed to initialize AWS client for profile '{}': {}", profile_name, e))
-                }
-            })?;
+        let client = self.create_s3_client_for_profile(profile_name).await?;
 
-        let response = client
-            .list_buckets()
-            .send()
-            .await
-            .map_err(|e| {
-                let error_msg = e.to_string().to_lowercase();
-                if error_msg.contains("expired") {
-                    S3DeckError::S3(format!("Profile '{}' credentials have expired. Please run 'aws sso login' or refresh your credentials.", profile_name))
-                } else if error_msg.contains("invalid") || error_msg.contains("access denied") || error_msg.contains("forbidden") {
-                    S3DeckError::S3(format!("Profile '{}' credentials are invalid or access is denied. Please check your AWS permissions.", profile_name))
-                } else if error_msg.contains("no credentials") || error_msg.contains("credential") {
-                    S3DeckError::S3(format!("No valid credentials found for profile '{}'. Please configure your AWS credentials.", profile_name))
-                } else if error_msg.contains("network") || error_msg.contains("connection") || error_msg.contains("timeout") {
-                    S3DeckError::S3(format!("Network error when connecting with profile '{}'. Please check your internet connection.", profile_name))
-                } else if error_msg.contains("region") {
-                    S3DeckError::S3(format!("Invalid or missing region for profile '{}'. Please check your AWS configuration.", profile_name))
-                } else {
-                    S3DeckError::S3(format!("Failed to list buckets for profile '{}': {}. Please check your AWS configuration and credentials.", profile_name, e))
-                }
-            })?;
+        let response = client.list_buckets().send().await.map_err(|e| {
+            let error = S3DeckError::from_sdk(
+                &format!("Failed to list buckets for profile '{}'", profile_name),
+                e,
+            );
+            let message = match &error {
+                S3DeckError::ExpiredToken(_) => format!("Profile '{}' credentials have expired. Please run 'aws sso login' or refresh your credentials.", profile_name),
+                S3DeckError::AccessDenied(_) => format!("Profile '{}' credentials are invalid or access is denied. Please check your AWS permissions.", profile_name),
+                S3DeckError::Credentials(_) => format!("No valid credentials found for profile '{}'. Please configure your AWS credentials or run 'aws sso login'.", profile_name),
+                S3DeckError::Network(_) => format!("Network error when connecting with profile '{}'. Please check your internet connection.", profile_name),
+                _ => format!("{}. Please check your AWS configuration and credentials.", error),
+            };
+            error.with_message(message)
+        })?;
 
         let mut buckets = Vec::new();
 
@@ -124,6 +104,92 @@ impl AwsProfileManager {
         Ok(buckets)
     }
 
+    /// Resolve a profile and describe where its credentials come from and which account they
+    /// land in
+    pub async fn get_profile_details(
+        &self,
+        profile_name: &str,
+        sts_endpoint: Option<&str>,
+    ) -> Result<ProfileDetails> {
+        // Environment variables take precedence over any profile in the default chain
+        let credential_source = if std::env::var("AWS_ACCESS_KEY_ID").is_ok()
+            && std::env::var("AWS_SECRET_ACCESS_KEY").is_ok()
+        {
+            CredentialSource::Environment
+        } else {
+            credential_source_for(&self.read_profile_settings(profile_name))
+        };
+
+        let sdk_config = self.load_sdk_config(profile_name).await?;
+
+        Ok(self
+            .describe_identity(profile_name, credential_source, &sdk_config, sts_endpoint)
+            .await)
+    }
+
+    /// Load credentials from an SDK config and ask STS who they belong to
+    async fn describe_identity(
+        &self,
+        profile_name: &str,
+        credential_source: CredentialSource,
+        sdk_config: &SdkConfig,
+        sts_endpoint: Option<&str>,
+    ) -> ProfileDetails {
+        let mut details = ProfileDetails {
+            name: profile_name.to_string(),
+            region: sdk_config.region().map(|r| r.to_string()),
+            credential_source,
+            credentials_expire_at: None,
+            account_id: None,
+            arn: None,
+            user_id: None,
+            status: ProfileStatus::Unknown,
+            error: None,
+        };
+
+        let provider = match sdk_config.credentials_provider() {
+            Some(provider) => provider,
+            None => {
+                details.status = ProfileStatus::Invalid;
+                details.error = Some("No credential provider configured".to_string());
+                return details;
+            }
+        };
+
+        match provider.provide_credentials().await {
+            Ok(credentials) => {
+                details.credentials_expire_at = credentials.expiry().map(DateTime::<Utc>::from);
+            }
+            Err(e) => {
+                details.status = ProfileStatus::Invalid;
+                details.error = Some(format!("Failed to load credentials: {}", e));
+                return details;
+            }
+        }
+
+        let mut sts_config = aws_sdk_sts::config::Builder::from(sdk_config);
+        if let Some(endpoint) = sts_endpoint {
+            sts_config = sts_config.endpoint_url(endpoint);
+        }
+        let sts_client = aws_sdk_sts::Client::from_conf(sts_config.build());
+
+        match sts_client.get_caller_identity().send().await {
+            Ok(identity) => {
+                details.account_id = identity.account().map(|s| s.to_string());
+                details.arn = identity.arn().map(|s| s.to_string());
+                details.user_id = identity.user_id().map(|s| s.to_string());
+                details.status = ProfileStatus::Valid;
+            }
+            Err(e) => {
+                let error = S3DeckError::from_sdk("Failed to get caller identity", e);
+                details.status = status_for_error(&error);
+                details.error = Some(error.to_string());
+            }
+        }
+
+        details
+    }
+
     /// Get AWS config file path
     fn get_aws_config_path(&self) -> Result<PathBuf> {
         let home_dir = dirs::home_dir()
@@ -135,7 +201,22 @@ impl AwsProfileManager {
 
     /// Parse AWS config file and extract profiles
     fn parse_aws_config(&self, content: &str) -> Result<Vec<AwsProfile>> {
-        let mut profiles = Vec::new();
+        let profiles = self
+            .parse_profile_sections(content)
+            .into_iter()
+            .map(|(name, data)| AwsProfile {
+                name,
+                region: data.get("region").cloned(),
+                status: ProfileStatus::Unknown, // Will be validated separately
+            })
+            .collect();
+
+        Ok(profiles)
+    }
+
+    /// Split an AWS config or credentials file into named profile sections
+    fn parse_profile_sections(&self, content: &str) -> Vec<(String, HashMap<String, String>)> {
+        let mut sections = Vec::new();
         let mut current_profile: Option<String> = None;
         let mut profile_data: HashMap<String, String> = HashMap::new();
 
@@ -151,24 +232,18 @@ impl AwsProfileManager {
             if line.starts_with('[') && line.ends_with(']') {
                 // Save previous profile if exists
                 if let Some(profile_name) = current_profile.take() {
-                    let region = profile_data.get("region").cloned();
-                    profiles.push(AwsProfile {
-                        name: profile_name,
-                        region,
-                        status: ProfileStatus::Unknown, // Will be validated separately
-                    });
+                    sections.push((profile_name, std::mem::take(&mut profile_data)));
                 }
 
                 // Parse new profile name
                 let profile_section = &line[1..line.len() - 1];
                 current_profile = if profile_section == "default" {
                     Some("default".to_string())
-                } else if profile_section.starts_with("profile ") {
-                    Some(profile_section[8..].to_string()) // Remove "profile " prefix
+                } else if let Some(name) = profile_section.strip_prefix("profile ") {
+                    Some(name.to_string()) // Remove "profile " prefix
                 } else {
                     Some(profile_section.to_string())
                 };
-                profile_data.clear();
             } else if let Some(eq_pos) = line.find('=') {
                 // Key-value pair
                 let key = line[..eq_pos].trim();
@@ -179,23 +254,55 @@ impl AwsProfileManager {
 
         // Don't forget the last profile
         if let Some(profile_name) = current_profile {
-            let region = profile_data.get("region").cloned();
-            profiles.push(AwsProfile {
-                name: profile_name,
-                region,
-                status: ProfileStatus::Unknown,
-            });
+            sections.push((profile_name, profile_data));
         }
 
-        Ok(profiles)
+        sections
     }
 
-    /// Create S3 client for a specific profile
-    async fn create_s3_client_for_profile(&self, profile_name: &str) -> Result<Client> {
-        let config = aws_config::defaults(BehaviorVersion::latest())
+    /// Collect the settings of a profile from both ~/.aws/config and ~/.aws/credentials
+    fn read_profile_settings(&self, profile_name: &str) -> HashMap<String, String> {
+        let mut settings = HashMap::new();
+        let home_dir = match dirs::home_dir() {
+            Some(dir) => dir,
+            None => return settings,
+        };
+
+        let config_file = std::env::var("AWS_CONFIG_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home_dir.join(".aws").join("config"));
+        let credentials_file = std::env::var("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| home_dir.join(".aws").join("credentials"));
+
+        for path in [config_file, credentials_file] {
+            if let Ok(content) = fs::read_to_string(&path) {
+                for (name, data) in self.parse_profile_sections(&content) {
+                    if name == profile_name {
+                        settings.extend(data);
+                    }
+                }
+            }
+        }
+
+        settings
+    }
+
+    /// Load the SDK config for a profile, routed through the global proxy and CA settings
+    async fn load_sdk_config(&self, profile_name: &str) -> Result<SdkConfig> {
+        let network = ConfigManager::new()?.get_network_settings()?;
+        let http_client = build_http_client(&network, None)?;
+
+        Ok(aws_config::defaults(BehaviorVersion::latest())
             .profile_name(profile_name)
+            .http_client(http_client)
             .load()
-            .await;
+            .await)
+    }
+
+    /// Create S3 client for a specific profile
+    async fn create_s3_client_for_profile(&self, profile_name: &str) -> Result<Client> {
+        let config = self.load_sdk_config(profile_name).await?;
 
         let s3_config = aws_sdk_s3::config::Builder::from(&config).build();
         let client = Client::from_conf(s3_config);
@@ -228,3 +335,172 @@ impl AwsProfileManager {
         }
     }
 }
+
+/// Map a failed credential check onto the profile status shown in the UI
+fn status_for_error(error: &S3DeckError) -> ProfileStatus {
+    match error {
+        S3DeckError::ExpiredToken(_) => ProfileStatus::Expired,
+        S3DeckError::AccessDenied(_) | S3DeckError::Credentials(_) => ProfileStatus::Invalid,
+        _ => ProfileStatus::Unknown,
+    }
+}
+
+/// Work out which mechanism the SDK will use to obtain credentials for a profile
+fn credential_source_for(settings: &HashMap<String, String>) -> CredentialSource {
+    if settings.contains_key("role_arn") {
+        if settings.contains_key("web_identity_token_file") {
+            CredentialSource::WebIdentity
+        } else {
+            CredentialSource::AssumeRole
+        }
+    } else if settings.contains_key("sso_session") || settings.contains_key("sso_start_url") {
+        CredentialSource::Sso
+    } else if settings.contains_key("credential_process") {
+        CredentialSource::Process
+    } else if settings.contains_key("aws_access_key_id") {
+        CredentialSource::Static
+    } else {
+        CredentialSource::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::config::{Credentials, SharedCredentialsProvider};
+    use aws_types::region::Region;
+    use std::time::{Duration, SystemTime};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const CALLER_IDENTITY_RESPONSE: &str = r#"<GetCallerIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <GetCallerIdentityResult>
+    <Arn>arn:aws:iam::123456789012:user/deck</Arn>
+    <UserId>AIDAEXAMPLE</UserId>
+    <Account>123456789012</Account>
+  </GetCallerIdentityResult>
+  <ResponseMetadata>
+    <RequestId>01234567-89ab-cdef-0123-456789abcdef</RequestId>
+  </ResponseMetadata>
+</GetCallerIdentityResponse>"#;
+
+    /// Serve a single canned STS response on a local port
+    async fn sts_stand_in() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            // Read until the form-encoded body has arrived
+            while !String::from_utf8_lossy(&request).contains("Action=GetCallerIdentity") {
+                let read = socket.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CALLER_IDENTITY_RESPONSE.len(),
+                CALLER_IDENTITY_RESPONSE
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn test_credential_source_detection() {
+        let settings = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            credential_source_for(&settings(&[("aws_access_key_id", "AKIA")])),
+            CredentialSource::Static
+        );
+        assert_eq!(
+            credential_source_for(&settings(&[
+                ("sso_session", "corp"),
+                ("sso_account_id", "1")
+            ])),
+            CredentialSource::Sso
+        );
+        assert_eq!(
+            credential_source_for(&settings(&[
+                ("role_arn", "arn"),
+                ("source_profile", "base")
+            ])),
+            CredentialSource::AssumeRole
+        );
+        assert_eq!(
+            credential_source_for(&settings(&[
+                ("role_arn", "arn"),
+                ("web_identity_token_file", "/tmp/token")
+            ])),
+            CredentialSource::WebIdentity
+        );
+        assert_eq!(
+            credential_source_for(&settings(&[("credential_process", "vault aws")])),
+            CredentialSource::Process
+        );
+        assert_eq!(
+            credential_source_for(&settings(&[("region", "eu-west-1")])),
+            CredentialSource::Unknown
+        );
+    }
+
+    #[test]
+    fn test_profile_sections_merge_prefixes() {
+        let manager = AwsProfileManager::new();
+        let sections = manager.parse_profile_sections(
+            "[default]\nregion = us-east-1\n\n[profile dev]\nregion = eu-west-1\nsso_session = corp\n",
+        );
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].0, "default");
+        assert_eq!(sections[1].0, "dev");
+        assert_eq!(sections[1].1.get("sso_session").unwrap(), "corp");
+    }
+
+    #[tokio::test]
+    async fn test_describe_identity_against_local_sts() {
+        let endpoint = sts_stand_in().await;
+        let expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+        let credentials = Credentials::new("AKIDEXAMPLE", "secret", None, Some(expiry), "test");
+
+        let sdk_config = SdkConfig::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("eu-central-1"))
+            .credentials_provider(SharedCredentialsProvider::new(credentials))
+            .build();
+
+        let details = AwsProfileManager::new()
+            .describe_identity(
+                "dev",
+                CredentialSource::Static,
+                &sdk_config,
+                Some(&endpoint),
+            )
+            .await;
+
+        assert_eq!(details.status, ProfileStatus::Valid, "{:?}", details.error);
+        assert_eq!(details.region.as_deref(), Some("eu-central-1"));
+        assert_eq!(details.account_id.as_deref(), Some("123456789012"));
+        assert_eq!(
+            details.arn.as_deref(),
+            Some("arn:aws:iam::123456789012:user/deck")
+        );
+        assert_eq!(
+            details.credentials_expire_at.map(|dt| dt.timestamp()),
+            Some(2_000_000_000)
+        );
+    }
+}
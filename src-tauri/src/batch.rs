@@ -0,0 +1,467 @@
+use crate::csv_format;
+use crate::models::{AuditOutcome, BatchOperation, Result, S3DeckError, TrashItem};
+use crate::s3_client::{S3Client, WriteOutcome};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Requests a batch job keeps in flight when the request doesn't say
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+const MAX_CONCURRENCY: usize = 64;
+
+/// Audit entries are written in chunks of this many keys, so large jobs don't hold them all
+pub const AUDIT_CHUNK: usize = 1000;
+
+/// The keys listed in a manifest, in order and without repeats. Text files list one key per
+/// line. CSV files either have a header with a `key` column, like a listing export, or follow
+/// the S3 Batch Operations layout of `bucket,key[,versionId]` rows with URL-encoded keys.
+pub fn read_manifest(path: &Path) -> Result<Vec<String>> {
+    let read_error = |e: &dyn std::fmt::Display| {
+        S3DeckError::Io(format!(
+            "Failed to read manifest '{}': {}",
+            path.display(),
+            e
+        ))
+    };
+    let content = fs::read_to_string(path).map_err(|e| read_error(&e))?;
+
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if !is_csv {
+        return Ok(unique(
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.to_string()),
+        ));
+    }
+
+    let mut records = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes())
+        .into_records()
+        .peekable();
+    let header_column = match records.peek() {
+        Some(Ok(first)) => first.iter().position(|f| f.eq_ignore_ascii_case("key")),
+        _ => None,
+    };
+    if header_column.is_some() {
+        records.next();
+    }
+
+    let mut keys = Vec::new();
+    for record in records {
+        let record = record.map_err(|e| read_error(&e))?;
+        let key = match header_column {
+            Some(column) => record.get(column).map(|key| key.to_string()),
+            None if record.len() > 1 => match urlencoding::decode(&record[1]) {
+                Ok(key) => Some(key.into_owned()),
+                Err(e) => return Err(read_error(&e)),
+            },
+            None => record.get(0).map(|key| key.to_string()),
+        };
+        keys.extend(key.filter(|key| !key.is_empty()));
+    }
+    Ok(unique(keys))
+}
+
+/// Drop repeated keys, keeping the first occurrence
+pub fn unique(keys: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    keys.into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect()
+}
+
+/// Where a copy of `key` goes
+pub fn destination_key(destination_prefix: &str, key: &str) -> String {
+    format!("{}{}", destination_prefix, key)
+}
+
+/// What happened to one key of a batch job
+#[derive(Debug, Clone)]
+pub struct KeyResult {
+    pub key: String,
+    pub outcome: AuditOutcome,
+    /// The destination key of a copy, or why the key was skipped or failed
+    pub detail: String,
+    /// Objects a delete moved to the trash
+    pub trashed: Vec<TrashItem>,
+}
+
+impl KeyResult {
+    fn new(key: String, result: Result<(AuditOutcome, String, Vec<TrashItem>)>) -> Self {
+        match result {
+            Ok((outcome, detail, trashed)) => Self {
+                key,
+                outcome,
+                detail,
+                trashed,
+            },
+            Err(e) => Self {
+                key,
+                outcome: AuditOutcome::Failed,
+                detail: e.to_string(),
+                trashed: Vec::new(),
+            },
+        }
+    }
+}
+
+/// The operation a batch job applies and the buckets it works on
+pub struct BatchJob {
+    pub source: Arc<S3Client>,
+    /// Where copies go; the source bucket for every other operation
+    pub destination: Arc<S3Client>,
+    pub operation: BatchOperation,
+    /// The token the job was confirmed with, needed to delete objects the trash can't take
+    pub confirmation: Option<String>,
+}
+
+/// Apply the job's operation to every key, `concurrency` keys at a time. Each key is retried
+/// on its own, so a failing key is reported without stopping the others. Results are handed to
+/// `on_result` as they complete; an error from it stops the job. Returns false when
+/// `cancelled` was raised before every key was started.
+pub async fn run(
+    job: Arc<BatchJob>,
+    keys: Vec<String>,
+    concurrency: usize,
+    cancelled: &AtomicBool,
+    mut on_result: impl FnMut(KeyResult) -> Result<()>,
+) -> Result<bool> {
+    let concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+    let mut keys = keys.into_iter();
+    let mut tasks = JoinSet::new();
+
+    loop {
+        while tasks.len() < concurrency && !cancelled.load(Ordering::Relaxed) {
+            let Some(key) = keys.next() else {
+                break;
+            };
+            let job = job.clone();
+            tasks.spawn(async move {
+                let result = job.source.retry_object(|| apply(&job, &key)).await;
+                KeyResult::new(key, result)
+            });
+        }
+
+        match tasks.join_next().await {
+            Some(Ok(result)) => on_result(result)?,
+            Some(Err(e)) => return Err(S3DeckError::S3(format!("Batch task failed: {}", e))),
+            None => return Ok(keys.len() == 0),
+        }
+    }
+}
+
+async fn apply(job: &BatchJob, key: &str) -> Result<(AuditOutcome, String, Vec<TrashItem>)> {
+    let (source, destination) = (&job.source, &job.destination);
+    let done = |detail: &str| Ok((AuditOutcome::Success, detail.to_string(), Vec::new()));
+
+    match &job.operation {
+        BatchOperation::Delete => {
+            let deletion = source
+                .delete_object(key, job.confirmation.as_deref())
+                .await?;
+            let detail = if deletion.trashed.is_empty() {
+                ""
+            } else {
+                "Moved to the trash"
+            };
+            Ok((AuditOutcome::Success, detail.to_string(), deletion.trashed))
+        }
+        BatchOperation::Copy {
+            destination_prefix,
+            overwrite,
+            ..
+        } => {
+            let destination_key = destination_key(destination_prefix, key);
+            match source
+                .copy_object_to(destination, key, &destination_key, *overwrite)
+                .await?
+            {
+                WriteOutcome::Written(written_key) => done(&written_key),
+                WriteOutcome::Skipped => Ok((
+                    AuditOutcome::Skipped,
+                    format!("'{}' already exists", destination_key),
+                    Vec::new(),
+                )),
+            }
+        }
+        BatchOperation::SetStorageClass { storage_class } => {
+            source.set_storage_class(key, storage_class).await?;
+            done("")
+        }
+        BatchOperation::SetTags { tags } => {
+            source.set_object_tags(key, tags).await?;
+            done("")
+        }
+        BatchOperation::SetMetadata { metadata } => {
+            let mut headers = source.get_object_headers(key).await?;
+            headers.metadata.extend(metadata.clone());
+            source.update_object_metadata(key, &headers).await?;
+            done("")
+        }
+        BatchOperation::Restore { days, tier } => {
+            if source.restore_object(key, *days, *tier).await? {
+                done("")
+            } else {
+                Ok((
+                    AuditOutcome::Skipped,
+                    "Restore already in progress".to_string(),
+                    Vec::new(),
+                ))
+            }
+        }
+    }
+}
+
+/// The per-key result report of a batch job, written as results come in so an interrupted
+/// job still leaves a record of what was done
+pub struct BatchReport {
+    path: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl BatchReport {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(|e| write_error(path, e))?;
+        let mut report = Self {
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+        };
+        report.write(&csv_format::row(&["key", "status", "detail"]))?;
+        Ok(report)
+    }
+
+    pub fn add(&mut self, result: &KeyResult) -> Result<()> {
+        // Enums serialize as plain strings, e.g. "Success"
+        let outcome = serde_json::to_value(result.outcome)?;
+        let row = csv_format::row(&[
+            result.key.as_str(),
+            outcome.as_str().unwrap_or_default(),
+            result.detail.as_str(),
+        ]);
+        self.write(&row)
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush().map_err(|e| write_error(&self.path, e))
+    }
+
+    fn write(&mut self, text: &str) -> Result<()> {
+        self.out
+            .write_all(text.as_bytes())
+            .map_err(|e| write_error(&self.path, e))
+    }
+}
+
+fn write_error(path: &Path, e: std::io::Error) -> S3DeckError {
+    S3DeckError::Io(format!(
+        "Failed to write batch report '{}': {}",
+        path.display(),
+        e
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BucketConfig, OverwritePolicy, RestoreTier};
+    use crate::test_support::{direct_network, FakeS3};
+    use std::collections::HashMap;
+
+    fn job(
+        client: &Arc<S3Client>,
+        operation: BatchOperation,
+        confirmation: Option<&str>,
+    ) -> Arc<BatchJob> {
+        Arc::new(BatchJob {
+            source: client.clone(),
+            destination: client.clone(),
+            operation,
+            confirmation: confirmation.map(str::to_string),
+        })
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("s3deck-batch-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_read_manifest_layouts() {
+        let dir = temp_dir();
+
+        let text = dir.join("keys.txt");
+        fs::write(&text, "data/a.csv\r\n\ndata/b c.csv\ndata/a.csv\n").unwrap();
+        assert_eq!(
+            read_manifest(&text).unwrap(),
+            vec!["data/a.csv", "data/b c.csv"]
+        );
+
+        let export = dir.join("export.csv");
+        fs::write(
+            &export,
+            "size,key,etag\n5,\"data/a, b.csv\",abc\n7,data/c.csv,def\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_manifest(&export).unwrap(),
+            vec!["data/a, b.csv", "data/c.csv"]
+        );
+
+        let batch_operations = dir.join("manifest.csv");
+        fs::write(
+            &batch_operations,
+            "my-bucket,data/b%20c.csv\nmy-bucket,data/d.csv,3HL4kqtJlcpXroDTDmjVBH40Nrjfkd\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_manifest(&batch_operations).unwrap(),
+            vec!["data/b c.csv", "data/d.csv"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_operations() {
+        let fake = FakeS3::start().await;
+        for key in ["data/a.csv", "data/b.csv", "data/c.csv"] {
+            fake.put(key, key.as_bytes());
+        }
+        fake.put("copies/data/b.csv", b"already here");
+        let client = Arc::new(fake.client().await);
+        let keys = vec![
+            "data/a.csv".to_string(),
+            "data/b.csv".to_string(),
+            "data/c.csv".to_string(),
+        ];
+        let not_cancelled = AtomicBool::new(false);
+
+        let run_operation = |operation: BatchOperation, keys: Vec<String>| {
+            let job = job(&client, operation, None);
+            let not_cancelled = &not_cancelled;
+            async move {
+                let mut results = Vec::new();
+                let completed = run(job, keys, 2, not_cancelled, |result| {
+                    results.push(result);
+                    Ok(())
+                })
+                .await
+                .unwrap();
+                assert!(completed);
+                results.sort_by(|a, b| a.key.cmp(&b.key));
+                results
+            }
+        };
+
+        let copy = BatchOperation::Copy {
+            destination_bucket_id: None,
+            destination_prefix: "copies/".to_string(),
+            overwrite: OverwritePolicy::Skip,
+        };
+        let results = run_operation(copy, keys.clone()).await;
+        let outcomes: Vec<AuditOutcome> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                AuditOutcome::Success,
+                AuditOutcome::Skipped,
+                AuditOutcome::Success
+            ]
+        );
+        assert_eq!(results[0].detail, "copies/data/a.csv");
+        assert_eq!(fake.get("copies/data/a.csv").unwrap().body, b"data/a.csv");
+        assert_eq!(fake.get("copies/data/b.csv").unwrap().body, b"already here");
+
+        let tags = HashMap::from([("project".to_string(), "delivery".to_string())]);
+        run_operation(BatchOperation::SetTags { tags }, keys.clone()).await;
+        assert_eq!(
+            fake.get("data/c.csv").unwrap().tags,
+            vec![("project".to_string(), "delivery".to_string())]
+        );
+
+        let metadata = HashMap::from([("ticket".to_string(), "T-42".to_string())]);
+        run_operation(BatchOperation::SetMetadata { metadata }, keys.clone()).await;
+        let storage_class = BatchOperation::SetStorageClass {
+            storage_class: "GLACIER".to_string(),
+        };
+        run_operation(storage_class, keys.clone()).await;
+        let headers = fake.get("data/a.csv").unwrap().headers;
+        assert!(headers.contains(&("x-amz-meta-ticket".to_string(), "T-42".to_string())));
+        assert!(headers.contains(&("x-amz-storage-class".to_string(), "GLACIER".to_string())));
+
+        let restore = BatchOperation::Restore {
+            days: 3,
+            tier: RestoreTier::Bulk,
+        };
+        let results = run_operation(restore, keys.clone()).await;
+        assert!(results.iter().all(|r| r.outcome == AuditOutcome::Success));
+
+        let results = run_operation(BatchOperation::Delete, keys.clone()).await;
+        assert!(results.iter().all(|r| r.outcome == AuditOutcome::Success));
+        assert_eq!(
+            fake.keys(),
+            vec![
+                "copies/data/a.csv",
+                "copies/data/b.csv",
+                "copies/data/c.csv"
+            ]
+        );
+
+        // Cancelled before anything started
+        let completed = run(
+            job(&client, BatchOperation::Delete, None),
+            vec!["copies/data/a.csv".to_string()],
+            2,
+            &AtomicBool::new(true),
+            |_| Ok(()),
+        )
+        .await
+        .unwrap();
+        assert!(!completed);
+        assert_eq!(fake.keys().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delete_archived_keys_with_confirmation() {
+        let fake = FakeS3::start().await;
+        fake.put("logs/2019.gz", b"old logs");
+        fake.set_storage_class("logs/2019.gz", "GLACIER");
+        let config = BucketConfig {
+            trash_enabled: true,
+            ..fake.bucket_config()
+        };
+        let client = Arc::new(S3Client::new(&config, &direct_network()).await.unwrap());
+
+        let delete = |confirmation: Option<&str>| {
+            let job = job(&client, BatchOperation::Delete, confirmation);
+            async move {
+                let mut results = Vec::new();
+                let keys = vec!["logs/2019.gz".to_string()];
+                run(job, keys, 2, &AtomicBool::new(false), |result| {
+                    results.push(result);
+                    Ok(())
+                })
+                .await
+                .unwrap();
+                results[0].outcome
+            }
+        };
+
+        // The trash can't take an archived object, so it needs the job's confirmation
+        assert_eq!(delete(None).await, AuditOutcome::Failed);
+        assert_eq!(fake.keys(), vec!["logs/2019.gz"]);
+        assert_eq!(delete(Some(&config.name)).await, AuditOutcome::Success);
+        assert!(fake.keys().is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::ConfigManager;
+use crate::models::{RegionMismatch, Result, S3DeckError, StorageProvider};
+use crate::providers;
+use crate::s3_client::S3Client;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+type ClientSlot = Arc<OnceCell<Arc<S3Client>>>;
+
+/// Keeps one S3 client per bucket configuration in Tauri state, so commands don't
+/// re-read config.json and reload the credential chain on every invocation
+#[derive(Default)]
+pub struct ClientCache {
+    clients: Mutex<HashMap<String, ClientSlot>>,
+    /// Buckets whose client runs in a different region than the stored config says
+    region_mismatches: Mutex<HashMap<String, RegionMismatch>>,
+}
+
+impl ClientCache {
+    /// Get the cached client for a bucket, building it on first use
+    pub async fn get(&self, bucket_id: &str) -> Result<Arc<S3Client>> {
+        // Each bucket gets its own slot so a slow SSO login for one bucket
+        // doesn't block commands for the others
+        let slot = self
+            .clients
+            .lock()
+            .unwrap()
+            .entry(bucket_id.to_string())
+            .or_default()
+            .clone();
+
+        let client = slot
+            .get_or_try_init(|| async {
+                let config_manager = ConfigManager::new()?;
+                let mut bucket_config = config_manager.get_bucket(bucket_id)?;
+                let network = config_manager.get_network_settings()?;
+                let client = S3Client::new(&bucket_config, &network).await?;
+
+                // A wrong region only shows up as opaque redirects later, so check it once per
+                // client and follow S3 to the right one
+                if providers::provider_for(&bucket_config) != StorageProvider::Aws {
+                    return Ok(Arc::new(client));
+                }
+                match client.detect_region().await {
+                    Ok(Some(region)) if region != bucket_config.region => {
+                        self.region_mismatches.lock().unwrap().insert(
+                            bucket_id.to_string(),
+                            RegionMismatch {
+                                bucket_id: bucket_id.to_string(),
+                                configured_region: bucket_config.region.clone(),
+                                detected_region: region.clone(),
+                            },
+                        );
+                        bucket_config.region = region;
+                        S3Client::new(&bucket_config, &network).await.map(Arc::new)
+                    }
+                    Ok(Some(_)) => {
+                        self.region_mismatches.lock().unwrap().remove(bucket_id);
+                        Ok(Arc::new(client))
+                    }
+                    // Detection needs network access and permissions; the operation itself
+                    // will report any real problem
+                    _ => Ok(Arc::new(client)),
+                }
+            })
+            .await?;
+
+        Ok(client.clone())
+    }
+
+    /// Drop the cached client so the next command rebuilds it from the stored config
+    pub fn invalidate(&self, bucket_id: &str) {
+        self.clients.lock().unwrap().remove(bucket_id);
+    }
+
+    /// Buckets currently being accessed in a different region than the stored one
+    pub fn region_mismatches(&self) -> Vec<RegionMismatch> {
+        self.region_mismatches
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Forget a recorded mismatch, e.g. once the stored region has been corrected
+    pub fn clear_region_mismatch(&self, bucket_id: &str) {
+        self.region_mismatches.lock().unwrap().remove(bucket_id);
+    }
+
+    /// Drop every cached client, e.g. after the global network settings change
+    pub fn invalidate_all(&self) {
+        self.clients.lock().unwrap().clear();
+    }
+
+    /// Invalidate the client when a result shows its credentials expired or the bucket moved
+    /// region, without retrying. Used for operations that change the bucket and are not safe
+    /// to replay.
+    pub fn check<T>(&self, bucket_id: &str, result: Result<T>) -> Result<T> {
+        if let Err(
+            S3DeckError::ExpiredToken(_)
+            | S3DeckError::Credentials(_)
+            | S3DeckError::WrongRegion(_),
+        ) = &result
+        {
+            self.invalidate(bucket_id);
+        }
+        result
+    }
+
+    /// Run a read-only operation with the bucket's client, rebuilding the client once if its
+    /// credentials expired or S3 redirected it to another region
+    pub async fn run<T, F, Fut>(&self, bucket_id: &str, operation: F) -> Result<T>
+    where
+        F: Fn(Arc<S3Client>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let client = self.get(bucket_id).await?;
+
+        match operation(client).await {
+            Err(
+                S3DeckError::ExpiredToken(_)
+                | S3DeckError::Credentials(_)
+                | S3DeckError::WrongRegion(_),
+            ) => {
+                // Credentials captured when the client was built may have been refreshed
+                // on disk since (e.g. `aws sso login`), so start from a fresh credential chain.
+                // The rebuilt client also re-detects the bucket's region.
+                self.invalidate(bucket_id);
+                let client = self.get(bucket_id).await?;
+                operation(client).await
+            }
+            result => result,
+        }
+    }
+}
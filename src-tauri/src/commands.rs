@@ -1,18 +1,45 @@
 use crate::{
+    audit::{self, AuditLog},
     aws_profiles::AwsProfileManager,
+    batch::{self, BatchJob, BatchReport},
+    client_cache::ClientCache,
     config::ConfigManager,
     content_type::validate_filename,
+    cost,
+    duplicates::{self, DuplicateFinder},
+    export::{self, ListingWriter},
+    history::{self, OperationHistory},
+    index::{self, MetadataIndex},
+    inventory::{self, InventoryManifest},
+    jobs::JobManager,
     models::{
-        AwsProfile, BucketConfig, DeleteResponse, FileItem, ObjectMetadata, ProfileBucket,
-        ProfileStatus, RenameRequest, RenameResponse, Result, S3DeckError, UploadFileInfo,
-        UploadRequest, UploadResponse,
+        ArchiveRestoreRequest, ArchiveRestoreResponse, AuditAction, AuditEntry, AuditOutcome,
+        AuditQuery, AwsProfile, BatchJobProgress, BatchJobRequest, BatchJobSummary, BatchOperation,
+        BucketConfig, CopyResponse, CostEstimate, DeleteResponse, DuplicateAction, DuplicateReport,
+        DuplicateScanRequest, FileItem, FolderStats, FolderStatsProgress, HistoryEntry,
+        IndexStatus, IndexedListing, InventoryImportProgress, InventoryImportRequest, JobInfo,
+        JobKind, ListingExportRequest, ListingExportSummary, NetworkSettings, ObjectHeaders,
+        ObjectMetadata, ObjectSummary, OverwritePolicy, PendingRestore, PricingTable,
+        ProfileBucket, ProfileDetails, ProfileStatus, RegionMismatch, RenameRequest,
+        RenameResponse, ResolveDuplicatesRequest, ResolveDuplicatesResponse, RestoreResponse,
+        RestoreStatus, Result, ReversibleOperation, S3DeckError, S3ErrorInfo, SearchRequest,
+        SearchSummary, StorageProvider, TierTransition, TrashItem, UndoResponse, UploadFileInfo,
+        UploadRequest, UploadResponse, UsageReport, UsageReportRequest,
     },
-    s3_client::S3Client,
+    providers::{self, ProviderPreset},
+    restore::{self, RestoreTracker},
+    s3_client::{detect_region_for, normalize_root_prefix, S3Client, WriteOutcome, TRASH_FOLDER},
+    safeguards::ProtectedPrefixes,
+    search::{SearchFilter, SearchJobs},
+    stats::{FolderStatsBuilder, FolderStatsCache},
+    usage::{self, UsageReportBuilder},
 };
 use std::future::Future;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use tauri::command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{command, ipc::Channel, State};
 
 // Config management commands
 #[command]
@@ -23,20 +50,125 @@ pub async fn get_buckets() -> Result<Vec<BucketConfig>> {
 
 #[command]
 pub async fn add_bucket(bucket: BucketConfig) -> Result<Vec<BucketConfig>> {
-    let config_manager = ConfigManager::new()?;
-    config_manager.add_bucket(bucket)
+    let bucket_name = bucket.name.clone();
+    let result = async {
+        let config_manager = ConfigManager::new()?;
+        let bucket = validate_bucket(&config_manager, bucket).await?;
+        config_manager.add_bucket(bucket)
+    }
+    .await;
+
+    // The ID is only assigned when the bucket is saved
+    let bucket_id = result.as_ref().ok().and_then(|buckets| buckets.last());
+    audit::record(vec![AuditEntry::new(
+        AuditAction::AddBucket,
+        bucket_id.map(|b| b.id.as_str()),
+        Vec::new(),
+    )
+    .bucket_name(&bucket_name)
+    .result(&result)]);
+    result
+}
+
+#[command]
+pub async fn update_bucket(
+    clients: State<'_, ClientCache>,
+    bucket: BucketConfig,
+) -> Result<Vec<BucketConfig>> {
+    let entry = AuditEntry::new(AuditAction::UpdateBucket, Some(&bucket.id), Vec::new())
+        .bucket_name(&bucket.name);
+    let bucket_id = bucket.id.clone();
+    let result = async {
+        let config_manager = ConfigManager::new()?;
+        let bucket = validate_bucket(&config_manager, bucket).await?;
+        config_manager.update_bucket(bucket)
+    }
+    .await;
+    audit::record(vec![entry.result(&result)]);
+
+    let buckets = result?;
+    clients.invalidate(&bucket_id);
+    clients.clear_region_mismatch(&bucket_id);
+    Ok(buckets)
+}
+
+/// Ask S3 which region a bucket lives in, using the (possibly unsaved) bucket's credentials
+#[command]
+pub async fn detect_bucket_region(bucket: BucketConfig) -> Result<String> {
+    let network = ConfigManager::new()?.get_network_settings()?;
+
+    // Other providers don't report regions; their preset already decides it
+    if providers::provider_for(&bucket) != StorageProvider::Aws {
+        return Ok(providers::resolve(&bucket)?.region);
+    }
+
+    detect_region_for(&bucket, &network).await?.ok_or_else(|| {
+        S3DeckError::S3(format!(
+            "S3 did not report a region for bucket '{}'",
+            bucket.name
+        ))
+    })
 }
 
 #[command]
-pub async fn update_bucket(bucket: BucketConfig) -> Result<Vec<BucketConfig>> {
+pub async fn get_region_mismatches(clients: State<'_, ClientCache>) -> Result<Vec<RegionMismatch>> {
+    Ok(clients.region_mismatches())
+}
+
+/// Store the region S3 reported for a bucket in place of the configured one
+#[command]
+pub async fn fix_bucket_region(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+) -> Result<Vec<BucketConfig>> {
+    let mismatch = clients
+        .region_mismatches()
+        .into_iter()
+        .find(|m| m.bucket_id == bucket_id)
+        .ok_or_else(|| {
+            S3DeckError::Config(format!(
+                "No region mismatch recorded for bucket {}",
+                bucket_id
+            ))
+        })?;
+
     let config_manager = ConfigManager::new()?;
-    config_manager.update_bucket(bucket)
+    let mut bucket = config_manager.get_bucket(&bucket_id)?;
+    let entry =
+        AuditEntry::new(AuditAction::UpdateBucket, Some(&bucket_id), Vec::new()).message(format!(
+            "Region changed from '{}' to '{}'",
+            mismatch.configured_region, mismatch.detected_region
+        ));
+    bucket.region = mismatch.detected_region;
+    let result = config_manager.update_bucket(bucket);
+    audit::record(vec![entry.result(&result)]);
+    let buckets = result?;
+
+    clients.clear_region_mismatch(&bucket_id);
+    clients.invalidate(&bucket_id);
+    Ok(buckets)
 }
 
 #[command]
-pub async fn delete_bucket_config(bucket_id: String) -> Result<Vec<BucketConfig>> {
+pub async fn delete_bucket_config(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+) -> Result<Vec<BucketConfig>> {
     let config_manager = ConfigManager::new()?;
-    config_manager.delete_bucket(&bucket_id)
+    let mut entry = AuditEntry::new(AuditAction::DeleteBucket, Some(&bucket_id), Vec::new());
+    // Look the name up while the bucket still exists
+    if let Ok(bucket) = config_manager.get_bucket(&bucket_id) {
+        entry = entry.bucket_name(&bucket.name);
+    }
+    let result = config_manager.delete_bucket(&bucket_id);
+    audit::record(vec![entry.result(&result)]);
+
+    let buckets = result?;
+    clients.invalidate(&bucket_id);
+    if let Err(e) = MetadataIndex::delete(&bucket_id) {
+        eprintln!("Failed to delete index of bucket {}: {}", bucket_id, e);
+    }
+    Ok(buckets)
 }
 
 #[command]
@@ -45,57 +177,241 @@ pub async fn get_bucket(bucket_id: String) -> Result<BucketConfig> {
     config_manager.get_bucket(&bucket_id)
 }
 
-// S3 operations commands
 #[command]
-pub async fn list_objects(bucket_id: String, prefix: Option<String>) -> Result<Vec<FileItem>> {
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&bucket_id)?;
-
-    let s3_client = S3Client::new(&bucket_config).await?;
-    s3_client.list_objects(prefix.as_deref()).await
+pub async fn get_provider_presets() -> Result<Vec<ProviderPreset>> {
+    Ok(providers::all_presets())
 }
 
 #[command]
-pub async fn delete_object(bucket_id: String, key: String) -> Result<DeleteResponse> {
+pub async fn get_network_settings() -> Result<NetworkSettings> {
     let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&bucket_id)?;
+    config_manager.get_network_settings()
+}
 
-    let s3_client = S3Client::new(&bucket_config).await?;
+#[command]
+pub async fn update_network_settings(
+    clients: State<'_, ClientCache>,
+    network: NetworkSettings,
+) -> Result<NetworkSettings> {
+    let config_manager = ConfigManager::new()?;
+    let result = config_manager.update_network_settings(network);
+    audit::record(vec![AuditEntry::new(
+        AuditAction::UpdateNetworkSettings,
+        None,
+        Vec::new(),
+    )
+    .result(&result)]);
+    let network = result?;
+    // Every bucket without its own overrides uses these settings
+    clients.invalidate_all();
+    Ok(network)
+}
 
-    if key.ends_with('/') {
-        // It's a folder
-        let count = s3_client.delete_folder(&key).await?;
-        Ok(DeleteResponse {
-            message: format!("Deleted folder and {} objects", count),
-            key,
-            count: Some(count),
-        })
-    } else {
-        // It's a file
-        s3_client.delete_object(&key).await?;
-        Ok(DeleteResponse {
-            message: "Object deleted successfully".to_string(),
-            key,
-            count: None,
+// S3 operations commands
+#[command]
+pub async fn list_objects(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    prefix: Option<String>,
+) -> Result<Vec<FileItem>> {
+    let prefix = prefix.as_deref();
+    let listed_at = chrono::Utc::now();
+    let (level, items, complete) = clients
+        .run(&bucket_id, |s3_client| async move {
+            let level = s3_client.scoped_prefix(prefix)?.to_string();
+            let (items, complete) = s3_client.list_objects(Some(&level)).await?;
+            Ok((level, items, complete))
         })
+        .await?;
+
+    // Browsing keeps the visited levels of an index fresh
+    let (index_bucket, listed) = (bucket_id.clone(), items.clone());
+    let refreshed = index::blocking(move || match MetadataIndex::open_existing(&index_bucket)? {
+        Some(index) => index.record_level(&level, &listed, complete, listed_at),
+        None => Ok(()),
+    })
+    .await;
+    if let Err(e) = refreshed {
+        eprintln!("Failed to refresh index of bucket {}: {}", bucket_id, e);
     }
+
+    Ok(items)
 }
 
 #[command]
-pub async fn get_object_metadata(bucket_id: String, key: String) -> Result<ObjectMetadata> {
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&bucket_id)?;
+pub async fn delete_object(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+    bucket_id: String,
+    key: String,
+    confirmation: Option<String>,
+) -> Result<DeleteResponse> {
+    let is_folder = key.ends_with('/');
+    let result = async {
+        let s3_client = clients.get(&bucket_id).await?;
+        s3_client.ensure_confirmed(&key, confirmation.as_deref())?;
+        if is_folder {
+            s3_client.delete_folder(&key, confirmation.as_deref()).await
+        } else {
+            s3_client.delete_object(&key, confirmation.as_deref()).await
+        }
+    }
+    .await;
+
+    let entry =
+        |keys: &[String]| AuditEntry::new(AuditAction::Delete, Some(&bucket_id), keys.to_vec());
+    let result = result.map(|deletion| {
+        let trashed = !deletion.trashed.is_empty();
+        let message = match (is_folder, trashed) {
+            (true, true) => format!("Moved folder and {} objects to the trash", deletion.count),
+            (true, false) => format!("Deleted folder and {} objects", deletion.count),
+            (false, true) => "Object moved to the trash".to_string(),
+            (false, false) => "Object deleted successfully".to_string(),
+        };
+
+        // Every deleted key is recorded, in chunks for large folders, and so is every key
+        // that was left behind
+        let mut entries: Vec<AuditEntry> = deletion
+            .keys
+            .chunks(batch::AUDIT_CHUNK)
+            .map(|keys| entry(keys).message(&message))
+            .collect();
+        if entries.is_empty() {
+            entries.push(entry(std::slice::from_ref(&key)).message(&message));
+        }
+        entries.extend(
+            deletion
+                .failed
+                .iter()
+                .map(|(failed_key, error)| entry(std::slice::from_ref(failed_key)).failed(error)),
+        );
+        audit::record(entries);
+
+        // Only deletes the trash can bring back are undoable
+        if trashed {
+            history.record(
+                &bucket_id,
+                format!("Delete '{}'", key),
+                ReversibleOperation::Delete {
+                    items: deletion.trashed,
+                },
+            );
+        }
+
+        DeleteResponse {
+            message,
+            key: key.clone(),
+            count: is_folder.then_some(deletion.count),
+            trashed,
+        }
+    });
+    // Failures are recorded too, including refusals such as a missing confirmation
+    if let Err(e) = &result {
+        audit::record(vec![entry(std::slice::from_ref(&key)).failed(e)]);
+    }
+
+    clients.check(&bucket_id, result)
+}
+
+#[command]
+pub async fn get_object_metadata(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    key: String,
+) -> Result<ObjectMetadata> {
+    let key = key.as_str();
+    clients
+        .run(&bucket_id, |s3_client| async move {
+            s3_client.get_object_metadata(key).await
+        })
+        .await
+}
+
+/// Replace an object's content type, caching headers and user metadata
+#[command]
+pub async fn update_object_metadata(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+    bucket_id: String,
+    key: String,
+    headers: ObjectHeaders,
+) -> Result<ObjectMetadata> {
+    let s3_client = clients.get(&bucket_id).await?;
+    let result = s3_client.update_object_metadata(&key, &headers).await;
+
+    let entry = AuditEntry::new(
+        AuditAction::UpdateMetadata,
+        Some(&bucket_id),
+        vec![key.clone()],
+    );
+    audit::record(vec![entry.result(&result)]);
+
+    let previous = clients.check(&bucket_id, result)?;
+    history.record(
+        &bucket_id,
+        format!("Edit metadata of '{}'", key),
+        ReversibleOperation::MetadataEdit {
+            key: key.clone(),
+            previous,
+        },
+    );
 
-    let s3_client = S3Client::new(&bucket_config).await?;
     s3_client.get_object_metadata(&key).await
 }
 
 #[command]
-pub async fn upload_files(request: UploadRequest) -> Result<UploadResponse> {
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&request.bucket)?;
+pub async fn upload_files(
+    clients: State<'_, ClientCache>,
+    request: UploadRequest,
+) -> Result<UploadResponse> {
+    let result = upload_to_bucket(&clients, &request).await;
+
+    let entries = match &result {
+        Ok(response) => response
+            .uploaded_files
+            .iter()
+            .chain(&response.failed_files)
+            .map(|file| {
+                let entry = AuditEntry::new(
+                    AuditAction::Upload,
+                    Some(&request.bucket),
+                    vec![file.key.clone()],
+                );
+                match (file.status.as_str(), &file.error) {
+                    ("skipped", _) => entry.skipped(),
+                    (_, Some(error)) => entry.failed(error),
+                    _ => entry.bytes(file.size),
+                }
+            })
+            .collect(),
+        // Refused before any file was tried, e.g. on a read-only bucket
+        Err(e) => vec![AuditEntry::new(
+            AuditAction::Upload,
+            Some(&request.bucket),
+            request.files.clone(),
+        )
+        .failed(e)],
+    };
+    audit::record(entries);
 
-    let s3_client = S3Client::new(&bucket_config).await?;
+    result
+}
+
+async fn upload_to_bucket(
+    clients: &ClientCache,
+    request: &UploadRequest,
+) -> Result<UploadResponse> {
+    let s3_client = clients.get(&request.bucket).await?;
+    s3_client.ensure_writable()?;
+    let current_path = s3_client.scoped_key(&request.current_path);
+    if request.overwrite == OverwritePolicy::Overwrite {
+        // Uploads may land anywhere below the current folder, so protect the folder as a whole
+        let target_folder = match current_path.as_str() {
+            "" => String::new(),
+            path => format!("{}/", path.trim_end_matches('/')),
+        };
+        s3_client.ensure_confirmed(&target_folder, request.confirmation.as_deref())?;
+    }
 
     let mut uploaded_files = Vec::new();
     let mut failed_files = Vec::new();
@@ -118,8 +434,9 @@ pub async fn upload_files(request: UploadRequest) -> Result<UploadResponse> {
             match upload_directory(
                 &s3_client,
                 &request.base_path,
-                &request.current_path,
+                &current_path,
                 file_path,
+                request.overwrite,
             )
             .await
             {
@@ -138,16 +455,14 @@ pub async fn upload_files(request: UploadRequest) -> Result<UploadResponse> {
             }
         } else {
             // Handle single file
-            let s3_key = build_s3_key(&request.base_path, &request.current_path, file_path)?;
+            let s3_key = build_s3_key(&request.base_path, &current_path, file_path)?;
 
-            match s3_client.upload_file(&s3_key, path).await {
-                Ok(size) => {
-                    uploaded_files.push(UploadFileInfo {
-                        key: s3_key,
-                        size,
-                        status: "completed".to_string(),
-                        error: None,
-                    });
+            match s3_client
+                .retry_object(|| s3_client.upload_file(&s3_key, path, request.overwrite))
+                .await
+            {
+                Ok((outcome, size)) => {
+                    uploaded_files.push(uploaded_file_info(s3_key, outcome, size));
                 }
                 Err(e) => {
                     failed_files.push(UploadFileInfo {
@@ -162,12 +477,21 @@ pub async fn upload_files(request: UploadRequest) -> Result<UploadResponse> {
     }
 
     let total_files = request.files.len();
-    let success_count = uploaded_files.len();
+    let skipped_count = uploaded_files
+        .iter()
+        .filter(|f| f.status == "skipped")
+        .count();
+    let success_count = uploaded_files.len() - skipped_count;
     let failed_count = failed_files.len();
 
-    let message = if failed_count == 0 {
+    let message = if failed_count == 0 && skipped_count > 0 {
+        format!(
+            "Uploaded {} file(s), {} skipped because they already exist",
+            success_count, skipped_count
+        )
+    } else if failed_count == 0 {
         format!("Successfully uploaded {} file(s)", success_count)
-    } else if success_count == 0 {
+    } else if success_count == 0 && skipped_count == 0 {
         format!("Failed to upload all {} file(s)", failed_count)
     } else {
         format!(
@@ -202,6 +526,57 @@ pub async fn count_files(files: Vec<String>) -> Result<usize> {
 }
 
 // Utility functions
+/// Normalize the root prefix, check the protected prefix globs, fill in an empty region from S3
+/// and reject one S3 says is wrong. Region detection needs network access and permissions, so a
+/// bucket is still saved as typed when it fails.
+async fn validate_bucket(
+    config_manager: &ConfigManager,
+    mut bucket: BucketConfig,
+) -> Result<BucketConfig> {
+    let root_prefix = normalize_root_prefix(bucket.root_prefix.as_deref())?;
+    bucket.root_prefix = Some(root_prefix).filter(|p| !p.is_empty());
+    ProtectedPrefixes::new(&bucket.protected_prefixes)?;
+
+    if providers::provider_for(&bucket) != StorageProvider::Aws {
+        return Ok(bucket);
+    }
+
+    let network = config_manager.get_network_settings()?;
+    match detect_region_for(&bucket, &network).await {
+        Ok(Some(region)) if bucket.region.trim().is_empty() => bucket.region = region,
+        Ok(Some(region)) if region != bucket.region => {
+            return Err(S3DeckError::WrongRegion(S3ErrorInfo {
+                message: format!(
+                    "Bucket '{}' is in region '{}', not '{}'",
+                    bucket.name, region, bucket.region
+                ),
+                bucket_region: Some(region),
+                ..Default::default()
+            }));
+        }
+        _ => {}
+    }
+
+    Ok(bucket)
+}
+
+fn uploaded_file_info(requested_key: String, outcome: WriteOutcome, size: i64) -> UploadFileInfo {
+    match outcome {
+        WriteOutcome::Written(key) => UploadFileInfo {
+            key,
+            size,
+            status: "completed".to_string(),
+            error: None,
+        },
+        WriteOutcome::Skipped => UploadFileInfo {
+            key: requested_key,
+            size: 0,
+            status: "skipped".to_string(),
+            error: None,
+        },
+    }
+}
+
 fn build_s3_key(base_path: &str, current_path: &str, file_path: &str) -> Result<String> {
     let path = Path::new(file_path);
     let file_name = path
@@ -260,64 +635,265 @@ fn count_files_in_directory(dir: &Path) -> Result<usize> {
     Ok(count)
 }
 
+/// Folders crawled into the index more recently than this are answered from it, as the
+/// answer isn't marked as coming from the index
+const INDEX_MAX_AGE: chrono::TimeDelta = chrono::TimeDelta::hours(1);
+
 #[command]
 pub async fn get_folder_latest_modified(
+    clients: State<'_, ClientCache>,
     bucket_id: String,
     folder_key: String,
 ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&bucket_id)?;
+    // Recently crawled folders are answered from the index instead of walking them again
+    let (index_bucket, index_folder) = (bucket_id.clone(), folder_key.clone());
+    let indexed = index::blocking(move || {
+        let Some(index) = MetadataIndex::open_existing(&index_bucket)? else {
+            return Ok(None);
+        };
+        let fresh = index
+            .crawled_at(&index_folder)?
+            .is_some_and(|crawled_at| chrono::Utc::now() - crawled_at < INDEX_MAX_AGE);
+        if !fresh {
+            return Ok(None);
+        }
+        index.latest_modified(&index_folder).map(Some)
+    })
+    .await;
+    match indexed {
+        Ok(Some(latest_modified)) => return Ok(latest_modified),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to read index of bucket {}: {}", bucket_id, e),
+    }
 
-    let s3_client = S3Client::new(&bucket_config).await?;
-    s3_client.get_folder_latest_modified(&folder_key).await
+    let folder_key = folder_key.as_str();
+    clients
+        .run(&bucket_id, |s3_client| async move {
+            s3_client.get_folder_latest_modified(folder_key).await
+        })
+        .await
 }
 
+/// Size, object count, storage classes and extremes of everything below a folder. Results are
+/// cached for a few minutes unless `refresh` is set; `on_progress` gets running totals while a
+/// folder is walked.
 #[command]
-pub async fn create_folder(bucket_id: String, folder_path: String) -> Result<String> {
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&bucket_id)?;
+pub async fn get_folder_stats(
+    clients: State<'_, ClientCache>,
+    stats_cache: State<'_, FolderStatsCache>,
+    bucket_id: String,
+    folder_key: Option<String>,
+    refresh: bool,
+    on_progress: Channel<FolderStatsProgress>,
+) -> Result<FolderStats> {
+    let s3_client = clients.get(&bucket_id).await?;
+    let prefix = s3_client.scoped_prefix(folder_key.as_deref())?.to_string();
+    if !refresh {
+        if let Some(stats) = stats_cache.get(&bucket_id, &prefix) {
+            return Ok(stats);
+        }
+    }
+
+    let mut builder = FolderStatsBuilder::default();
+    let not_cancelled = std::sync::atomic::AtomicBool::new(false);
+    s3_client
+        .walk_objects(Some(&prefix), &not_cancelled, |page| {
+            builder.add_page(&page);
+            // Progress is informational, so a closed window doesn't stop the walk
+            let _ = on_progress.send(builder.progress());
+        })
+        .await?;
 
-    let s3_client = S3Client::new(&bucket_config).await?;
-    s3_client.create_folder(&folder_path).await
+    let stats = builder.finish(&prefix);
+    stats_cache.insert(&bucket_id, stats.clone());
+    Ok(stats)
 }
 
 #[command]
-pub async fn rename_object(request: RenameRequest) -> Result<RenameResponse> {
-    // Validate the new filename
-    let new_name = if request.is_folder {
-        // For folders, just validate the folder name
-        std::path::Path::new(&request.new_key)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(&request.new_key)
-    } else {
-        // For files, validate the full filename including extension
-        std::path::Path::new(&request.new_key)
+pub async fn create_folder(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    folder_path: String,
+    overwrite: Option<OverwritePolicy>,
+) -> Result<String> {
+    let s3_client = clients.get(&bucket_id).await?;
+    let folder_path = s3_client.scoped_key(&folder_path);
+    let result = s3_client
+        .create_folder(&folder_path, overwrite.unwrap_or_default())
+        .await;
+
+    let entry = AuditEntry::new(
+        AuditAction::CreateFolder,
+        Some(&bucket_id),
+        vec![folder_path],
+    );
+    audit::record(vec![match &result {
+        Ok(message) => entry.message(message),
+        Err(e) => entry.failed(e),
+    }]);
+
+    clients.check(&bucket_id, result)
+}
+
+#[command]
+pub async fn copy_object(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    source_key: String,
+    destination_key: String,
+    overwrite: Option<OverwritePolicy>,
+    confirmation: Option<String>,
+) -> Result<CopyResponse> {
+    let overwrite = overwrite.unwrap_or_default();
+    let result = async {
+        let file_name = Path::new(&destination_key)
             .file_name()
             .and_then(|n| n.to_str())
-            .unwrap_or(&request.new_key)
+            .unwrap_or(&destination_key);
+
+        if !validate_filename(file_name) {
+            return Err(S3DeckError::InvalidPath(
+                "Invalid filename: contains invalid characters or reserved names".to_string(),
+            ));
+        }
+
+        if source_key == destination_key {
+            return Err(S3DeckError::InvalidPath(
+                "Destination must be different from the source".to_string(),
+            ));
+        }
+
+        let s3_client = clients.get(&bucket_id).await?;
+        if overwrite == OverwritePolicy::Overwrite {
+            s3_client.ensure_confirmed(&destination_key, confirmation.as_deref())?;
+        }
+        s3_client
+            .copy_object(&source_key, &destination_key, overwrite)
+            .await
+    }
+    .await;
+
+    let written_key = match &result {
+        Ok(WriteOutcome::Written(written_key)) => written_key,
+        _ => &destination_key,
     };
+    let entry = AuditEntry::new(
+        AuditAction::Copy,
+        Some(&bucket_id),
+        vec![source_key.clone(), written_key.clone()],
+    );
+    audit::record(vec![match &result {
+        Ok(WriteOutcome::Written(_)) => entry,
+        Ok(WriteOutcome::Skipped) => entry.skipped(),
+        Err(e) => entry.failed(e),
+    }]);
 
-    if !validate_filename(new_name) {
-        return Err(S3DeckError::InvalidPath(
-            "Invalid filename: contains invalid characters or reserved names".to_string(),
-        ));
+    match clients.check(&bucket_id, result)? {
+        WriteOutcome::Written(written_key) => Ok(CopyResponse {
+            message: format!("Copied '{}' to '{}'", source_key, written_key),
+            source_key,
+            destination_key: written_key,
+            skipped: false,
+        }),
+        WriteOutcome::Skipped => Ok(CopyResponse {
+            message: format!("Skipped '{}': destination already exists", source_key),
+            source_key,
+            destination_key,
+            skipped: true,
+        }),
     }
+}
 
-    // Check if old and new keys are the same
-    if request.old_key == request.new_key {
-        return Err(S3DeckError::InvalidPath(
-            "New name must be different from the current name".to_string(),
-        ));
+#[command]
+pub async fn rename_object(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+    request: RenameRequest,
+) -> Result<RenameResponse> {
+    let result = async {
+        // Validate the new filename
+        let new_name = if request.is_folder {
+            // For folders, just validate the folder name
+            std::path::Path::new(&request.new_key)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(&request.new_key)
+        } else {
+            // For files, validate the full filename including extension
+            std::path::Path::new(&request.new_key)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(&request.new_key)
+        };
+
+        if !validate_filename(new_name) {
+            return Err(S3DeckError::InvalidPath(
+                "Invalid filename: contains invalid characters or reserved names".to_string(),
+            ));
+        }
+
+        // Check if old and new keys are the same
+        if request.old_key == request.new_key {
+            return Err(S3DeckError::InvalidPath(
+                "New name must be different from the current name".to_string(),
+            ));
+        }
+
+        let s3_client = clients.get(&request.bucket_id).await?;
+        // A rename deletes the source, and overwriting replaces whatever is at the destination
+        let confirmation = request.confirmation.as_deref();
+        s3_client.ensure_confirmed(&request.old_key, confirmation)?;
+        if request.overwrite == OverwritePolicy::Overwrite {
+            s3_client.ensure_confirmed(&request.new_key, confirmation)?;
+        }
+        s3_client
+            .rename_object(
+                &request.old_key,
+                &request.new_key,
+                request.is_folder,
+                request.overwrite,
+            )
+            .await
     }
+    .await;
 
-    let config_manager = ConfigManager::new()?;
-    let bucket_config = config_manager.get_bucket(&request.bucket_id)?;
-    let s3_client = S3Client::new(&bucket_config).await?;
+    let entry = AuditEntry::new(
+        AuditAction::Rename,
+        Some(&request.bucket_id),
+        vec![request.old_key.clone(), request.new_key.clone()],
+    );
+    audit::record(vec![match &result {
+        Ok(response) => {
+            let entry = AuditEntry {
+                keys: vec![response.old_key.clone(), response.new_key.clone()],
+                ..entry.message(&response.message)
+            };
+            let skipped = response
+                .skipped_files
+                .as_ref()
+                .is_some_and(|skipped| !skipped.is_empty());
+            if skipped && response.total_moved.unwrap_or(0) == 0 {
+                entry.skipped()
+            } else {
+                entry
+            }
+        }
+        Err(e) => entry.failed(e),
+    }]);
 
-    s3_client
-        .rename_object(&request.old_key, &request.new_key, request.is_folder)
-        .await
+    if let Ok(response) = &result {
+        if !response.moved_objects.is_empty() {
+            history.record(
+                &request.bucket_id,
+                format!("Rename '{}' to '{}'", response.old_key, response.new_key),
+                ReversibleOperation::Rename {
+                    moves: response.moved_objects.clone(),
+                },
+            );
+        }
+    }
+
+    clients.check(&request.bucket_id, result)
 }
 
 // Helper function to recursively upload a directory
@@ -326,6 +902,7 @@ fn upload_directory<'a>(
     base_path: &'a str,
     current_path: &'a str,
     dir_path: &'a str,
+    overwrite: OverwritePolicy,
 ) -> Pin<Box<dyn Future<Output = Result<(Vec<UploadFileInfo>, Vec<UploadFileInfo>)>> + Send + 'a>> {
     Box::pin(async move {
         let mut uploaded_files = Vec::new();
@@ -342,14 +919,12 @@ fn upload_directory<'a>(
                     // Upload the file
                     let s3_key = build_s3_key(base_path, current_path, &path_str)?;
 
-                    match s3_client.upload_file(&s3_key, &path).await {
-                        Ok(size) => {
-                            uploaded_files.push(UploadFileInfo {
-                                key: s3_key,
-                                size,
-                                status: "completed".to_string(),
-                                error: None,
-                            });
+                    match s3_client
+                        .retry_object(|| s3_client.upload_file(&s3_key, &path, overwrite))
+                        .await
+                    {
+                        Ok((outcome, size)) => {
+                            uploaded_files.push(uploaded_file_info(s3_key, outcome, size));
                         }
                         Err(e) => {
                             failed_files.push(UploadFileInfo {
@@ -362,7 +937,9 @@ fn upload_directory<'a>(
                     }
                 } else if path.is_dir() {
                     // Recursively process subdirectory
-                    match upload_directory(s3_client, base_path, current_path, &path_str).await {
+                    match upload_directory(s3_client, base_path, current_path, &path_str, overwrite)
+                        .await
+                    {
                         Ok(mut dir_results) => {
                             uploaded_files.append(&mut dir_results.0);
                             failed_files.append(&mut dir_results.1);
@@ -389,6 +966,1207 @@ fn upload_directory<'a>(
     })
 }
 
+// Trash commands
+#[command]
+pub async fn list_trash(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+) -> Result<Vec<TrashItem>> {
+    clients
+        .run(&bucket_id, |s3_client| async move {
+            s3_client.list_trash().await
+        })
+        .await
+}
+
+/// Put trashed objects back. Restoring over a protected object that exists again at the
+/// original key needs the confirmation token, like any other overwrite.
+#[command]
+pub async fn restore_from_trash(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    items: Vec<TrashItem>,
+    overwrite: Option<OverwritePolicy>,
+    confirmation: Option<String>,
+) -> Result<RestoreResponse> {
+    let overwrite = overwrite.unwrap_or_default();
+    let checked = async {
+        let s3_client = clients.get(&bucket_id).await?;
+        s3_client.ensure_writable()?;
+        if overwrite == OverwritePolicy::Overwrite {
+            for item in &items {
+                if s3_client.object_exists(&item.key).await? {
+                    s3_client.ensure_confirmed(&item.key, confirmation.as_deref())?;
+                }
+            }
+        }
+        Ok(s3_client)
+    }
+    .await;
+    let s3_client = match checked {
+        Ok(s3_client) => s3_client,
+        Err(e) => {
+            let keys: Vec<String> = items.iter().map(|item| item.key.clone()).collect();
+            audit::record(
+                keys.chunks(batch::AUDIT_CHUNK)
+                    .map(|keys| {
+                        AuditEntry::new(AuditAction::Restore, Some(&bucket_id), keys.to_vec())
+                            .failed(&e)
+                    })
+                    .collect(),
+            );
+            return clients.check(&bucket_id, Err(e));
+        }
+    };
+
+    let mut restored = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+    let mut entries = Vec::new();
+
+    for item in &items {
+        let result = s3_client
+            .retry_object(|| s3_client.restore_trash_item(item, overwrite))
+            .await;
+        let entry = AuditEntry::new(
+            AuditAction::Restore,
+            Some(&bucket_id),
+            vec![item.key.clone()],
+        );
+        match result {
+            Ok(WriteOutcome::Written(key)) => {
+                restored.push(key);
+                entries.push(entry.bytes(item.size));
+            }
+            Ok(WriteOutcome::Skipped) => {
+                skipped.push(item.key.clone());
+                entries.push(entry.skipped());
+            }
+            Err(e) => {
+                eprintln!("Failed to restore {}: {}", item.key, e);
+                failed.push(item.key.clone());
+                entries.push(entry.failed(e));
+            }
+        }
+    }
+    audit::record(entries);
+
+    let message = if failed.is_empty() && skipped.is_empty() {
+        format!("Restored {} object(s)", restored.len())
+    } else if failed.is_empty() {
+        format!(
+            "Restored {} object(s), {} skipped because they already exist",
+            restored.len(),
+            skipped.len()
+        )
+    } else {
+        format!(
+            "Restored {} object(s), {} failed",
+            restored.len(),
+            failed.len()
+        )
+    };
+
+    Ok(RestoreResponse {
+        message,
+        restored,
+        skipped,
+        failed,
+    })
+}
+
+/// Permanently delete trashed objects older than the given number of days; 0 empties the trash.
+/// Trashed objects from protected prefixes need the confirmation token.
+#[command]
+pub async fn purge_trash(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    older_than_days: u32,
+    confirmation: Option<String>,
+) -> Result<i32> {
+    let result = async {
+        let s3_client = clients.get(&bucket_id).await?;
+        s3_client
+            .purge_trash(older_than_days, confirmation.as_deref())
+            .await
+    }
+    .await;
+
+    let entry = AuditEntry::new(AuditAction::PurgeTrash, Some(&bucket_id), Vec::new());
+    audit::record(vec![match &result {
+        Ok(count) => entry.message(format!(
+            "Purged {} object(s) deleted more than {} day(s) ago",
+            count, older_than_days
+        )),
+        Err(e) => entry.failed(e),
+    }]);
+
+    clients.check(&bucket_id, result)
+}
+
+// Usage report commands
+/// Break down the storage used below a prefix by sub-prefix, age, extension and storage class,
+/// from a live walk or from the local index. `on_progress` gets running totals.
+#[command]
+pub async fn analyze_usage(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    request: UsageReportRequest,
+    on_progress: Channel<FolderStatsProgress>,
+) -> Result<UsageReport> {
+    let depth = request.depth.unwrap_or(usage::DEFAULT_DEPTH);
+    let not_cancelled = std::sync::atomic::AtomicBool::new(false);
+
+    if request.use_index {
+        return index::blocking(move || {
+            let (index, prefix) = indexed_prefix(&bucket_id, request.prefix.as_deref())?;
+            let mut builder = UsageReportBuilder::new(&prefix, depth);
+            index.walk_objects(&prefix, &not_cancelled, |page| {
+                builder.add_page(&page);
+                let _ = on_progress.send(builder.progress());
+            })?;
+            let mut report = builder.finish(index.crawled_at(&prefix)?);
+            report.inventory_date = index.inventory_date(&prefix)?;
+            Ok(report)
+        })
+        .await;
+    }
+
+    let s3_client = clients.get(&bucket_id).await?;
+    let prefix = s3_client.scoped_prefix(request.prefix.as_deref())?;
+    let mut builder = UsageReportBuilder::new(prefix, depth);
+    s3_client
+        .walk_objects(Some(prefix), &not_cancelled, |page| {
+            builder.add_page(&page);
+            // Progress is informational, so a closed window doesn't stop the walk
+            let _ = on_progress.send(builder.progress());
+        })
+        .await?;
+    Ok(builder.finish(None))
+}
+
+/// Save a usage report as CSV when `destination` ends in `.csv`, or as JSON otherwise
+#[command]
+pub async fn export_usage_report(report: UsageReport, destination: String) -> Result<()> {
+    usage::export(&report, Path::new(&destination))
+}
+
+// Cost estimate commands
+/// The pricing table used for cost estimates: the user's edits, or the bundled AWS prices
+#[command]
+pub async fn get_pricing_table() -> Result<PricingTable> {
+    PricingTable::load()
+}
+
+#[command]
+pub async fn update_pricing_table(pricing: PricingTable) -> Result<PricingTable> {
+    pricing.save()?;
+    Ok(pricing)
+}
+
+#[command]
+pub async fn reset_pricing_table() -> Result<PricingTable> {
+    PricingTable::reset()
+}
+
+/// Estimate the monthly storage cost of a usage report at the bucket region's prices, with
+/// the savings `transitions` would bring
+#[command]
+pub async fn estimate_cost(
+    bucket_id: String,
+    report: UsageReport,
+    transitions: Vec<TierTransition>,
+) -> Result<CostEstimate> {
+    let bucket = ConfigManager::new()?.get_bucket(&bucket_id)?;
+    cost::estimate(
+        &report,
+        &PricingTable::load()?,
+        &bucket.region,
+        &transitions,
+    )
+}
+
+// Search commands
+/// Search below a prefix, streaming matches over `on_results` one listing page at a time.
+/// Resolves with the totals once the search finishes or is cancelled.
+#[command]
+pub async fn search_objects(
+    clients: State<'_, ClientCache>,
+    searches: State<'_, SearchJobs>,
+    bucket_id: String,
+    request: SearchRequest,
+    on_results: Channel<Vec<FileItem>>,
+) -> Result<SearchSummary> {
+    let filter = SearchFilter::new(&request)?;
+    let cancelled = searches.start(&request.search_id);
+    // The window listening for results is gone, so stop searching
+    let on_matches = {
+        let cancelled = cancelled.clone();
+        move |items| {
+            if on_results.send(items).is_err() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+    };
+
+    let result = if request.use_index {
+        let cancelled = cancelled.clone();
+        index::blocking(move || {
+            let (index, prefix) = indexed_prefix(&bucket_id, request.prefix.as_deref())?;
+            index.search(&prefix, &filter, &cancelled, on_matches)
+        })
+        .await
+    } else {
+        match clients.get(&bucket_id).await {
+            Ok(s3_client) => {
+                s3_client
+                    .search_objects(request.prefix.as_deref(), &filter, &cancelled, on_matches)
+                    .await
+            }
+            Err(e) => Err(e),
+        }
+    };
+    searches.finish(&request.search_id);
+
+    result
+}
+
+/// Stop a running search. Returns false when it already finished.
+#[command]
+pub async fn cancel_search(searches: State<'_, SearchJobs>, search_id: String) -> Result<bool> {
+    Ok(searches.cancel(&search_id))
+}
+
+// Job commands
+/// Batch jobs, listing exports, duplicate scans, archive restores and restore watches that are
+/// running or finished recently, most recently started first
+#[command]
+pub async fn list_jobs(jobs: State<'_, JobManager>) -> Result<Vec<JobInfo>> {
+    Ok(jobs.list())
+}
+
+/// Status of a job, `None` once it has been forgotten or when it never ran
+#[command]
+pub async fn get_job_status(
+    jobs: State<'_, JobManager>,
+    job_id: String,
+) -> Result<Option<JobInfo>> {
+    Ok(jobs.get(&job_id))
+}
+
+/// Stop a running job. Returns false when it already finished.
+#[command]
+pub async fn cancel_job(jobs: State<'_, JobManager>, job_id: String) -> Result<bool> {
+    Ok(jobs.cancel(&job_id))
+}
+
+// Duplicate commands
+/// Group the objects below a prefix that share a size and ETag, optionally confirmed by
+/// comparing checksums. `on_progress` gets running totals, and `cancel_job` with the scan id
+/// stops the scan early with the groups found so far.
+#[command]
+pub async fn find_duplicates(
+    clients: State<'_, ClientCache>,
+    jobs: State<'_, JobManager>,
+    bucket_id: String,
+    request: DuplicateScanRequest,
+    on_progress: Channel<FolderStatsProgress>,
+) -> Result<DuplicateReport> {
+    let cancelled = jobs.start(&request.scan_id, JobKind::DuplicateScan, Some(&bucket_id))?;
+    let result = scan_duplicates(&clients, &bucket_id, &request, &cancelled, on_progress).await;
+    jobs.finish(&request.scan_id, &result);
+    result
+}
+
+async fn scan_duplicates(
+    clients: &ClientCache,
+    bucket_id: &str,
+    request: &DuplicateScanRequest,
+    cancelled: &Arc<AtomicBool>,
+    on_progress: Channel<FolderStatsProgress>,
+) -> Result<DuplicateReport> {
+    let (finder, prefix, completed, index_dates) = if request.use_index {
+        let (bucket_id, prefix) = (bucket_id.to_string(), request.prefix.clone());
+        let cancelled = cancelled.clone();
+        index::blocking(move || {
+            let (index, prefix) = indexed_prefix(&bucket_id, prefix.as_deref())?;
+            let mut finder = DuplicateFinder::default();
+            let completed = index.walk_objects(&prefix, &cancelled, |page| {
+                finder.add_page(&page);
+                let _ = on_progress.send(finder.progress());
+            })?;
+            let index_dates = (index.crawled_at(&prefix)?, index.inventory_date(&prefix)?);
+            Ok((finder, prefix, completed, index_dates))
+        })
+        .await?
+    } else {
+        let s3_client = clients.get(bucket_id).await?;
+        let prefix = s3_client
+            .scoped_prefix(request.prefix.as_deref())?
+            .to_string();
+        let mut finder = DuplicateFinder::default();
+        let completed = s3_client
+            .walk_objects(Some(&prefix), cancelled, |page| {
+                finder.add_page(&page);
+                let _ = on_progress.send(finder.progress());
+            })
+            .await?;
+        (finder, prefix, completed, (None, None))
+    };
+
+    let (scanned, groups) = finder.finish();
+    let (groups, completed) = if request.verify_checksums && completed {
+        let s3_client = clients.get(bucket_id).await?;
+        duplicates::verify(&s3_client, groups, cancelled).await
+    } else {
+        (groups, completed)
+    };
+    let (indexed_at, inventory_date) = index_dates;
+    let mut report = duplicates::report(&prefix, scanned, groups, !completed, indexed_at);
+    report.inventory_date = inventory_date;
+    Ok(report)
+}
+
+/// Delete the copies in each group, or swap them for placeholders redirecting to the kept
+/// object. Groups whose kept object changed since the scan are skipped.
+#[command]
+pub async fn resolve_duplicates(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+    bucket_id: String,
+    request: ResolveDuplicatesRequest,
+    confirmation: Option<String>,
+) -> Result<ResolveDuplicatesResponse> {
+    let s3_client = clients.get(&bucket_id).await?;
+    for group in &request.groups {
+        for key in &group.duplicates {
+            s3_client.ensure_confirmed(key, confirmation.as_deref())?;
+        }
+    }
+
+    let placeholder = request.action == DuplicateAction::Replace;
+    let mut resolved = Vec::new();
+    let mut failed = Vec::new();
+    let mut trashed = Vec::new();
+    let mut entries = Vec::new();
+
+    for group in &request.groups {
+        let kept = s3_client
+            .get_object_metadata(&group.keep)
+            .await
+            .and_then(|kept| check_kept(&kept, &group.etag));
+        if let Err(e) = kept {
+            eprintln!("Skipping duplicates of {}: {}", group.keep, e);
+            failed.extend(group.duplicates.iter().cloned());
+            continue;
+        }
+
+        for key in &group.duplicates {
+            let entry = AuditEntry::new(AuditAction::Delete, Some(&bucket_id), vec![key.clone()]);
+            match s3_client
+                .remove_duplicate(
+                    key,
+                    &group.etag,
+                    &group.keep,
+                    placeholder,
+                    confirmation.as_deref(),
+                )
+                .await
+            {
+                Ok(deletion) => {
+                    entries.push(entry.message(format!("Duplicate of '{}'", group.keep)));
+                    trashed.extend(deletion.trashed);
+                    resolved.push(key.clone());
+                }
+                Err(e) => {
+                    eprintln!("Failed to remove duplicate {}: {}", key, e);
+                    entries.push(entry.failed(&e));
+                    failed.push(key.clone());
+                }
+            }
+        }
+    }
+    audit::record(entries);
+
+    let is_trashed = !trashed.is_empty();
+    // A placeholder sits where a restored copy would go, so only plain deletes are undoable
+    if is_trashed && !placeholder {
+        history.record(
+            &bucket_id,
+            format!("Delete {} duplicates", resolved.len()),
+            ReversibleOperation::Delete { items: trashed },
+        );
+    }
+
+    let verb = match (placeholder, is_trashed) {
+        (true, _) => "Replaced",
+        (false, true) => "Moved to the trash",
+        (false, false) => "Deleted",
+    };
+    let message = if failed.is_empty() {
+        format!("{} {} duplicates", verb, resolved.len())
+    } else {
+        format!(
+            "{} {} duplicates, {} failed",
+            verb,
+            resolved.len(),
+            failed.len()
+        )
+    };
+
+    Ok(ResolveDuplicatesResponse {
+        message,
+        resolved,
+        failed,
+        trashed: is_trashed,
+    })
+}
+
+/// The kept copy must still be the scanned content before any duplicate of it goes
+fn check_kept(kept: &ObjectMetadata, etag: &str) -> Result<()> {
+    if kept.etag.as_deref().map(|e| e.trim_matches('"')) == Some(etag.trim_matches('"')) {
+        Ok(())
+    } else {
+        Err(S3DeckError::PreconditionFailed(S3ErrorInfo {
+            message: format!("'{}' changed since it was scanned", kept.key),
+            ..Default::default()
+        }))
+    }
+}
+
+// Listing export commands
+/// Write a recursive listing of a prefix to a local CSV, JSON or NDJSON file as it is listed.
+/// `on_progress` gets running totals, and `cancel_job` with the export id stops the export
+/// without leaving a partial file.
+#[command]
+pub async fn export_listing(
+    clients: State<'_, ClientCache>,
+    jobs: State<'_, JobManager>,
+    bucket_id: String,
+    request: ListingExportRequest,
+    on_progress: Channel<FolderStatsProgress>,
+) -> Result<ListingExportSummary> {
+    let cancelled = jobs.start(&request.export_id, JobKind::ListingExport, Some(&bucket_id))?;
+    let result = write_listing(&clients, &bucket_id, &request, &cancelled, &on_progress).await;
+    jobs.finish(&request.export_id, &result);
+    result
+}
+
+async fn write_listing(
+    clients: &ClientCache,
+    bucket_id: &str,
+    request: &ListingExportRequest,
+    cancelled: &AtomicBool,
+    on_progress: &Channel<FolderStatsProgress>,
+) -> Result<ListingExportSummary> {
+    let s3_client = clients.get(bucket_id).await?;
+    let prefix = s3_client.scoped_prefix(request.prefix.as_deref())?;
+    let mut writer = ListingWriter::create(
+        Path::new(&request.destination),
+        request.format,
+        &request.fields,
+    )?;
+
+    let listed = export::export_listing(&s3_client, prefix, &mut writer, cancelled, |progress| {
+        let _ = on_progress.send(progress);
+    })
+    .await;
+    let mut summary = ListingExportSummary {
+        destination: request.destination.clone(),
+        object_count: 0,
+        total_bytes: 0,
+        cancelled: false,
+    };
+    match listed {
+        Ok(true) => {
+            (summary.object_count, summary.total_bytes) = writer.finish()?;
+        }
+        Ok(false) => {
+            let progress = writer.progress();
+            writer.abandon();
+            summary.object_count = progress.object_count;
+            summary.total_bytes = progress.total_bytes;
+            summary.cancelled = true;
+        }
+        Err(e) => {
+            writer.abandon();
+            return Err(e);
+        }
+    }
+    Ok(summary)
+}
+
+// Batch job commands
+/// Apply one operation to every key of a manifest and/or an explicit key list, several keys at
+/// a time, writing a `key,status,detail` row per key to the report. `on_progress` gets running
+/// totals, and `cancel_job` with the job id stops starting new keys.
+#[command]
+pub async fn run_batch_job(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+    jobs: State<'_, JobManager>,
+    bucket_id: String,
+    request: BatchJobRequest,
+    confirmation: Option<String>,
+    on_progress: Channel<BatchJobProgress>,
+) -> Result<BatchJobSummary> {
+    let cancelled = jobs.start(&request.job_id, JobKind::BatchJob, Some(&bucket_id))?;
+    let result = run_batch(
+        &clients,
+        &history,
+        &bucket_id,
+        &request,
+        confirmation.as_deref(),
+        &cancelled,
+        &on_progress,
+    )
+    .await;
+    jobs.finish(&request.job_id, &result);
+    result
+}
+
+async fn run_batch(
+    clients: &ClientCache,
+    history: &OperationHistory,
+    bucket_id: &str,
+    request: &BatchJobRequest,
+    confirmation: Option<&str>,
+    cancelled: &AtomicBool,
+    on_progress: &Channel<BatchJobProgress>,
+) -> Result<BatchJobSummary> {
+    let mut keys = match &request.manifest {
+        Some(manifest) => batch::read_manifest(Path::new(manifest))?,
+        None => Vec::new(),
+    };
+    keys.extend(request.keys.iter().cloned());
+    let keys = batch::unique(keys);
+    if keys.is_empty() {
+        return Err(S3DeckError::InvalidInput(
+            "The batch job has no keys".to_string(),
+        ));
+    }
+
+    let source = clients.get(bucket_id).await?;
+    let destination = match &request.operation {
+        BatchOperation::Copy {
+            destination_bucket_id: Some(destination_id),
+            ..
+        } => clients.get(destination_id).await?,
+        _ => source.clone(),
+    };
+
+    // Ask for confirmation up front rather than failing protected keys one by one
+    match &request.operation {
+        BatchOperation::Delete => {
+            for key in &keys {
+                source.ensure_confirmed(key, confirmation)?;
+            }
+        }
+        BatchOperation::Copy {
+            destination_prefix,
+            overwrite,
+            ..
+        } => {
+            destination.ensure_writable()?;
+            if *overwrite == OverwritePolicy::Overwrite {
+                for key in &keys {
+                    let destination_key = batch::destination_key(destination_prefix, key);
+                    destination.ensure_confirmed(&destination_key, confirmation)?;
+                }
+            }
+        }
+        _ => source.ensure_writable()?,
+    }
+
+    let action = match &request.operation {
+        BatchOperation::Delete => AuditAction::Delete,
+        BatchOperation::Copy { .. } => AuditAction::Copy,
+        BatchOperation::Restore { .. } => AuditAction::RestoreArchive,
+        BatchOperation::SetStorageClass { .. }
+        | BatchOperation::SetTags { .. }
+        | BatchOperation::SetMetadata { .. } => AuditAction::UpdateMetadata,
+    };
+    let mut report = BatchReport::create(Path::new(&request.report_path))?;
+    let mut progress = BatchJobProgress {
+        total: keys.len() as u64,
+        ..Default::default()
+    };
+    let mut entries = Vec::new();
+    let mut trashed = Vec::new();
+
+    let job = BatchJob {
+        source,
+        destination,
+        operation: request.operation.clone(),
+        confirmation: confirmation.map(str::to_string),
+    };
+    let completed = batch::run(
+        Arc::new(job),
+        keys,
+        request.concurrency.unwrap_or(batch::DEFAULT_CONCURRENCY),
+        cancelled,
+        |result| {
+            report.add(&result)?;
+
+            let entry = AuditEntry::new(action, Some(bucket_id), vec![result.key.clone()]);
+            entries.push(match result.outcome {
+                AuditOutcome::Success if result.detail.is_empty() => entry,
+                AuditOutcome::Success => entry.message(result.detail),
+                AuditOutcome::Skipped => entry.skipped().message(result.detail),
+                AuditOutcome::Failed => entry.failed(result.detail),
+            });
+            if entries.len() >= batch::AUDIT_CHUNK {
+                audit::record(std::mem::take(&mut entries));
+            }
+            trashed.extend(result.trashed);
+
+            match result.outcome {
+                AuditOutcome::Success => progress.succeeded += 1,
+                AuditOutcome::Skipped => progress.skipped += 1,
+                AuditOutcome::Failed => progress.failed += 1,
+            }
+            let _ = on_progress.send(progress.clone());
+            Ok(())
+        },
+    )
+    .await;
+    audit::record(entries);
+    if !trashed.is_empty() {
+        history.record(
+            bucket_id,
+            format!("Batch delete of {} objects", trashed.len()),
+            ReversibleOperation::Delete { items: trashed },
+        );
+    }
+    let completed = completed?;
+    report.finish()?;
+
+    let processed = progress.succeeded + progress.skipped + progress.failed;
+    let mut message = format!(
+        "Processed {} of {} keys: {} succeeded, {} skipped, {} failed",
+        processed, progress.total, progress.succeeded, progress.skipped, progress.failed
+    );
+    if !completed {
+        message.push_str(" (cancelled)");
+    }
+    Ok(BatchJobSummary {
+        message,
+        total: progress.total,
+        succeeded: progress.succeeded,
+        skipped: progress.skipped,
+        failed: progress.failed,
+        report_path: request.report_path.clone(),
+        cancelled: !completed,
+    })
+}
+
+// Archive restore commands
+/// Request restores of archived objects, listed by key or found below a prefix, several at a
+/// time, and track them until they complete. `cancel_job` with the restore id stops starting
+/// new requests.
+#[command]
+pub async fn restore_archived_objects(
+    clients: State<'_, ClientCache>,
+    tracker: State<'_, RestoreTracker>,
+    jobs: State<'_, JobManager>,
+    bucket_id: String,
+    request: ArchiveRestoreRequest,
+) -> Result<ArchiveRestoreResponse> {
+    let cancelled = jobs.start(
+        &request.restore_id,
+        JobKind::ArchiveRestore,
+        Some(&bucket_id),
+    )?;
+    let result = restore_archived(&clients, &tracker, &bucket_id, &request, &cancelled).await;
+    jobs.finish(&request.restore_id, &result);
+    result
+}
+
+async fn restore_archived(
+    clients: &ClientCache,
+    tracker: &RestoreTracker,
+    bucket_id: &str,
+    request: &ArchiveRestoreRequest,
+    cancelled: &AtomicBool,
+) -> Result<ArchiveRestoreResponse> {
+    if request.days < 1 {
+        return Err(S3DeckError::InvalidInput(
+            "Restored copies must be kept for at least a day".to_string(),
+        ));
+    }
+
+    let s3_client = clients.get(bucket_id).await?;
+    s3_client.ensure_writable()?;
+    let mut keys = request.keys.clone();
+    if let Some(prefix) = &request.prefix {
+        s3_client
+            .walk_objects(Some(prefix), cancelled, |page| {
+                keys.extend(page.into_iter().filter_map(|object| {
+                    let archived = object
+                        .storage_class
+                        .as_deref()
+                        .is_some_and(restore::is_archive_class);
+                    archived.then_some(object.key)
+                }));
+            })
+            .await?;
+    }
+    let keys = batch::unique(keys);
+
+    let mut requested = Vec::new();
+    let mut in_progress = Vec::new();
+    let mut failed = Vec::new();
+    let mut entries = Vec::new();
+    let job = BatchJob {
+        source: s3_client.clone(),
+        destination: s3_client,
+        operation: BatchOperation::Restore {
+            days: request.days,
+            tier: request.tier,
+        },
+        confirmation: None,
+    };
+    let completed = batch::run(
+        Arc::new(job),
+        keys,
+        request.concurrency.unwrap_or(batch::DEFAULT_CONCURRENCY),
+        cancelled,
+        |result| {
+            let entry = AuditEntry::new(
+                AuditAction::RestoreArchive,
+                Some(bucket_id),
+                vec![result.key.clone()],
+            );
+            match result.outcome {
+                AuditOutcome::Success => {
+                    entries.push(entry.message(format!("Restore for {} days", request.days)));
+                    requested.push(result.key);
+                }
+                AuditOutcome::Skipped => {
+                    entries.push(entry.skipped().message(result.detail));
+                    in_progress.push(result.key);
+                }
+                AuditOutcome::Failed => {
+                    eprintln!("Failed to restore {}: {}", result.key, result.detail);
+                    entries.push(entry.failed(result.detail));
+                    failed.push(result.key);
+                }
+            }
+            if entries.len() >= batch::AUDIT_CHUNK {
+                audit::record(std::mem::take(&mut entries));
+            }
+            Ok(())
+        },
+    )
+    .await;
+    audit::record(entries);
+
+    let tracked: Vec<String> = requested.iter().chain(&in_progress).cloned().collect();
+    tracker.add(bucket_id, &tracked, request.tier, request.days);
+    let completed = completed?;
+
+    let mut message = format!("Requested restore of {} objects", requested.len());
+    if !in_progress.is_empty() {
+        message.push_str(&format!(", {} already in progress", in_progress.len()));
+    }
+    if !failed.is_empty() {
+        message.push_str(&format!(", {} failed", failed.len()));
+    }
+    if !completed {
+        message.push_str(" (cancelled)");
+    }
+    Ok(ArchiveRestoreResponse {
+        message,
+        requested,
+        in_progress,
+        failed,
+        cancelled: !completed,
+    })
+}
+
+#[command]
+pub async fn get_restore_status(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    key: String,
+) -> Result<RestoreStatus> {
+    let key = key.as_str();
+    clients
+        .run(&bucket_id, |s3_client| async move {
+            s3_client.restore_status(key).await
+        })
+        .await
+}
+
+/// Restores still being waited on, of one bucket or of all of them
+#[command]
+pub async fn list_pending_restores(
+    tracker: State<'_, RestoreTracker>,
+    bucket_id: Option<String>,
+) -> Result<Vec<PendingRestore>> {
+    Ok(tracker.pending(bucket_id.as_deref()))
+}
+
+/// Check the tracked restores once, returning the ones that became readable since the last check
+#[command]
+pub async fn check_pending_restores(
+    clients: State<'_, ClientCache>,
+    tracker: State<'_, RestoreTracker>,
+    bucket_id: Option<String>,
+) -> Result<Vec<PendingRestore>> {
+    Ok(restore::check_pending(&clients, &tracker, bucket_id.as_deref()).await)
+}
+
+/// Check the tracked restores every `interval_seconds` and send the ones that became readable
+/// over `on_available`. Runs until nothing is pending, the window stops listening, or
+/// `cancel_job` is called with the watch id.
+#[command]
+pub async fn watch_pending_restores(
+    clients: State<'_, ClientCache>,
+    tracker: State<'_, RestoreTracker>,
+    jobs: State<'_, JobManager>,
+    watch_id: String,
+    interval_seconds: Option<u64>,
+    on_available: Channel<Vec<PendingRestore>>,
+) -> Result<()> {
+    let interval = interval_seconds
+        .unwrap_or(restore::DEFAULT_CHECK_INTERVAL_SECONDS)
+        .max(restore::MIN_CHECK_INTERVAL_SECONDS);
+    let cancelled = jobs.start(&watch_id, JobKind::RestoreWatch, None)?;
+
+    while !cancelled.load(Ordering::Relaxed) && !tracker.pending(None).is_empty() {
+        let available = restore::check_pending(&clients, &tracker, None).await;
+        if !available.is_empty() && on_available.send(available).is_err() {
+            break;
+        }
+
+        // Sleep in short steps so a cancel doesn't wait out the whole interval
+        for _ in 0..interval {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    }
+
+    jobs.finish(&watch_id, &Ok(()));
+    Ok(())
+}
+
+// Index commands
+/// Crawl a prefix, or the whole bucket, into the local index and return the index's status
+#[command]
+pub async fn build_index(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    prefix: Option<String>,
+) -> Result<IndexStatus> {
+    let s3_client = clients.get(&bucket_id).await?;
+    let prefix = s3_client.scoped_prefix(prefix.as_deref())?.to_string();
+    let index_bucket = bucket_id.clone();
+    let index = Arc::new(index::blocking(move || MetadataIndex::open(&index_bucket)).await?);
+
+    let crawl = chrono::Utc::now();
+    // Pages are written by a blocking worker while the walk goes on. A write failure stops the
+    // walk rather than crawling on for nothing.
+    let failed = Arc::new(AtomicBool::new(false));
+    let (pages, mut received) = tokio::sync::mpsc::unbounded_channel::<Vec<ObjectSummary>>();
+    let writer = {
+        let (index, failed) = (index.clone(), failed.clone());
+        index::blocking(move || {
+            while let Some(page) = received.blocking_recv() {
+                if let Err(e) = index.insert_objects(crawl, &page) {
+                    failed.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        })
+    };
+    let walk = async {
+        let walked = s3_client
+            .walk_objects(Some(&prefix), &failed, |page| {
+                let _ = pages.send(page);
+            })
+            .await;
+        drop(pages);
+        walked
+    };
+    let (walked, written) = tokio::join!(walk, writer);
+    walked?;
+    written?;
+
+    index::blocking(move || {
+        index.finish_crawl(&prefix, crawl)?;
+        index.status(&bucket_id)
+    })
+    .await
+}
+
+/// Status of a bucket's index, or `None` when it has none
+#[command]
+pub async fn get_index_status(bucket_id: String) -> Result<Option<IndexStatus>> {
+    index::blocking(move || {
+        MetadataIndex::open_existing(&bucket_id)?
+            .map(|index| index.status(&bucket_id))
+            .transpose()
+    })
+    .await
+}
+
+#[command]
+pub async fn delete_index(bucket_id: String) -> Result<bool> {
+    MetadataIndex::delete(&bucket_id)
+}
+
+/// List a folder from the index, without contacting the bucket
+#[command]
+pub async fn list_indexed_objects(
+    bucket_id: String,
+    prefix: Option<String>,
+) -> Result<IndexedListing> {
+    index::blocking(move || {
+        let (index, prefix) = indexed_prefix(&bucket_id, prefix.as_deref())?;
+        index.list_level(&prefix)
+    })
+    .await
+}
+
+/// Load an S3 Inventory report into a bucket's index, so buckets too large to list can be
+/// browsed, searched and analyzed from it. The report is read from a local folder, or from the
+/// bucket it was delivered to when `inventoryBucketId` is set.
+#[command]
+pub async fn import_inventory(
+    clients: State<'_, ClientCache>,
+    bucket_id: String,
+    request: InventoryImportRequest,
+    on_progress: Channel<InventoryImportProgress>,
+) -> Result<IndexStatus> {
+    let bucket = ConfigManager::new()?.get_bucket(&bucket_id)?;
+    let source = match &request.inventory_bucket_id {
+        Some(source_id) => {
+            let source_bucket = ConfigManager::new()?.get_bucket(source_id)?;
+            Some((clients.get(source_id).await?, source_bucket.name))
+        }
+        None => None,
+    };
+
+    // Reports read from S3 are downloaded one file at a time, then removed
+    let download_dir =
+        std::env::temp_dir().join(format!("s3deck-inventory-{}", uuid::Uuid::new_v4()));
+    let source = source
+        .as_ref()
+        .map(|(client, name)| (client.as_ref(), name.as_str()));
+    let result = load_inventory(&bucket, source, &request, &download_dir, &on_progress).await;
+    let _ = std::fs::remove_dir_all(&download_dir);
+    result
+}
+
+async fn load_inventory(
+    bucket: &BucketConfig,
+    source: Option<(&S3Client, &str)>,
+    request: &InventoryImportRequest,
+    download_dir: &Path,
+    on_progress: &Channel<InventoryImportProgress>,
+) -> Result<IndexStatus> {
+    let prefix = config_prefix(bucket, request.prefix.as_deref())?;
+    let trash_prefix = format!(
+        "{}{}",
+        normalize_root_prefix(bucket.root_prefix.as_deref())?,
+        TRASH_FOLDER
+    );
+
+    let (manifest_path, location) = match source {
+        Some((client, source_name)) => {
+            std::fs::create_dir_all(download_dir)?;
+            let path = download_dir.join("manifest.json");
+            client.download_object(&request.manifest, &path).await?;
+            (path, format!("s3://{}/{}", source_name, request.manifest))
+        }
+        None => (PathBuf::from(&request.manifest), request.manifest.clone()),
+    };
+    let manifest = InventoryManifest::parse(&std::fs::read(&manifest_path)?)?;
+    if manifest.source_bucket != bucket.name {
+        return Err(S3DeckError::InvalidInput(format!(
+            "The inventory lists bucket '{}', not '{}'",
+            manifest.source_bucket, bucket.name
+        )));
+    }
+    let inventory_date = manifest.created_at()?;
+
+    let bucket_id = bucket.id.clone();
+    let index = Arc::new(
+        index::blocking(move || {
+            let index = MetadataIndex::open(&bucket_id)?;
+            index.begin_import()?;
+            Ok(index)
+        })
+        .await?,
+    );
+    let file_count = manifest.files.len();
+    let manifest = Arc::new(manifest);
+    let mut progress = InventoryImportProgress {
+        files_read: 0,
+        file_count,
+        object_count: 0,
+    };
+    for file in &manifest.files {
+        let path = match source {
+            Some((client, _)) => {
+                let name = file.key.rsplit('/').next().unwrap_or(&file.key);
+                let path = download_dir.join(name);
+                client.download_object(&file.key, &path).await?;
+                path
+            }
+            None => inventory::local_data_file(&manifest_path, file)?,
+        };
+
+        let downloaded = source.is_some();
+        let staged = {
+            let (manifest, index, on_progress) =
+                (manifest.clone(), index.clone(), on_progress.clone());
+            let (prefix, trash_prefix) = (prefix.clone(), trash_prefix.clone());
+            index::blocking(move || {
+                manifest.read_file(&path, |page| {
+                    // Folder markers and the trash are left out, like in a crawl
+                    let page: Vec<ObjectSummary> = page
+                        .into_iter()
+                        .filter(|object| {
+                            object.key.starts_with(&prefix)
+                                && !object.key.ends_with('/')
+                                && !object.key.starts_with(&trash_prefix)
+                        })
+                        .collect();
+                    progress.object_count += page.len() as u64;
+                    let _ = on_progress.send(progress.clone());
+                    index.stage_objects(&page)
+                })?;
+                if downloaded {
+                    let _ = std::fs::remove_file(&path);
+                }
+                Ok(progress)
+            })
+        };
+        progress = staged.await?;
+
+        progress.files_read += 1;
+        let _ = on_progress.send(progress.clone());
+    }
+
+    let bucket_id = bucket.id.clone();
+    index::blocking(move || {
+        index.finish_import(&prefix, inventory_date, &location)?;
+        index.status(&bucket_id)
+    })
+    .await
+}
+
+/// Open a bucket's index and resolve a prefix against its root prefix, from the saved config
+/// alone so it works offline
+fn indexed_prefix(bucket_id: &str, prefix: Option<&str>) -> Result<(MetadataIndex, String)> {
+    let bucket = ConfigManager::new()?.get_bucket(bucket_id)?;
+    let prefix = config_prefix(&bucket, prefix)?;
+
+    let index = MetadataIndex::open_existing(bucket_id)?.ok_or_else(|| {
+        S3DeckError::Index(format!(
+            "Bucket '{}' has no index, build it first",
+            bucket.name
+        ))
+    })?;
+    Ok((index, prefix))
+}
+
+/// The given prefix, or the bucket's root prefix when none is given, checked against the root
+fn config_prefix(bucket: &BucketConfig, prefix: Option<&str>) -> Result<String> {
+    let root_prefix = normalize_root_prefix(bucket.root_prefix.as_deref())?;
+    let prefix = prefix
+        .filter(|p| !p.is_empty())
+        .unwrap_or(&root_prefix)
+        .to_string();
+    if !prefix.starts_with(&root_prefix) {
+        return Err(S3DeckError::InvalidPath(format!(
+            "'{}' is outside the bucket root '{}'",
+            prefix, root_prefix
+        )));
+    }
+    Ok(prefix)
+}
+
+// Undo commands
+#[command]
+pub async fn get_operation_history(
+    history: State<'_, OperationHistory>,
+) -> Result<Vec<HistoryEntry>> {
+    Ok(history.entries())
+}
+
+/// Reverse the most recent rename, move, trashed delete or metadata edit
+#[command]
+pub async fn undo_last_operation(
+    clients: State<'_, ClientCache>,
+    history: State<'_, OperationHistory>,
+) -> Result<UndoResponse> {
+    let entry = history
+        .pop()
+        .ok_or_else(|| S3DeckError::Config("There is nothing to undo".to_string()))?;
+
+    let s3_client = match clients.get(&entry.bucket_id).await {
+        Ok(s3_client) => s3_client,
+        Err(e) => {
+            history.push(entry);
+            return Err(e);
+        }
+    };
+    let (restored, failed) = history::undo(&s3_client, &entry.operation).await;
+
+    let audit_entry = AuditEntry::new(AuditAction::Undo, Some(&entry.bucket_id), restored.clone())
+        .message(&entry.description);
+    audit::record(vec![if failed.is_empty() {
+        audit_entry
+    } else {
+        audit_entry.failed(format!("Could not restore: {}", failed.join(", ")))
+    }]);
+
+    let message = if failed.is_empty() {
+        format!(
+            "Undid {}: restored {} object(s)",
+            entry.description,
+            restored.len()
+        )
+    } else {
+        format!(
+            "Partially undid {}: restored {} object(s), {} could not be restored",
+            entry.description,
+            restored.len(),
+            failed.len()
+        )
+    };
+
+    // Keep an operation that couldn't be reversed at all, so it can be retried
+    if restored.is_empty() && !failed.is_empty() {
+        history.push(entry.clone());
+    }
+
+    Ok(UndoResponse {
+        message,
+        undone: entry,
+        restored,
+        failed,
+    })
+}
+
+// Audit log commands
+#[command]
+pub async fn query_audit_log(query: AuditQuery) -> Result<Vec<AuditEntry>> {
+    AuditLog::new()?.query(&query)
+}
+
+/// Export matching audit entries to a file, as CSV for `.csv` paths and JSONL otherwise
+#[command]
+pub async fn export_audit_log(query: AuditQuery, destination: String) -> Result<usize> {
+    AuditLog::new()?.export(&query, Path::new(&destination))
+}
+
 // AWS Profile commands
 #[command]
 pub async fn get_aws_profiles() -> Result<Vec<AwsProfile>> {
@@ -427,3 +2205,14 @@ pub async fn validate_aws_profile(profile_name: String) -> Result<ProfileStatus>
     let profile_manager = AwsProfileManager::new();
     profile_manager.validate_aws_profile(&profile_name).await
 }
+
+#[command]
+pub async fn get_profile_details(
+    profile_name: String,
+    sts_endpoint: Option<String>,
+) -> Result<ProfileDetails> {
+    let profile_manager = AwsProfileManager::new();
+    profile_manager
+        .get_profile_details(&profile_name, sts_endpoint.as_deref())
+        .await
+}
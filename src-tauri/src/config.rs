@@ -1,4 +1,4 @@
-use crate::models::{BucketConfig, Config, Result, S3DeckError};
+use crate::models::{BucketConfig, Config, NetworkSettings, Result, S3DeckError};
 use std::fs;
 use std::path::PathBuf;
 
@@ -6,18 +6,23 @@ pub struct ConfigManager {
     config_path: PathBuf,
 }
 
-impl ConfigManager {
-    pub fn new() -> Result<Self> {
-        let config_dir = dirs::home_dir()
-            .ok_or_else(|| S3DeckError::Config("Could not find home directory".to_string()))?
-            .join(".s3deck");
+/// Directory holding the app's config and logs, created on first use
+pub fn app_dir() -> Result<PathBuf> {
+    let app_dir = dirs::home_dir()
+        .ok_or_else(|| S3DeckError::Config("Could not find home directory".to_string()))?
+        .join(".s3deck");
 
-        // Create config directory if it doesn't exist
-        if !config_dir.exists() {
-            fs::create_dir_all(&config_dir)?;
-        }
+    // Create config directory if it doesn't exist
+    if !app_dir.exists() {
+        fs::create_dir_all(&app_dir)?;
+    }
 
-        let config_path = config_dir.join("config.json");
+    Ok(app_dir)
+}
+
+impl ConfigManager {
+    pub fn new() -> Result<Self> {
+        let config_path = app_dir()?.join("config.json");
 
         Ok(Self { config_path })
     }
@@ -90,4 +95,16 @@ impl ConfigManager {
         let config = self.load_config()?;
         Ok(config.buckets)
     }
+
+    pub fn get_network_settings(&self) -> Result<NetworkSettings> {
+        let config = self.load_config()?;
+        Ok(config.network)
+    }
+
+    pub fn update_network_settings(&self, network: NetworkSettings) -> Result<NetworkSettings> {
+        let mut config = self.load_config()?;
+        config.network = network;
+        self.save_config(&config)?;
+        Ok(config.network)
+    }
 }
\ No newline at end of file
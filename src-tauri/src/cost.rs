@@ -0,0 +1,297 @@
+use crate::config::app_dir;
+use crate::models::{
+    CostEstimate, PricingTable, Result, S3DeckError, StorageClassCost, StorageClassUsage,
+    TierTransition, UsageReport,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// AWS list prices shipped with the app, used until the user saves their own table
+const DEFAULT_PRICING: &str = include_str!("../resources/pricing.json");
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+impl PricingTable {
+    pub fn defaults() -> Self {
+        serde_json::from_str(DEFAULT_PRICING).expect("bundled pricing table is valid")
+    }
+
+    fn path() -> Result<PathBuf> {
+        Ok(app_dir()?.join("pricing.json"))
+    }
+
+    /// The user's pricing table, or the defaults when they haven't saved one
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::defaults());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if !self.regions.contains_key(&self.default_region) {
+            return Err(S3DeckError::InvalidInput(format!(
+                "Default region '{}' has no prices",
+                self.default_region
+            )));
+        }
+        let negative = self
+            .regions
+            .iter()
+            .flat_map(|(region, prices)| prices.iter().map(move |(class, p)| (region, class, p)))
+            .find(|(_, _, price)| !price.is_finite() || **price < 0.0);
+        if let Some((region, class, price)) = negative {
+            return Err(S3DeckError::InvalidInput(format!(
+                "Price of {} in {} must be zero or more, not {}",
+                class, region, price
+            )));
+        }
+
+        fs::write(Self::path()?, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Drop the user's table and go back to the defaults
+    pub fn reset() -> Result<Self> {
+        let path = Self::path()?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(Self::defaults())
+    }
+
+    /// The region whose prices apply to a bucket region
+    fn priced_region<'a>(&'a self, region: &'a str) -> &'a str {
+        if self.regions.contains_key(region) {
+            region
+        } else {
+            &self.default_region
+        }
+    }
+
+    fn monthly_cost(&self, region: &str, usage: &StorageClassUsage) -> Option<f64> {
+        self.class_cost(region, &usage.storage_class, usage)
+    }
+
+    /// Cost of some usage if it were stored in `storage_class`
+    fn class_cost(
+        &self,
+        region: &str,
+        storage_class: &str,
+        usage: &StorageClassUsage,
+    ) -> Option<f64> {
+        let price = self.regions.get(region)?.get(storage_class)?;
+        let overhead = self
+            .classes
+            .get(storage_class)
+            .map_or(0, |billing| billing.overhead_bytes);
+        let billed_bytes = usage.bytes + overhead * usage.object_count as i64;
+        Some(billed_bytes as f64 / BYTES_PER_GB * price)
+    }
+}
+
+/// Estimate the monthly storage cost of a usage report, and what it would cost after moving
+/// old objects to cheaper classes. Request, retrieval and transition fees are not included.
+///
+/// Transitions are applied per bucket of the report's age histogram: a bucket moves only once
+/// its youngest objects are old enough, so a transition after 60 days leaves the whole
+/// 30-90 day bucket where it is. Objects without a last-modified date stay in their class.
+pub fn estimate(
+    report: &UsageReport,
+    pricing: &PricingTable,
+    bucket_region: &str,
+    transitions: &[TierTransition],
+) -> Result<CostEstimate> {
+    let region = pricing.priced_region(bucket_region);
+
+    for transition in transitions {
+        if pricing
+            .regions
+            .get(region)
+            .is_none_or(|prices| !prices.contains_key(&transition.storage_class))
+        {
+            return Err(S3DeckError::InvalidInput(format!(
+                "No price for {} in {}",
+                transition.storage_class, region
+            )));
+        }
+    }
+
+    let mut unpriced_classes = Vec::new();
+    let mut storage_classes = Vec::new();
+    for usage in &report.storage_classes {
+        match pricing.monthly_cost(region, usage) {
+            Some(monthly_cost) => storage_classes.push(StorageClassCost {
+                storage_class: usage.storage_class.clone(),
+                bytes: usage.bytes,
+                object_count: usage.object_count,
+                monthly_cost,
+            }),
+            None => unpriced_classes.push(usage.storage_class.clone()),
+        }
+    }
+    storage_classes.sort_by(|a, b| b.monthly_cost.total_cmp(&a.monthly_cost));
+    let monthly_cost: f64 = storage_classes.iter().map(|cost| cost.monthly_cost).sum();
+
+    let mut projected_monthly_cost = None;
+    let mut transitioned_bytes = 0;
+    if !transitions.is_empty() {
+        // Usage the age histogram doesn't cover, for objects without a last-modified date
+        let mut unaged: HashMap<&str, StorageClassUsage> = report
+            .storage_classes
+            .iter()
+            .map(|usage| (usage.storage_class.as_str(), usage.clone()))
+            .collect();
+        let mut projected = 0.0;
+        for age_bucket in &report.age_histogram {
+            // The transition for the oldest age this bucket has reached
+            let transition = transitions
+                .iter()
+                .filter(|t| t.older_than_days <= age_bucket.min_days)
+                .max_by_key(|t| t.older_than_days);
+
+            for usage in &age_bucket.storage_classes {
+                if let Some(remaining) = unaged.get_mut(usage.storage_class.as_str()) {
+                    remaining.bytes -= usage.bytes;
+                    remaining.object_count -= usage.object_count;
+                }
+                let Some(current) = pricing.monthly_cost(region, usage) else {
+                    continue;
+                };
+                let moved = transition
+                    .and_then(|t| pricing.class_cost(region, &t.storage_class, usage))
+                    .filter(|cost| *cost < current);
+                match moved {
+                    Some(cost) => {
+                        projected += cost;
+                        transitioned_bytes += usage.bytes;
+                    }
+                    None => projected += current,
+                }
+            }
+        }
+        projected += unaged
+            .values()
+            .filter(|usage| usage.object_count > 0)
+            .filter_map(|usage| pricing.monthly_cost(region, usage))
+            .sum::<f64>();
+        projected_monthly_cost = Some(projected);
+    }
+
+    Ok(CostEstimate {
+        prefix: report.prefix.clone(),
+        region: region.to_string(),
+        currency: pricing.currency.clone(),
+        monthly_cost,
+        storage_classes,
+        projected_savings: projected_monthly_cost.map(|projected| monthly_cost - projected),
+        projected_monthly_cost,
+        transitioned_bytes,
+        unpriced_classes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ObjectSummary;
+    use crate::usage::UsageReportBuilder;
+    use chrono::{TimeDelta, Utc};
+
+    const GB: i64 = 1024 * 1024 * 1024;
+
+    fn report() -> UsageReport {
+        let object = |key: &str, size: i64, age_days: i64, class: Option<&str>| ObjectSummary {
+            key: key.to_string(),
+            size,
+            last_modified: Some(Utc::now() - TimeDelta::days(age_days)),
+            etag: None,
+            storage_class: class.map(|c| c.to_string()),
+        };
+
+        let mut builder = UsageReportBuilder::new("", 1);
+        builder.add_page(&[
+            object("new.bin", 100 * GB, 5, None),
+            object("old.bin", 100 * GB, 400, None),
+            object("archive.bin", 100 * GB, 400, Some("DEEP_ARCHIVE")),
+            object("odd.bin", GB, 5, Some("SOMETHING_NEW")),
+        ]);
+        builder.finish(None)
+    }
+
+    #[test]
+    fn test_estimate_with_defaults() {
+        let estimate = estimate(&report(), &PricingTable::defaults(), "eu-west-1", &[]).unwrap();
+
+        assert_eq!(estimate.region, "eu-west-1");
+        assert_eq!(estimate.storage_classes[0].storage_class, "STANDARD");
+        assert!((estimate.storage_classes[0].monthly_cost - 4.6).abs() < 1e-9);
+        // Deep Archive bills 40 KB of overhead per object on top of the data
+        let archive = estimate.storage_classes[1].monthly_cost;
+        assert!(archive > 0.099 && archive < 0.0991);
+        assert_eq!(estimate.unpriced_classes, vec!["SOMETHING_NEW"]);
+        assert!(estimate.projected_monthly_cost.is_none());
+    }
+
+    #[test]
+    fn test_transitions_project_savings() {
+        let transitions = [
+            TierTransition {
+                older_than_days: 30,
+                storage_class: "STANDARD_IA".to_string(),
+            },
+            TierTransition {
+                older_than_days: 365,
+                storage_class: "GLACIER_IR".to_string(),
+            },
+        ];
+        // Unknown regions fall back to the default region's prices
+        let projection =
+            estimate(&report(), &PricingTable::defaults(), "mars-1", &transitions).unwrap();
+
+        assert_eq!(projection.region, "us-east-1");
+        // Only the year-old Standard object moves; Deep Archive is already cheaper
+        assert_eq!(projection.transitioned_bytes, 100 * GB);
+        let savings = projection.projected_savings.unwrap();
+        assert!((savings - 100.0 * (0.023 - 0.004)).abs() < 1e-9);
+
+        let tape = TierTransition {
+            older_than_days: 30,
+            storage_class: "TAPE".to_string(),
+        };
+        let unknown = estimate(&report(), &PricingTable::defaults(), "us-east-1", &[tape]);
+        assert!(matches!(unknown, Err(S3DeckError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_objects_without_dates_stay_in_projection() {
+        let mut builder = UsageReportBuilder::new("", 1);
+        builder.add_page(&[ObjectSummary {
+            key: "undated.bin".to_string(),
+            size: 100 * GB,
+            last_modified: None,
+            etag: None,
+            storage_class: None,
+        }]);
+        let transitions = [TierTransition {
+            older_than_days: 0,
+            storage_class: "STANDARD_IA".to_string(),
+        }];
+        let projection = estimate(
+            &builder.finish(None),
+            &PricingTable::defaults(),
+            "us-east-1",
+            &transitions,
+        )
+        .unwrap();
+
+        assert_eq!(projection.transitioned_bytes, 0);
+        assert_eq!(
+            projection.projected_monthly_cost,
+            Some(projection.monthly_cost)
+        );
+        assert_eq!(projection.projected_savings, Some(0.0));
+    }
+}
@@ -0,0 +1,13 @@
+/// One CSV line, quoting fields that contain separators, quotes or line breaks
+pub fn row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| field(f.as_ref())).collect();
+    format!("{}\n", fields.join(","))
+}
+
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,274 @@
+use crate::models::{DuplicateGroup, DuplicateReport, FolderStatsProgress, ObjectSummary};
+use crate::s3_client::S3Client;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Collects objects with the same size and ETag, one listing page at a time
+#[derive(Default)]
+pub struct DuplicateFinder {
+    scanned: u64,
+    total_bytes: i64,
+    candidates: HashMap<(i64, String), Vec<ObjectSummary>>,
+}
+
+impl DuplicateFinder {
+    pub fn add_page(&mut self, page: &[ObjectSummary]) {
+        for object in page {
+            self.scanned += 1;
+            self.total_bytes += object.size;
+
+            // Empty objects all share one ETag but waste nothing
+            if object.size == 0 {
+                continue;
+            }
+            if let Some(etag) = object.etag.as_deref().and_then(etag_key) {
+                self.candidates
+                    .entry((object.size, etag))
+                    .or_default()
+                    .push(object.clone());
+            }
+        }
+    }
+
+    pub fn progress(&self) -> FolderStatsProgress {
+        FolderStatsProgress {
+            object_count: self.scanned,
+            total_bytes: self.total_bytes,
+        }
+    }
+
+    /// The number of objects scanned and the groups of two or more
+    pub fn finish(self) -> (u64, Vec<DuplicateGroup>) {
+        let groups = self
+            .candidates
+            .into_iter()
+            .filter(|(_, objects)| objects.len() > 1)
+            .map(|((size, etag), objects)| group(size, etag, objects, None))
+            .collect();
+        (self.scanned, groups)
+    }
+}
+
+/// The comparable form of an ETag: unquoted and lower-cased. Multipart ETags end in `-<parts>`,
+/// so two of them are only equal when the objects were uploaded in as many parts, and never
+/// equal a single-part ETag.
+fn etag_key(etag: &str) -> Option<String> {
+    let etag = etag.trim().trim_matches('"').to_ascii_lowercase();
+    let (hash, parts) = match etag.split_once('-') {
+        Some((hash, parts)) => (hash, Some(parts)),
+        None => (etag.as_str(), None),
+    };
+    let valid = !hash.is_empty()
+        && hash.chars().all(|c| c.is_ascii_hexdigit())
+        && parts.is_none_or(|parts| parts.parse::<u32>().is_ok_and(|n| n > 0));
+    valid.then_some(etag)
+}
+
+fn group(
+    size: i64,
+    etag: String,
+    mut objects: Vec<ObjectSummary>,
+    checksum: Option<String>,
+) -> DuplicateGroup {
+    objects.sort_by(|a, b| {
+        a.last_modified
+            .cmp(&b.last_modified)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    DuplicateGroup {
+        size,
+        etag,
+        wasted_bytes: size * (objects.len() as i64 - 1),
+        objects,
+        checksum,
+    }
+}
+
+/// Download every candidate and split the groups by checksum, dropping copies that turn out to
+/// differ. Objects that can't be read are left out. Stops early when `cancelled` is raised,
+/// returning the groups confirmed so far and false.
+pub async fn verify(
+    s3_client: &S3Client,
+    groups: Vec<DuplicateGroup>,
+    cancelled: &AtomicBool,
+) -> (Vec<DuplicateGroup>, bool) {
+    let mut verified = Vec::new();
+
+    for candidates in groups {
+        let mut by_checksum: HashMap<String, Vec<ObjectSummary>> = HashMap::new();
+        for object in candidates.objects {
+            if cancelled.load(Ordering::Relaxed) {
+                return (verified, false);
+            }
+            match s3_client
+                .retry_object(|| s3_client.object_checksum(&object.key))
+                .await
+            {
+                Ok(checksum) => by_checksum.entry(checksum).or_default().push(object),
+                Err(e) => eprintln!("Failed to checksum {}: {}", object.key, e),
+            }
+        }
+
+        verified.extend(
+            by_checksum
+                .into_iter()
+                .filter(|(_, objects)| objects.len() > 1)
+                .map(|(checksum, objects)| {
+                    group(
+                        candidates.size,
+                        candidates.etag.clone(),
+                        objects,
+                        Some(checksum),
+                    )
+                }),
+        );
+    }
+
+    (verified, true)
+}
+
+pub fn report(
+    prefix: &str,
+    scanned: u64,
+    mut groups: Vec<DuplicateGroup>,
+    cancelled: bool,
+    indexed_at: Option<DateTime<Utc>>,
+) -> DuplicateReport {
+    groups.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.objects[0].key.cmp(&b.objects[0].key))
+    });
+
+    DuplicateReport {
+        prefix: prefix.to_string(),
+        scanned,
+        duplicate_count: groups.iter().map(|g| g.objects.len() as u64 - 1).sum(),
+        wasted_bytes: groups.iter().map(|g| g.wasted_bytes).sum(),
+        groups,
+        cancelled,
+        indexed_at,
+        inventory_date: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BucketConfig, S3DeckError};
+    use crate::test_support::{direct_network, FakeS3};
+
+    fn object(key: &str, size: i64, etag: &str, modified: i64) -> ObjectSummary {
+        ObjectSummary {
+            key: key.to_string(),
+            size,
+            last_modified: DateTime::from_timestamp(modified, 0),
+            etag: Some(etag.to_string()),
+            storage_class: None,
+        }
+    }
+
+    #[test]
+    fn test_groups_by_size_and_etag() {
+        let mut finder = DuplicateFinder::default();
+        finder.add_page(&[
+            object("a/train.csv", 100, "\"ABC123\"", 2),
+            object("b/train.csv", 100, "\"abc123\"", 1),
+            object("c/train.csv", 100, "\"abc123\"", 3),
+            // Same ETag, different size
+            object("d/other.csv", 99, "\"abc123\"", 1),
+            // Same content uploaded in a different number of parts can't be compared
+            object("a/model.bin", 500, "\"ff00-2\"", 1),
+            object("b/model.bin", 500, "\"ff00-3\"", 1),
+            object("c/model.bin", 500, "\"ff00-3\"", 2),
+            object("a/empty", 0, "\"d41d8cd98f00b204e9800998ecf8427e\"", 1),
+            object("b/empty", 0, "\"d41d8cd98f00b204e9800998ecf8427e\"", 1),
+        ]);
+        assert_eq!(finder.progress().object_count, 9);
+
+        let (scanned, groups) = finder.finish();
+        let report = report("", scanned, groups, false, None);
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].etag, "ff00-3");
+        assert_eq!(report.groups[0].wasted_bytes, 500);
+
+        let csv = &report.groups[1];
+        let keys: Vec<&str> = csv.objects.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(keys, ["b/train.csv", "a/train.csv", "c/train.csv"]);
+        assert_eq!(csv.wasted_bytes, 200);
+        assert_eq!(report.duplicate_count, 3);
+        assert_eq!(report.wasted_bytes, 700);
+
+        assert_eq!(etag_key("\"ff00-0\""), None);
+        assert_eq!(etag_key("W/\"ff00\""), None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_remove_duplicates() {
+        let fake = FakeS3::start().await;
+        fake.put("a/data.bin", b"same bytes");
+        fake.put("b/data.bin", b"same bytes");
+        fake.put("c/data.bin", b"same bytes");
+        let client = fake.client().await;
+        let etag = fake.get("a/data.bin").unwrap().etag;
+
+        // Pretend a different file was listed with the same ETag, as can happen with SSE-C
+        let mut candidates = DuplicateFinder::default();
+        fake.put("d/data.bin", b"different!");
+        candidates.add_page(
+            &["a/data.bin", "b/data.bin", "c/data.bin", "d/data.bin"]
+                .map(|key| object(key, 10, &etag, 1)),
+        );
+        let (_, groups) = candidates.finish();
+        let (verified, completed) = verify(&client, groups, &AtomicBool::new(false)).await;
+        assert!(completed);
+        assert_eq!(verified.len(), 1);
+        assert_eq!(verified[0].objects.len(), 3);
+        assert!(verified[0].checksum.is_some());
+
+        client
+            .remove_duplicate("b/data.bin", &etag, "a/data.bin", false, None)
+            .await
+            .unwrap();
+        assert!(fake.get("b/data.bin").is_none());
+        assert_eq!(fake.last_header("if-match"), Some(etag.clone()));
+
+        client
+            .remove_duplicate("c/data.bin", &etag, "a/data.bin", true, None)
+            .await
+            .unwrap();
+        assert_eq!(fake.last_header("if-none-match").as_deref(), Some("*"));
+        let placeholder = fake.get("c/data.bin").unwrap();
+        assert!(placeholder.body.is_empty());
+        assert!(placeholder.headers.contains(&(
+            "x-amz-meta-s3deck-duplicate-of".to_string(),
+            "a/data.bin".to_string()
+        )));
+
+        // The placeholder no longer has the scanned ETag, so it is left alone
+        let changed = client
+            .remove_duplicate("c/data.bin", &etag, "a/data.bin", false, None)
+            .await;
+        assert!(matches!(changed, Err(S3DeckError::PreconditionFailed(_))));
+        assert!(fake.get("a/data.bin").is_some());
+
+        // In a trash-enabled bucket an archived copy needs the confirmation
+        fake.put("e/data.bin", b"same bytes");
+        fake.set_storage_class("e/data.bin", "DEEP_ARCHIVE");
+        let config = BucketConfig {
+            trash_enabled: true,
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+        let refused = client
+            .remove_duplicate("e/data.bin", &etag, "a/data.bin", false, None)
+            .await;
+        assert!(matches!(refused, Err(S3DeckError::ConfirmationRequired(_))));
+        client
+            .remove_duplicate("e/data.bin", &etag, "a/data.bin", false, Some(&config.name))
+            .await
+            .unwrap();
+        assert!(fake.get("e/data.bin").is_none());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::csv_format;
+use crate::models::{
+    ExportField, ExportFormat, FolderStatsProgress, ObjectSummary, Result, S3DeckError,
+};
+use crate::s3_client::S3Client;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Columns exported when the request doesn't pick any
+const DEFAULT_FIELDS: [ExportField; 5] = [
+    ExportField::Key,
+    ExportField::Size,
+    ExportField::LastModified,
+    ExportField::ETag,
+    ExportField::StorageClass,
+];
+
+/// A listed object with the metadata and tags the export asked for
+#[derive(Debug)]
+pub struct ExportRow {
+    pub object: ObjectSummary,
+    pub metadata: Option<HashMap<String, String>>,
+    pub tags: Option<HashMap<String, String>>,
+}
+
+/// Writes a listing to disk row by row. Rows go to a `.part` file next to the destination,
+/// which only replaces the destination once `finish` is called, so a cancelled or failed export
+/// never leaves a truncated manifest behind.
+pub struct ListingWriter {
+    destination: PathBuf,
+    partial: PathBuf,
+    out: BufWriter<File>,
+    format: ExportFormat,
+    fields: Vec<ExportField>,
+    object_count: u64,
+    total_bytes: i64,
+}
+
+impl ListingWriter {
+    pub fn create(
+        destination: &Path,
+        format: ExportFormat,
+        fields: &[ExportField],
+    ) -> Result<Self> {
+        let mut unique = Vec::new();
+        for field in fields {
+            if !unique.contains(field) {
+                unique.push(*field);
+            }
+        }
+        if unique.is_empty() {
+            unique = DEFAULT_FIELDS.to_vec();
+        }
+
+        let mut partial = destination.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let file = File::create(&partial).map_err(|e| write_error(destination, e))?;
+
+        let mut writer = Self {
+            destination: destination.to_path_buf(),
+            partial,
+            out: BufWriter::new(file),
+            format,
+            fields: unique,
+            object_count: 0,
+            total_bytes: 0,
+        };
+        match format {
+            ExportFormat::Csv => {
+                let header: Vec<&str> = writer.fields.iter().map(|f| name(*f)).collect();
+                writer.write(&csv_format::row(&header))?;
+            }
+            ExportFormat::Json => writer.write("[")?,
+            ExportFormat::Ndjson => {}
+        }
+        Ok(writer)
+    }
+
+    pub fn includes(&self, field: ExportField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    pub fn write_row(&mut self, row: &ExportRow) -> Result<()> {
+        let line = match self.format {
+            ExportFormat::Csv => {
+                let fields: Vec<String> = self
+                    .fields
+                    .iter()
+                    .map(|field| match value(*field, row) {
+                        Value::Null => String::new(),
+                        Value::String(text) => text,
+                        // Maps stay unambiguous as JSON objects
+                        other => other.to_string(),
+                    })
+                    .collect();
+                csv_format::row(&fields)
+            }
+            ExportFormat::Json | ExportFormat::Ndjson => {
+                // Built by hand so the keys keep the requested column order
+                let members: Vec<String> = self
+                    .fields
+                    .iter()
+                    .map(|field| format!("{}:{}", json!(name(*field)), value(*field, row)))
+                    .collect();
+                let object = format!("{{{}}}", members.join(","));
+                match self.format {
+                    ExportFormat::Json if self.object_count == 0 => format!("\n{}", object),
+                    ExportFormat::Json => format!(",\n{}", object),
+                    _ => format!("{}\n", object),
+                }
+            }
+        };
+        self.write(&line)?;
+
+        self.object_count += 1;
+        self.total_bytes += row.object.size;
+        Ok(())
+    }
+
+    pub fn progress(&self) -> FolderStatsProgress {
+        FolderStatsProgress {
+            object_count: self.object_count,
+            total_bytes: self.total_bytes,
+        }
+    }
+
+    /// Complete the file and move it to the destination, returning the object count and bytes
+    pub fn finish(mut self) -> Result<(u64, i64)> {
+        if self.format == ExportFormat::Json {
+            self.write("\n]\n")?;
+        }
+        self.out
+            .flush()
+            .map_err(|e| write_error(&self.destination, e))?;
+        drop(self.out);
+        fs::rename(&self.partial, &self.destination)
+            .map_err(|e| write_error(&self.destination, e))?;
+        Ok((self.object_count, self.total_bytes))
+    }
+
+    /// Stop writing and remove the partial file
+    pub fn abandon(self) {
+        drop(self.out);
+        if let Err(e) = fs::remove_file(&self.partial) {
+            eprintln!("Failed to remove {}: {}", self.partial.display(), e);
+        }
+    }
+
+    fn write(&mut self, text: &str) -> Result<()> {
+        self.out
+            .write_all(text.as_bytes())
+            .map_err(|e| write_error(&self.destination, e))
+    }
+}
+
+fn write_error(destination: &Path, e: std::io::Error) -> S3DeckError {
+    S3DeckError::Io(format!(
+        "Failed to write listing export '{}': {}",
+        destination.display(),
+        e
+    ))
+}
+
+/// Column name in CSV headers and JSON keys
+fn name(field: ExportField) -> &'static str {
+    match field {
+        ExportField::Key => "key",
+        ExportField::Size => "size",
+        ExportField::LastModified => "lastModified",
+        ExportField::ETag => "etag",
+        ExportField::StorageClass => "storageClass",
+        ExportField::Metadata => "metadata",
+        ExportField::Tags => "tags",
+    }
+}
+
+fn value(field: ExportField, row: &ExportRow) -> Value {
+    // Sorted so rows are easy to compare
+    let map = |entries: &Option<HashMap<String, String>>| match entries {
+        Some(entries) => json!(entries.iter().collect::<BTreeMap<_, _>>()),
+        None => Value::Null,
+    };
+
+    let object = &row.object;
+    match field {
+        ExportField::Key => json!(object.key),
+        ExportField::Size => json!(object.size),
+        ExportField::LastModified => json!(object.last_modified.map(|dt| dt.to_rfc3339())),
+        // Without the quotes S3 puts around it, so single-part ETags read as plain MD5s
+        ExportField::ETag => json!(object.etag.as_deref().map(|etag| etag.trim_matches('"'))),
+        ExportField::StorageClass => {
+            json!(object.storage_class.as_deref().unwrap_or("STANDARD"))
+        }
+        ExportField::Metadata => map(&row.metadata),
+        ExportField::Tags => map(&row.tags),
+    }
+}
+
+/// List everything below a scoped prefix into `writer`, one page at a time, fetching metadata
+/// and tags per object when they are exported. Objects deleted while the export runs are left
+/// out. `on_progress` is called after each page. Returns false when `cancelled` was raised
+/// before the listing finished.
+pub async fn export_listing(
+    s3_client: &S3Client,
+    prefix: &str,
+    writer: &mut ListingWriter,
+    cancelled: &AtomicBool,
+    mut on_progress: impl FnMut(FolderStatsProgress),
+) -> Result<bool> {
+    let with_metadata = writer.includes(ExportField::Metadata);
+    let with_tags = writer.includes(ExportField::Tags);
+    let mut continuation_token = None;
+
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        let (page, next_token) = s3_client.list_page(prefix, continuation_token).await?;
+        for object in page {
+            if (with_metadata || with_tags) && cancelled.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+
+            let mut row = ExportRow {
+                object,
+                metadata: None,
+                tags: None,
+            };
+            let key = row.object.key.as_str();
+            let details = async {
+                if with_metadata {
+                    let metadata = s3_client
+                        .retry_object(|| s3_client.get_object_metadata(key))
+                        .await?;
+                    row.metadata = Some(metadata.metadata);
+                }
+                if with_tags {
+                    let tags = s3_client
+                        .retry_object(|| s3_client.get_object_tags(key))
+                        .await?;
+                    row.tags = Some(tags);
+                }
+                Ok(())
+            };
+            match details.await {
+                Ok(()) => writer.write_row(&row)?,
+                Err(S3DeckError::NoSuchKey(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        on_progress(writer.progress());
+
+        match next_token {
+            Some(token) => continuation_token = Some(token),
+            None => return Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeS3;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("s3deck-export-{}", uuid::Uuid::new_v4()))
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn test_export_listing_formats() {
+        let fake = FakeS3::start().await;
+        fake.put("data/a.csv", b"a,b\n1,2\n");
+        fake.put("data/sub/b, \"quoted\".bin", b"bytes");
+        fake.put("other/c.txt", b"not exported");
+        fake.set_tags("data/a.csv", &[("team", "ml"), ("delivery", "T-42")]);
+        let client = fake.client().await;
+        let not_cancelled = AtomicBool::new(false);
+
+        let destination = temp_file("listing.csv");
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        let mut writer = ListingWriter::create(&destination, ExportFormat::Csv, &[]).unwrap();
+        let completed = export_listing(&client, "data/", &mut writer, &not_cancelled, |_| {})
+            .await
+            .unwrap();
+        assert!(completed);
+        assert_eq!(writer.finish().unwrap(), (2, 13));
+
+        let csv = fs::read_to_string(&destination).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "key,size,lastModified,etag,storageClass");
+        assert!(lines[1].starts_with("data/a.csv,8,"));
+        assert!(lines[2].starts_with("\"data/sub/b, \"\"quoted\"\".bin\",5,"));
+        assert!(!lines[1].contains("\"\""));
+        assert!(!destination.with_extension("csv.part").exists());
+
+        let destination = temp_file("listing.json");
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        let fields = [ExportField::Key, ExportField::Tags, ExportField::Key];
+        let mut writer = ListingWriter::create(&destination, ExportFormat::Json, &fields).unwrap();
+        export_listing(&client, "data/", &mut writer, &not_cancelled, |_| {})
+            .await
+            .unwrap();
+        writer.finish().unwrap();
+
+        let rows: Value = serde_json::from_str(&fs::read_to_string(&destination).unwrap()).unwrap();
+        assert_eq!(
+            rows,
+            json!([
+                {"key": "data/a.csv", "tags": {"delivery": "T-42", "team": "ml"}},
+                {"key": "data/sub/b, \"quoted\".bin", "tags": {}},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_export_leaves_nothing() {
+        let fake = FakeS3::start().await;
+        fake.put("data/a.csv", b"a");
+        let client = fake.client().await;
+
+        let destination = temp_file("listing.ndjson");
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        let fields = [ExportField::Key, ExportField::Metadata];
+        let mut writer =
+            ListingWriter::create(&destination, ExportFormat::Ndjson, &fields).unwrap();
+        let completed = export_listing(
+            &client,
+            "data/",
+            &mut writer,
+            &AtomicBool::new(true),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert!(!completed);
+        writer.abandon();
+
+        assert!(!destination.exists());
+        assert!(fs::read_dir(destination.parent().unwrap())
+            .unwrap()
+            .next()
+            .is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::models::{HistoryEntry, OverwritePolicy, ReversibleOperation};
+use crate::s3_client::S3Client;
+use chrono::Utc;
+use std::sync::Mutex;
+
+/// Older entries are dropped once the history is this long
+const MAX_ENTRIES: usize = 50;
+
+/// Reversible operations done in this session, kept in Tauri state for the undo command
+#[derive(Default)]
+pub struct OperationHistory {
+    entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl OperationHistory {
+    pub fn record(&self, bucket_id: &str, description: String, operation: ReversibleOperation) {
+        self.push(HistoryEntry {
+            timestamp: Utc::now(),
+            bucket_id: bucket_id.to_string(),
+            description,
+            operation,
+        });
+    }
+
+    /// Put an entry back on top, e.g. after an undo that restored nothing
+    pub fn push(&self, entry: HistoryEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        if entries.len() > MAX_ENTRIES {
+            entries.remove(0);
+        }
+    }
+
+    pub fn pop(&self) -> Option<HistoryEntry> {
+        self.entries.lock().unwrap().pop()
+    }
+
+    /// Most recent first
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
+
+/// Reverse an operation, returning the keys put back and the keys that couldn't be.
+/// Nothing is overwritten: a key that has been reused since is reported as failed.
+pub async fn undo(
+    s3_client: &S3Client,
+    operation: &ReversibleOperation,
+) -> (Vec<String>, Vec<String>) {
+    let mut restored = Vec::new();
+    let mut failed = Vec::new();
+
+    match operation {
+        ReversibleOperation::Rename { moves } => {
+            for moved in moves.iter().rev() {
+                let result = s3_client
+                    .retry_object(|| {
+                        s3_client.rename_object(
+                            &moved.to,
+                            &moved.from,
+                            false,
+                            OverwritePolicy::Fail,
+                        )
+                    })
+                    .await;
+                match result {
+                    Ok(response) if response.moved_objects.is_empty() => {
+                        failed.push(moved.from.clone())
+                    }
+                    Ok(_) => restored.push(moved.from.clone()),
+                    Err(e) => {
+                        eprintln!("Failed to move {} back: {}", moved.to, e);
+                        failed.push(moved.from.clone());
+                    }
+                }
+            }
+        }
+        ReversibleOperation::Delete { items } => {
+            for item in items {
+                let result = s3_client
+                    .retry_object(|| s3_client.restore_trash_item(item, OverwritePolicy::Fail))
+                    .await;
+                match result {
+                    Ok(_) => restored.push(item.key.clone()),
+                    Err(e) => {
+                        eprintln!("Failed to restore {}: {}", item.key, e);
+                        failed.push(item.key.clone());
+                    }
+                }
+            }
+        }
+        ReversibleOperation::MetadataEdit { key, previous } => {
+            match s3_client.update_object_metadata(key, previous).await {
+                Ok(_) => restored.push(key.clone()),
+                Err(e) => {
+                    eprintln!("Failed to restore metadata of {}: {}", key, e);
+                    failed.push(key.clone());
+                }
+            }
+        }
+    }
+
+    (restored, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BucketConfig, ObjectHeaders};
+    use crate::test_support::{direct_network, FakeS3};
+
+    #[tokio::test]
+    async fn test_undo_folder_rename() {
+        let fake = FakeS3::start().await;
+        fake.put("old/a.txt", b"a");
+        fake.put("old/nested/b.txt", b"b");
+        let client = fake.client().await;
+
+        let response = client
+            .rename_object("old/", "new/", true, OverwritePolicy::Fail)
+            .await
+            .unwrap();
+        assert_eq!(response.moved_objects.len(), 2);
+
+        // A key reused since the rename is left alone
+        fake.put("old/a.txt", b"replacement");
+        let operation = ReversibleOperation::Rename {
+            moves: response.moved_objects,
+        };
+        let (restored, failed) = undo(&client, &operation).await;
+
+        assert_eq!(restored, vec!["old/nested/b.txt"]);
+        assert_eq!(failed, vec!["old/a.txt"]);
+        assert_eq!(fake.get("old/a.txt").unwrap().body, b"replacement");
+        assert_eq!(
+            fake.keys(),
+            vec!["new/a.txt", "old/a.txt", "old/nested/b.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undo_trashed_delete() {
+        let fake = FakeS3::start().await;
+        fake.put("docs/report.pdf", b"report");
+        let config = BucketConfig {
+            trash_enabled: true,
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+
+        let deletion = client.delete_object("docs/report.pdf", None).await.unwrap();
+        let operation = ReversibleOperation::Delete {
+            items: deletion.trashed,
+        };
+        let (restored, failed) = undo(&client, &operation).await;
+
+        assert_eq!(restored, vec!["docs/report.pdf"]);
+        assert!(failed.is_empty());
+        assert_eq!(fake.keys(), vec!["docs/report.pdf"]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_metadata_edit() {
+        let fake = FakeS3::start().await;
+        fake.put("site/index.html", b"<html>");
+        fake.set_storage_class("site/index.html", "STANDARD_IA");
+        let client = fake.client().await;
+
+        let original = client.get_object_headers("site/index.html").await.unwrap();
+        let previous = client
+            .update_object_metadata(
+                "site/index.html",
+                &ObjectHeaders {
+                    content_type: Some("text/html".to_string()),
+                    cache_control: Some("max-age=300".to_string()),
+                    metadata: [("owner".to_string(), "web".to_string())].into(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(previous, original);
+
+        let edited = client.get_object_headers("site/index.html").await.unwrap();
+        assert_eq!(edited.cache_control.as_deref(), Some("max-age=300"));
+        assert_eq!(
+            edited.metadata.get("owner").map(String::as_str),
+            Some("web")
+        );
+        assert_eq!(
+            fake.get("site/index.html").unwrap().storage_class(),
+            "STANDARD_IA"
+        );
+
+        let operation = ReversibleOperation::MetadataEdit {
+            key: "site/index.html".to_string(),
+            previous,
+        };
+        let (restored, failed) = undo(&client, &operation).await;
+
+        assert_eq!(restored, vec!["site/index.html"]);
+        assert!(failed.is_empty());
+        assert_eq!(
+            client.get_object_headers("site/index.html").await.unwrap(),
+            original
+        );
+        let object = fake.get("site/index.html").unwrap();
+        assert_eq!(object.body, b"<html>");
+        assert_eq!(object.storage_class(), "STANDARD_IA");
+    }
+}
@@ -0,0 +1,459 @@
+use crate::models::{BucketConfig, NetworkSettings, ProxySettings, Result, S3DeckError};
+use aws_sdk_s3::config::SharedHttpClient;
+use aws_smithy_http_client::proxy::ProxyConfig;
+use aws_smithy_http_client::tls::{self, rustls_provider::CryptoMode, TlsContext, TrustStore};
+use aws_smithy_http_client::{hyper_014::HyperClientBuilder, Builder, Connector};
+use hyper::client::connect::{Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::Uri;
+use hyper_util::client::proxy::matcher::Matcher;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Build the HTTP client used for SDK requests from the global network settings,
+/// applying the bucket's own proxy and CA overrides when one is given
+pub fn build_http_client(
+    network: &NetworkSettings,
+    bucket: Option<&BucketConfig>,
+) -> Result<SharedHttpClient> {
+    let proxy_settings = bucket
+        .and_then(|b| b.proxy.as_ref())
+        .unwrap_or(&network.proxy);
+    let proxy_config = proxy_config(proxy_settings)?;
+
+    // Certificate checks are only ever skipped for an explicitly configured HTTPS endpoint,
+    // never for AWS itself
+    if let Some(bucket) = bucket {
+        let https_endpoint = bucket
+            .endpoint
+            .as_deref()
+            .is_some_and(|endpoint| !endpoint.starts_with("http://"));
+        if bucket.allow_invalid_certificates && https_endpoint {
+            return insecure_http_client(proxy_settings);
+        }
+    }
+
+    let bucket_ca_bundles = bucket.map(|b| b.ca_bundles.as_slice()).unwrap_or_default();
+    let mut trust_store = TrustStore::empty().with_native_roots(true);
+    for path in network.ca_bundles.iter().chain(bucket_ca_bundles) {
+        trust_store = trust_store.with_pem_certificate(read_ca_bundle(path)?);
+    }
+    let tls_context = TlsContext::builder()
+        .with_trust_store(trust_store)
+        .build()
+        .map_err(|e| S3DeckError::Config(format!("Invalid CA bundle: {}", e)))?;
+
+    // The typed builder can't set a proxy yet, so assemble the connector ourselves the
+    // same way the default client does
+    Ok(
+        Builder::new().build_with_connector_fn(move |settings, components| {
+            let mut connector = Connector::builder();
+            connector.set_connector_settings(settings.cloned());
+            if let Some(components) = components {
+                connector.set_sleep_impl(components.sleep_impl());
+            }
+            connector
+                .proxy_config(proxy_config.clone())
+                .tls_provider(tls::Provider::Rustls(CryptoMode::AwsLc))
+                .tls_context(tls_context.clone())
+                .build()
+        }),
+    )
+}
+
+fn proxy_config(settings: &ProxySettings) -> Result<ProxyConfig> {
+    if settings.disabled {
+        return Ok(ProxyConfig::disabled());
+    }
+
+    let url = match settings.url.as_deref().map(str::trim) {
+        Some(url) if !url.is_empty() => url,
+        // No explicit proxy: honor HTTPS_PROXY, HTTP_PROXY and NO_PROXY
+        _ => return Ok(ProxyConfig::from_env()),
+    };
+
+    let mut config = ProxyConfig::all(url)
+        .map_err(|e| S3DeckError::Config(format!("Invalid proxy URL '{}': {}", url, e)))?;
+
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        config = config.with_basic_auth(username, password);
+    }
+    if let Some(no_proxy) = &settings.no_proxy {
+        config = config.no_proxy(no_proxy);
+    }
+
+    Ok(config)
+}
+
+fn read_ca_bundle(path: &str) -> Result<Vec<u8>> {
+    let pem = std::fs::read(path)
+        .map_err(|e| S3DeckError::Config(format!("Failed to read CA bundle '{}': {}", path, e)))?;
+
+    if !String::from_utf8_lossy(&pem).contains("-----BEGIN CERTIFICATE-----") {
+        return Err(S3DeckError::Config(format!(
+            "CA bundle '{}' does not contain any PEM certificates",
+            path
+        )));
+    }
+
+    Ok(pem)
+}
+
+/// Client for self-signed gateways such as an internal Ceph RGW. Certificates aren't verified,
+/// but connections otherwise use the same rustls and aws-lc stack as every other bucket and go
+/// through the configured proxy.
+fn insecure_http_client(proxy_settings: &ProxySettings) -> Result<SharedHttpClient> {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let tls_config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| S3DeckError::Config(format!("Invalid TLS configuration: {}", e)))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyServerCertificate(provider)))
+        .with_no_client_auth();
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    let connector = InsecureConnector {
+        http,
+        tls: TlsConnector::from(Arc::new(tls_config)),
+        proxy: Arc::new(proxy_matcher(proxy_settings)),
+    };
+
+    // The legacy hyper client is the SDK's only hook for a custom connector; it still applies
+    // the bucket's connect and read timeouts
+    Ok(HyperClientBuilder::new().build(connector))
+}
+
+/// The proxy rules of `proxy_config` in the form the insecure connector matches against
+fn proxy_matcher(settings: &ProxySettings) -> Matcher {
+    if settings.disabled {
+        return Matcher::builder().build();
+    }
+    let url = match settings.url.as_deref().map(str::trim) {
+        Some(url) if !url.is_empty() => url,
+        _ => return Matcher::from_env(),
+    };
+
+    // Credentials travel in the proxy URL, percent-encoded
+    let url = match (
+        &settings.username,
+        &settings.password,
+        url.split_once("://"),
+    ) {
+        (Some(username), Some(password), Some((scheme, rest))) => format!(
+            "{}://{}:{}@{}",
+            scheme,
+            urlencoding::encode(username),
+            urlencoding::encode(password),
+            rest
+        ),
+        _ => url.to_string(),
+    };
+    let mut builder = Matcher::builder().all(url);
+    if let Some(no_proxy) = &settings.no_proxy {
+        builder = builder.no(no_proxy.clone());
+    }
+    builder.build()
+}
+
+#[derive(Clone)]
+struct InsecureConnector {
+    http: HttpConnector,
+    tls: TlsConnector,
+    proxy: Arc<Matcher>,
+}
+
+impl Service<Uri> for InsecureConnector {
+    type Response = InsecureStream;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = std::result::Result<InsecureStream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), BoxError>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, destination: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(destination))
+    }
+}
+
+impl InsecureConnector {
+    async fn connect(mut self, destination: Uri) -> std::result::Result<InsecureStream, BoxError> {
+        // Certificates only matter over TLS, so plain HTTP endpoints never get here
+        if destination.scheme_str() != Some("https") {
+            return Err(format!("'{}' is not an HTTPS endpoint", destination).into());
+        }
+        let host = destination
+            .host()
+            .ok_or("endpoint has no host")?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = destination.port_u16().unwrap_or(443);
+
+        let intercept = self
+            .proxy
+            .intercept(&destination.to_string().parse::<http::Uri>()?);
+        let tcp = match intercept {
+            Some(proxy) => {
+                if proxy.uri().scheme_str() != Some("http") {
+                    return Err(format!(
+                        "Proxy '{}' must use http:// for buckets that allow invalid certificates",
+                        proxy.uri()
+                    )
+                    .into());
+                }
+                let mut tcp = self.http.call(proxy.uri().to_string().parse()?).await?;
+                tunnel(&mut tcp, &host, port, proxy.basic_auth()).await?;
+                tcp
+            }
+            None => self.http.call(destination).await?,
+        };
+
+        let server_name = ServerName::try_from(host)?;
+        Ok(InsecureStream(self.tls.connect(server_name, tcp).await?))
+    }
+}
+
+/// Open a `CONNECT` tunnel to `host:port` through an HTTP proxy
+async fn tunnel(
+    tcp: &mut TcpStream,
+    host: &str,
+    port: u16,
+    authorization: Option<&http::HeaderValue>,
+) -> std::result::Result<(), BoxError> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority).into_bytes();
+    if let Some(authorization) = authorization {
+        request.extend_from_slice(b"Proxy-Authorization: ");
+        request.extend_from_slice(authorization.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"\r\n");
+    tcp.write_all(&request).await?;
+
+    // The proxy sends nothing after its response until the TLS handshake starts, so reading
+    // whole chunks can't swallow any of the tunneled bytes
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = tcp.read(&mut buffer).await?;
+        if read == 0 || response.len() > 16 * 1024 {
+            return Err("proxy closed the tunnel before responding".into());
+        }
+        response.extend_from_slice(&buffer[..read]);
+    }
+
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(format!("proxy refused the tunnel: {}", status_line).into()),
+    }
+}
+
+struct InsecureStream(TlsStream<TcpStream>);
+
+impl Connection for InsecureStream {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for InsecureStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for InsecureStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Accepts any certificate and host name, but still checks handshake signatures so the
+/// connection is at least encrypted to whoever holds the certificate's key
+#[derive(Debug)]
+struct AcceptAnyServerCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyServerCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_proxy_url_must_be_valid() {
+        let settings = ProxySettings {
+            url: Some("not a url".to_string()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            proxy_config(&settings),
+            Err(S3DeckError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn proxy_falls_back_to_environment_unless_disabled() {
+        let from_env = proxy_config(&ProxySettings::default()).unwrap();
+        assert!(from_env.is_from_env());
+
+        let disabled = proxy_config(&ProxySettings {
+            url: Some("http://proxy.internal:3128".to_string()),
+            disabled: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(disabled.is_disabled());
+    }
+
+    #[test]
+    fn insecure_client_uses_proxy_credentials_and_exceptions() {
+        let matcher = proxy_matcher(&ProxySettings {
+            url: Some("http://proxy.internal:3128".to_string()),
+            username: Some("svc".to_string()),
+            password: Some("p@ss:word".to_string()),
+            no_proxy: Some("ceph.internal".to_string()),
+            ..Default::default()
+        });
+
+        let gateway = http::Uri::from_static("https://rgw.example.com/bucket");
+        let proxy = matcher.intercept(&gateway).unwrap();
+        assert_eq!(proxy.uri(), "http://proxy.internal:3128/");
+        // base64 of "svc:p@ss:word"
+        assert_eq!(proxy.basic_auth().unwrap(), "Basic c3ZjOnBAc3M6d29yZA==");
+
+        let excluded = http::Uri::from_static("https://ceph.internal/bucket");
+        assert!(matcher.intercept(&excluded).is_none());
+    }
+
+    #[tokio::test]
+    async fn tunnel_sends_connect_through_proxy() {
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for reply in ["HTTP/1.1 200 Connection established", "HTTP/1.1 407 Auth"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0u8; 1024];
+                let read = socket.read(&mut buffer).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buffer[..read]).to_string());
+                let response = format!("{}\r\n\r\n", reply);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        let authorization = http::HeaderValue::from_static("Basic c3ZjOnNlY3JldA==");
+        let mut tcp = TcpStream::connect(address).await.unwrap();
+        tunnel(&mut tcp, "rgw.example.com", 443, Some(&authorization))
+            .await
+            .unwrap();
+        let mut tcp = TcpStream::connect(address).await.unwrap();
+        let refused = tunnel(&mut tcp, "::1", 8443, None).await.unwrap_err();
+        assert!(refused.to_string().contains("407"));
+
+        let requests = proxy.await.unwrap();
+        assert_eq!(
+            requests[0],
+            "CONNECT rgw.example.com:443 HTTP/1.1\r\nHost: rgw.example.com:443\r\n\
+             Proxy-Authorization: Basic c3ZjOnNlY3JldA==\r\n\r\n"
+        );
+        assert!(requests[1].starts_with("CONNECT [::1]:8443 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn ca_bundle_must_contain_certificates() {
+        let dir = std::env::temp_dir().join(format!("s3deck-ca-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.pem");
+        std::fs::write(&path, "not a certificate").unwrap();
+
+        let result = read_ca_bundle(path.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(S3DeckError::Config(_))));
+    }
+}
@@ -0,0 +1,817 @@
+use crate::config::app_dir;
+use crate::models::{
+    FileItem, IndexStatus, IndexedInventory, IndexedListing, IndexedPrefix, ObjectSummary, Result,
+    S3DeckError, SearchSummary,
+};
+use crate::search::SearchFilter;
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Indexed objects are handed out in pages of this size, like a live listing
+const PAGE_SIZE: usize = 1000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS objects (
+        key TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        last_modified INTEGER,
+        etag TEXT,
+        storage_class TEXT,
+        -- When the crawl or listing that last saw the key started
+        seen_at INTEGER NOT NULL
+    ) WITHOUT ROWID;
+
+    CREATE TABLE IF NOT EXISTS listings (
+        prefix TEXT NOT NULL,
+        -- 1 for a crawl of everything below the prefix, 0 for a single-level listing
+        recursive INTEGER NOT NULL,
+        indexed_at INTEGER NOT NULL,
+        PRIMARY KEY (prefix, recursive)
+    );
+
+    CREATE TABLE IF NOT EXISTS inventories (
+        prefix TEXT PRIMARY KEY,
+        inventory_date INTEGER NOT NULL,
+        manifest TEXT NOT NULL,
+        imported_at INTEGER NOT NULL
+    );
+";
+
+/// Local SQLite copy of a bucket's object listing, at `~/.s3deck/index/<bucket id>.sqlite`.
+/// It is filled by crawling a prefix or importing an S3 Inventory report, and refreshed level
+/// by level as folders are browsed.
+pub struct MetadataIndex {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl MetadataIndex {
+    fn path_for(bucket_id: &str) -> Result<PathBuf> {
+        let dir = app_dir()?.join("index");
+        fs::create_dir_all(&dir)?;
+        Ok(dir.join(format!("{}.sqlite", bucket_id)))
+    }
+
+    /// Open a bucket's index, creating it if needed
+    pub fn open(bucket_id: &str) -> Result<Self> {
+        Self::at(Self::path_for(bucket_id)?)
+    }
+
+    /// Open a bucket's index only if one has been built
+    pub fn open_existing(bucket_id: &str) -> Result<Option<Self>> {
+        let path = Self::path_for(bucket_id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::at(path).map(Some)
+    }
+
+    /// Remove a bucket's index, returning false when there was none
+    pub fn delete(bucket_id: &str) -> Result<bool> {
+        let path = Self::path_for(bucket_id)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(path)?;
+        Ok(true)
+    }
+
+    pub fn at(path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path,
+        })
+    }
+
+    /// Store a page of a crawl that started at `crawl`
+    pub fn insert_objects(&self, crawl: DateTime<Utc>, objects: &[ObjectSummary]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO objects (key, size, last_modified, etag, storage_class, seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (key) DO UPDATE SET
+                     size = excluded.size,
+                     last_modified = excluded.last_modified,
+                     etag = excluded.etag,
+                     storage_class = excluded.storage_class,
+                     seen_at = excluded.seen_at",
+            )?;
+            for object in objects {
+                insert.execute(params![
+                    object.key,
+                    object.size,
+                    object.last_modified.map(|m| m.timestamp_millis()),
+                    object.etag,
+                    object.storage_class,
+                    crawl.timestamp_millis(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Complete a crawl of `prefix`: keys it didn't see are gone from the bucket
+    pub fn finish_crawl(&self, prefix: &str, crawl: DateTime<Utc>) -> Result<()> {
+        let (condition, mut values) = below(prefix);
+        values.push(Value::Integer(crawl.timestamp_millis()));
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!(
+                "DELETE FROM objects WHERE {} AND seen_at < ?{}",
+                condition,
+                values.len()
+            ),
+            params_from_iter(&values),
+        )?;
+        // The crawl is newer than any inventory report loaded below the prefix
+        tx.execute(
+            "DELETE FROM inventories WHERE substr(prefix, 1, length(?1)) = ?1",
+            [prefix],
+        )?;
+        record_listing(&tx, prefix, true, crawl)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Start importing an inventory report. Its objects are staged in a temporary table until
+    /// `finish_import`, so an import that fails part way leaves the index as it was.
+    pub fn begin_import(&self) -> Result<()> {
+        self.conn.lock().unwrap().execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS staged_objects (
+                 key TEXT PRIMARY KEY,
+                 size INTEGER NOT NULL,
+                 last_modified INTEGER,
+                 etag TEXT,
+                 storage_class TEXT
+             ) WITHOUT ROWID;
+             DELETE FROM staged_objects;",
+        )?;
+        Ok(())
+    }
+
+    /// Stage a page of the inventory report being imported
+    pub fn stage_objects(&self, objects: &[ObjectSummary]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO staged_objects (key, size, last_modified, etag, storage_class)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for object in objects {
+                insert.execute(params![
+                    object.key,
+                    object.size,
+                    object.last_modified.map(|m| m.timestamp_millis()),
+                    object.etag,
+                    object.storage_class,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Complete the import of an inventory report of `prefix` by moving its staged objects into
+    /// the index with the report's date. The report replaces whatever was indexed below the
+    /// prefix, even when that was newer, until the prefix is crawled or listed again.
+    pub fn finish_import(
+        &self,
+        prefix: &str,
+        inventory_date: DateTime<Utc>,
+        manifest: &str,
+    ) -> Result<()> {
+        let (condition, values) = below(prefix);
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!("DELETE FROM objects WHERE {}", condition),
+            params_from_iter(&values),
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO objects
+                 (key, size, last_modified, etag, storage_class, seen_at)
+             SELECT key, size, last_modified, etag, storage_class, ?1 FROM staged_objects",
+            [inventory_date.timestamp_millis()],
+        )?;
+        tx.execute("DELETE FROM staged_objects", [])?;
+        for table in ["listings", "inventories"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE substr(prefix, 1, length(?1)) = ?1",
+                    table
+                ),
+                [prefix],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO inventories (prefix, inventory_date, manifest, imported_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                prefix,
+                inventory_date.timestamp_millis(),
+                manifest,
+                Utc::now().timestamp_millis()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Bring one level in line with a live listing of it. Only a `complete` listing can tell
+    /// which indexed keys were deleted; a partial one just refreshes the keys it holds.
+    pub fn record_level(
+        &self,
+        prefix: &str,
+        items: &[FileItem],
+        complete: bool,
+        listed_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            // A changed object has a new ETag and may have a new storage class, which only a
+            // crawl can tell
+            let mut upsert = tx.prepare_cached(
+                "INSERT INTO objects (key, size, last_modified, seen_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (key) DO UPDATE SET
+                     etag = CASE WHEN size = excluded.size AND last_modified IS excluded.last_modified
+                         THEN etag END,
+                     storage_class = CASE WHEN size = excluded.size AND last_modified IS excluded.last_modified
+                         THEN storage_class END,
+                     size = excluded.size,
+                     last_modified = excluded.last_modified,
+                     seen_at = excluded.seen_at",
+            )?;
+            for item in items.iter().filter(|item| !item.is_folder) {
+                upsert.execute(params![
+                    item.key,
+                    item.size,
+                    item.last_modified.map(|m| m.timestamp_millis()),
+                    listed_at.timestamp_millis(),
+                ])?;
+            }
+        }
+
+        if complete {
+            let listed: HashSet<&str> = items.iter().map(|item| item.key.as_str()).collect();
+            let (files, folders) = level(&tx, prefix)?;
+            for file in files.iter().filter(|f| !listed.contains(f.key.as_str())) {
+                tx.execute("DELETE FROM objects WHERE key = ?1", [&file.key])?;
+            }
+            for folder in folders.iter().filter(|f| !listed.contains(f.as_str())) {
+                let (condition, values) = below(folder);
+                tx.execute(
+                    &format!("DELETE FROM objects WHERE {}", condition),
+                    params_from_iter(&values),
+                )?;
+            }
+            record_listing(&tx, prefix, false, listed_at)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The folders and files directly below a prefix, as last seen
+    pub fn list_level(&self, prefix: &str) -> Result<IndexedListing> {
+        let conn = self.conn.lock().unwrap();
+        let (files, folders) = level(&conn, prefix)?;
+
+        let mut items: Vec<FileItem> = folders
+            .into_iter()
+            .map(|folder| FileItem {
+                name: folder[prefix.len()..].trim_end_matches('/').to_string(),
+                key: folder,
+                size: 0,
+                is_folder: true,
+                last_modified: None,
+                storage_class: None,
+            })
+            .collect();
+        items.extend(files.iter().map(ObjectSummary::file_item));
+
+        // A crawl of the level or any folder above it is as good as listing the level itself
+        let dates = indexed_at(&conn, prefix, true)?;
+        Ok(IndexedListing {
+            items,
+            indexed_at: dates.indexed_at,
+            inventory_date: dates.inventory_date,
+        })
+    }
+
+    /// When everything below a prefix was last crawled, or the date of the inventory report it
+    /// was loaded from. `None` if it never was either.
+    pub fn crawled_at(&self, prefix: &str) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.conn.lock().unwrap();
+        crawled_at(&conn, prefix)
+    }
+
+    /// Date of the inventory report a prefix was loaded from, `None` when it wasn't or it was
+    /// crawled since
+    pub fn inventory_date(&self, prefix: &str) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(indexed_at(&conn, prefix, false)?.inventory_date)
+    }
+
+    /// Newest modification time below a prefix
+    pub fn latest_modified(&self, prefix: &str) -> Result<Option<DateTime<Utc>>> {
+        let (condition, values) = below(prefix);
+        let conn = self.conn.lock().unwrap();
+        let latest: Option<i64> = conn.query_row(
+            &format!("SELECT MAX(last_modified) FROM objects WHERE {}", condition),
+            params_from_iter(&values),
+            |row| row.get(0),
+        )?;
+        Ok(latest.and_then(DateTime::from_timestamp_millis))
+    }
+
+    /// Hand every object below a crawled prefix to `on_page`, in pages like a live walk.
+    /// Returns false when `cancelled` was raised before the walk finished.
+    pub fn walk_objects(
+        &self,
+        prefix: &str,
+        cancelled: &AtomicBool,
+        mut on_page: impl FnMut(Vec<ObjectSummary>),
+    ) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        if crawled_at(&conn, prefix)?.is_none() {
+            return Err(S3DeckError::Index(format!(
+                "'{}' hasn't been indexed yet, build the index first",
+                prefix
+            )));
+        }
+
+        let (condition, values) = below(prefix);
+        let mut statement = conn.prepare(&format!(
+            "SELECT key, size, last_modified, etag, storage_class FROM objects WHERE {} ORDER BY key",
+            condition
+        ))?;
+        let mut rows = statement.query(params_from_iter(&values))?;
+
+        let mut page = Vec::new();
+        while let Some(row) = rows.next()? {
+            page.push(object_from_row(row)?);
+            if page.len() >= PAGE_SIZE {
+                on_page(std::mem::take(&mut page));
+                if cancelled.load(Ordering::Relaxed) {
+                    return Ok(false);
+                }
+            }
+        }
+        if !page.is_empty() {
+            on_page(page);
+        }
+
+        Ok(true)
+    }
+
+    /// Search a crawled prefix, handing matches to `on_matches` in batches
+    pub fn search(
+        &self,
+        prefix: &str,
+        filter: &SearchFilter,
+        cancelled: &AtomicBool,
+        mut on_matches: impl FnMut(Vec<FileItem>),
+    ) -> Result<SearchSummary> {
+        let mut summary = SearchSummary {
+            indexed_at: self.crawled_at(prefix)?,
+            inventory_date: self.inventory_date(prefix)?,
+            ..Default::default()
+        };
+
+        let completed = self.walk_objects(prefix, cancelled, |page| {
+            summary.scanned += page.len() as u64;
+            let matches = filter.select(prefix, &page);
+            if !matches.is_empty() {
+                summary.matched += matches.len() as u64;
+                on_matches(matches);
+            }
+        })?;
+        summary.cancelled = !completed;
+
+        Ok(summary)
+    }
+
+    pub fn status(&self, bucket_id: &str) -> Result<IndexStatus> {
+        let conn = self.conn.lock().unwrap();
+        let (object_count, total_bytes): (i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM objects",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let mut statement = conn.prepare(
+            "SELECT prefix, indexed_at FROM listings WHERE recursive = 1 ORDER BY prefix",
+        )?;
+        let crawled_prefixes = statement
+            .query_map([], |row| {
+                Ok(IndexedPrefix {
+                    prefix: row.get(0)?,
+                    indexed_at: DateTime::from_timestamp_millis(row.get(1)?).unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut statement = conn.prepare(
+            "SELECT prefix, inventory_date, manifest, imported_at FROM inventories ORDER BY prefix",
+        )?;
+        let inventories = statement
+            .query_map([], |row| {
+                Ok(IndexedInventory {
+                    prefix: row.get(0)?,
+                    inventory_date: DateTime::from_timestamp_millis(row.get(1)?)
+                        .unwrap_or_default(),
+                    manifest: row.get(2)?,
+                    imported_at: DateTime::from_timestamp_millis(row.get(3)?).unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(IndexStatus {
+            bucket_id: bucket_id.to_string(),
+            object_count: object_count as u64,
+            total_bytes,
+            crawled_prefixes,
+            size_on_disk: fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+            inventories,
+        })
+    }
+}
+
+/// Run index work on the blocking thread pool, since every index call waits on SQLite
+pub async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .unwrap_or_else(|e| Err(S3DeckError::Index(format!("Index task failed: {}", e))))
+}
+
+fn record_listing(
+    conn: &Connection,
+    prefix: &str,
+    recursive: bool,
+    indexed_at: DateTime<Utc>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO listings (prefix, recursive, indexed_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (prefix, recursive) DO UPDATE SET indexed_at = excluded.indexed_at",
+        params![prefix, recursive, indexed_at.timestamp_millis()],
+    )?;
+    Ok(())
+}
+
+fn crawled_at(conn: &Connection, prefix: &str) -> Result<Option<DateTime<Utc>>> {
+    Ok(indexed_at(conn, prefix, false)?.indexed_at)
+}
+
+/// Where a prefix's indexed data comes from
+struct IndexDates {
+    /// When the prefix was last crawled or listed, or the date of the inventory report
+    indexed_at: Option<DateTime<Utc>>,
+    /// Set when the data comes from an inventory report
+    inventory_date: Option<DateTime<Utc>>,
+}
+
+/// When a prefix was last crawled, or listed when `level` is set, along with the date of the
+/// inventory report it was loaded from if that import came later. Reports are older than
+/// their import, so their date counts as the prefix's index date.
+fn indexed_at(conn: &Connection, prefix: &str, level: bool) -> Result<IndexDates> {
+    let listed_at: Option<i64> = conn.query_row(
+        "SELECT MAX(indexed_at) FROM listings
+         WHERE (recursive = 1 AND substr(?1, 1, length(prefix)) = prefix)
+            OR (recursive = 0 AND prefix = ?1 AND ?2)",
+        params![prefix, level],
+        |row| row.get(0),
+    )?;
+    let inventory: Option<(i64, i64)> = conn
+        .query_row(
+            "SELECT inventory_date, imported_at FROM inventories
+             WHERE substr(?1, 1, length(prefix)) = prefix
+             ORDER BY imported_at DESC LIMIT 1",
+            [prefix],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    Ok(match inventory {
+        Some((inventory_date, imported_at))
+            if listed_at.is_none_or(|listed_at| imported_at > listed_at) =>
+        {
+            let inventory_date = DateTime::from_timestamp_millis(inventory_date);
+            IndexDates {
+                indexed_at: inventory_date,
+                inventory_date,
+            }
+        }
+        _ => IndexDates {
+            indexed_at: listed_at.and_then(DateTime::from_timestamp_millis),
+            inventory_date: None,
+        },
+    })
+}
+
+/// Files and folder prefixes directly below a prefix. Each folder is skipped over with a single
+/// seek, so levels stay fast however many keys sit further down.
+fn level(conn: &Connection, prefix: &str) -> Result<(Vec<ObjectSummary>, Vec<String>)> {
+    const COLUMNS: &str = "key, size, last_modified, etag, storage_class";
+    let mut after = conn.prepare_cached(&format!(
+        "SELECT {} FROM objects WHERE key > ?1 ORDER BY key LIMIT 1",
+        COLUMNS
+    ))?;
+    let mut from = conn.prepare_cached(&format!(
+        "SELECT {} FROM objects WHERE key >= ?1 ORDER BY key LIMIT 1",
+        COLUMNS
+    ))?;
+
+    let mut files = Vec::new();
+    let mut folders = Vec::new();
+    // Folder markers aren't indexed, so the prefix itself is never a key
+    let mut next = after.query_row([prefix], object_from_row).optional()?;
+
+    while let Some(object) = next.take().filter(|o| o.key.starts_with(prefix)) {
+        match object.key[prefix.len()..].find('/') {
+            Some(position) => {
+                let folder = object.key[..prefix.len() + position + 1].to_string();
+                if let Some(end) = prefix_end(&folder) {
+                    next = from.query_row([&end], object_from_row).optional()?;
+                }
+                folders.push(folder);
+            }
+            None => {
+                next = after.query_row([&object.key], object_from_row).optional()?;
+                files.push(object);
+            }
+        }
+    }
+
+    Ok((files, folders))
+}
+
+fn object_from_row(row: &rusqlite::Row) -> rusqlite::Result<ObjectSummary> {
+    Ok(ObjectSummary {
+        key: row.get(0)?,
+        size: row.get(1)?,
+        last_modified: row
+            .get::<_, Option<i64>>(2)?
+            .and_then(DateTime::from_timestamp_millis),
+        etag: row.get(3)?,
+        storage_class: row.get(4)?,
+    })
+}
+
+/// `WHERE` condition and its parameters selecting the keys below a prefix
+fn below(prefix: &str) -> (&'static str, Vec<Value>) {
+    let start = Value::Text(prefix.to_string());
+    match prefix_end(prefix) {
+        Some(end) => ("key >= ?1 AND key < ?2", vec![start, Value::Text(end)]),
+        None => ("key >= ?1", vec![start]),
+    }
+}
+
+/// The smallest string sorting after every key that starts with `prefix`, or `None` when
+/// there is none, e.g. for the empty prefix. SQLite compares text byte by byte and UTF-8
+/// keeps code point order, so bumping the last character is enough.
+fn prefix_end(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SearchMatchMode, SearchRequest};
+    use chrono::Duration;
+
+    fn temp_index() -> (MetadataIndex, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("s3deck-index-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        (MetadataIndex::at(dir.join("index.sqlite")).unwrap(), dir)
+    }
+
+    fn object(key: &str, size: i64) -> ObjectSummary {
+        ObjectSummary {
+            key: key.to_string(),
+            size,
+            last_modified: DateTime::from_timestamp(1_700_000_000, 0),
+            etag: Some(format!("\"{}\"", size)),
+            storage_class: None,
+        }
+    }
+
+    fn keys(listing: &IndexedListing) -> Vec<&str> {
+        listing.items.iter().map(|item| item.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_crawl_and_list_levels() {
+        let (index, dir) = temp_index();
+        let crawl = Utc::now();
+        index
+            .insert_objects(
+                crawl,
+                &[
+                    object("a.txt", 1),
+                    object("data/2024/03/orders.csv", 10),
+                    object("data/2024/04/orders.csv", 20),
+                    object("data/readme.md", 5),
+                    object("data0.txt", 2),
+                ],
+            )
+            .unwrap();
+        index.finish_crawl("", crawl).unwrap();
+
+        let root = index.list_level("").unwrap();
+        assert_eq!(keys(&root), vec!["data/", "a.txt", "data0.txt"]);
+        assert_eq!(
+            root.indexed_at,
+            DateTime::from_timestamp_millis(crawl.timestamp_millis())
+        );
+
+        let data = index.list_level("data/").unwrap();
+        assert_eq!(keys(&data), vec!["data/2024/", "data/readme.md"]);
+        assert_eq!(data.items[0].name, "2024");
+        assert!(data.indexed_at.is_some());
+
+        assert_eq!(
+            index.latest_modified("data/2024/").unwrap(),
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+        assert_eq!(index.status("b1").unwrap().object_count, 5);
+
+        // A later crawl drops what it didn't see
+        let recrawl = crawl + Duration::seconds(1);
+        index
+            .insert_objects(recrawl, &[object("data/readme.md", 6)])
+            .unwrap();
+        index.finish_crawl("data/", recrawl).unwrap();
+        assert_eq!(
+            keys(&index.list_level("data/").unwrap()),
+            vec!["data/readme.md"]
+        );
+        assert_eq!(
+            keys(&index.list_level("").unwrap()),
+            vec!["data/", "a.txt", "data0.txt"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_complete_listing_removes_deleted_keys() {
+        let (index, dir) = temp_index();
+        let crawl = Utc::now();
+        index
+            .insert_objects(
+                crawl,
+                &[
+                    object("logs/old/1.log", 1),
+                    object("logs/app.log", 1),
+                    object("logs/gone.log", 1),
+                ],
+            )
+            .unwrap();
+        index.finish_crawl("", crawl).unwrap();
+
+        let listed = vec![
+            object("logs/app.log", 3).file_item(),
+            object("logs/new.log", 4).file_item(),
+        ];
+        index
+            .record_level("logs/", &listed, false, crawl + Duration::seconds(1))
+            .unwrap();
+        // A partial listing can't tell what was deleted
+        assert_eq!(
+            keys(&index.list_level("logs/").unwrap()),
+            vec!["logs/old/", "logs/app.log", "logs/gone.log", "logs/new.log"]
+        );
+
+        index
+            .record_level("logs/", &listed, true, crawl + Duration::seconds(2))
+            .unwrap();
+        let logs = index.list_level("logs/").unwrap();
+        assert_eq!(keys(&logs), vec!["logs/app.log", "logs/new.log"]);
+        assert_eq!(logs.items[0].size, 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_needs_a_crawl() {
+        let (index, dir) = temp_index();
+        let filter = SearchFilter::new(&SearchRequest {
+            query: Some("*.csv".to_string()),
+            match_mode: SearchMatchMode::Glob,
+            ..Default::default()
+        })
+        .unwrap();
+        let not_cancelled = AtomicBool::new(false);
+
+        let result = index.search("", &filter, &not_cancelled, |_| {});
+        assert!(matches!(result, Err(S3DeckError::Index(_))));
+
+        let crawl = Utc::now();
+        index
+            .insert_objects(
+                crawl,
+                &[
+                    object("data/a.csv", 1),
+                    object("data/b.txt", 1),
+                    object("other.csv", 1),
+                ],
+            )
+            .unwrap();
+        index.finish_crawl("data/", crawl).unwrap();
+
+        let mut found = Vec::new();
+        let summary = index
+            .search("data/", &filter, &not_cancelled, |items| {
+                found.extend(items.into_iter().map(|item| item.key))
+            })
+            .unwrap();
+        assert_eq!(found, vec!["data/a.csv"]);
+        assert_eq!(summary.scanned, 2);
+        assert!(summary.indexed_at.is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_inventory_import_replaces_prefix() {
+        let (index, dir) = temp_index();
+        let crawl = Utc::now();
+        index
+            .insert_objects(crawl, &[object("data/new.csv", 1), object("logs/a.log", 1)])
+            .unwrap();
+        index.finish_crawl("", crawl).unwrap();
+
+        // The report is older than the crawl but still replaces what it covers
+        let inventory_date = crawl - Duration::days(1);
+        index.begin_import().unwrap();
+        index.stage_objects(&[object("data/old.csv", 2)]).unwrap();
+        // Staged objects stay out of the index until the import finishes
+        assert_eq!(
+            keys(&index.list_level("data/").unwrap()),
+            vec!["data/new.csv"]
+        );
+        index
+            .finish_import("data/", inventory_date, "s3://inventories/manifest.json")
+            .unwrap();
+
+        let data = index.list_level("data/").unwrap();
+        assert_eq!(keys(&data), vec!["data/old.csv"]);
+        let inventory_date = DateTime::from_timestamp_millis(inventory_date.timestamp_millis());
+        assert_eq!(data.inventory_date, inventory_date);
+        assert_eq!(index.inventory_date("data/").unwrap(), inventory_date);
+        assert_eq!(index.list_level("").unwrap().inventory_date, None);
+        assert_eq!(index.status("b1").unwrap().inventories.len(), 1);
+
+        // Crawling over the report takes its place
+        index.finish_crawl("data/", crawl).unwrap();
+        assert_eq!(index.inventory_date("data/").unwrap(), None);
+        assert!(index.status("b1").unwrap().inventories.is_empty());
+
+        // An import that never finishes leaves nothing behind
+        index.begin_import().unwrap();
+        index
+            .stage_objects(&[object("data/partial.csv", 3)])
+            .unwrap();
+        drop(index);
+        let index = MetadataIndex::at(dir.join("index.sqlite")).unwrap();
+        index.begin_import().unwrap();
+        index
+            .finish_import("data/", crawl, "s3://inventories/manifest.json")
+            .unwrap();
+        assert!(index.list_level("data/").unwrap().items.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end(""), None);
+        assert_eq!(prefix_end("data/").as_deref(), Some("data0"));
+        assert_eq!(prefix_end("a\u{10FFFF}").as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,466 @@
+use crate::models::{ObjectSummary, Result, S3DeckError};
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type, TimeUnit, TimestampMillisecondType};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Objects handed over at a time, like a live listing page
+const PAGE_SIZE: usize = 1000;
+
+/// The `manifest.json` S3 Inventory writes for each report
+#[derive(Debug, Deserialize)]
+pub struct InventoryManifest {
+    #[serde(rename = "sourceBucket")]
+    pub source_bucket: String,
+    /// Milliseconds since the epoch, as a string
+    #[serde(rename = "creationTimestamp")]
+    creation_timestamp: String,
+    #[serde(rename = "fileFormat")]
+    file_format: String,
+    /// Comma-separated column names of CSV reports. ORC and Parquet files carry their own schema.
+    #[serde(default, rename = "fileSchema")]
+    file_schema: String,
+    pub files: Vec<InventoryFile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InventoryFile {
+    /// Key in the destination bucket
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InventoryFormat {
+    Csv,
+    Orc,
+    Parquet,
+}
+
+impl InventoryManifest {
+    pub fn parse(json: &[u8]) -> Result<Self> {
+        serde_json::from_slice(json)
+            .map_err(|e| S3DeckError::InvalidInput(format!("Not an S3 Inventory manifest: {}", e)))
+    }
+
+    /// When S3 took the inventory snapshot
+    pub fn created_at(&self) -> Result<DateTime<Utc>> {
+        self.creation_timestamp
+            .parse()
+            .ok()
+            .and_then(DateTime::from_timestamp_millis)
+            .ok_or_else(|| {
+                S3DeckError::InvalidInput(format!(
+                    "Invalid inventory creation timestamp '{}'",
+                    self.creation_timestamp
+                ))
+            })
+    }
+
+    fn format(&self) -> Result<InventoryFormat> {
+        match self.file_format.to_ascii_lowercase().as_str() {
+            "csv" => Ok(InventoryFormat::Csv),
+            "orc" => Ok(InventoryFormat::Orc),
+            "parquet" => Ok(InventoryFormat::Parquet),
+            _ => Err(S3DeckError::InvalidInput(format!(
+                "Unsupported inventory format '{}'",
+                self.file_format
+            ))),
+        }
+    }
+
+    /// Read one data file of the report, handing its objects to `on_page` a page at a time.
+    /// Noncurrent versions and delete markers of versioned reports are left out.
+    pub fn read_file(
+        &self,
+        path: &Path,
+        mut on_page: impl FnMut(Vec<ObjectSummary>) -> Result<()>,
+    ) -> Result<()> {
+        let file = File::open(path).map_err(|e| read_error(path, e))?;
+
+        match self.format()? {
+            InventoryFormat::Csv => {
+                let columns = Columns::find(self.file_schema.split(','))?;
+                let mut reader: Box<dyn Read> = if is_gzip(path)? {
+                    Box::new(MultiGzDecoder::new(BufReader::new(file)))
+                } else {
+                    Box::new(BufReader::new(file))
+                };
+                read_csv(path, &mut reader, &columns, &mut on_page)
+            }
+            InventoryFormat::Orc => {
+                let batches = orc_rust::ArrowReaderBuilder::try_new(file)
+                    .map_err(|e| read_error(path, e))?
+                    .with_batch_size(PAGE_SIZE)
+                    .build();
+                read_batches(path, batches, &mut on_page)
+            }
+            InventoryFormat::Parquet => {
+                let batches =
+                    parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+                        .and_then(|builder| builder.with_batch_size(PAGE_SIZE).build())
+                        .map_err(|e| read_error(path, e))?;
+                read_batches(path, batches, &mut on_page)
+            }
+        }
+    }
+}
+
+/// Where a data file of a report downloaded to a local folder is: next to the manifest, or in
+/// the `data/` folder S3 writes beside the dated manifest folders
+pub fn local_data_file(manifest_path: &Path, file: &InventoryFile) -> Result<PathBuf> {
+    let name = file.key.rsplit('/').next().unwrap_or(&file.key);
+    let dir = manifest_path.parent().unwrap_or(Path::new("."));
+    let mut candidates = vec![dir.join(name), dir.join("data").join(name)];
+    if let Some(parent) = dir.parent() {
+        candidates.push(parent.join("data").join(name));
+    }
+
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            S3DeckError::Io(format!(
+                "Inventory data file '{}' not found next to '{}'",
+                name,
+                manifest_path.display()
+            ))
+        })
+}
+
+fn read_error(path: &Path, error: impl Display) -> S3DeckError {
+    S3DeckError::Io(format!(
+        "Failed to read inventory file '{}': {}",
+        path.display(),
+        error
+    ))
+}
+
+fn is_gzip(path: &Path) -> Result<bool> {
+    let mut magic = [0u8; 2];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(read == 2 && magic == [0x1f, 0x8b])
+}
+
+/// Positions of the report columns S3Deck uses. Names are compared without case or
+/// underscores, so the CSV schema's `LastModifiedDate` and Parquet's `last_modified_date` match.
+struct Columns {
+    key: usize,
+    size: Option<usize>,
+    last_modified: Option<usize>,
+    etag: Option<usize>,
+    storage_class: Option<usize>,
+    is_latest: Option<usize>,
+    is_delete_marker: Option<usize>,
+}
+
+impl Columns {
+    fn find<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let names: Vec<String> = names
+            .into_iter()
+            .map(|name| name.trim().replace('_', "").to_ascii_lowercase())
+            .collect();
+        let position = |column: &str| names.iter().position(|name| name == column);
+
+        Ok(Self {
+            key: position("key").ok_or_else(|| {
+                S3DeckError::InvalidInput("Inventory report has no Key column".to_string())
+            })?,
+            size: position("size"),
+            last_modified: position("lastmodifieddate"),
+            etag: position("etag"),
+            storage_class: position("storageclass"),
+            is_latest: position("islatest"),
+            is_delete_marker: position("isdeletemarker"),
+        })
+    }
+}
+
+/// Listings quote ETags and reports don't, so quote them to match
+fn quoted(etag: &str) -> String {
+    format!("\"{}\"", etag.trim_matches('"'))
+}
+
+fn read_csv(
+    path: &Path,
+    reader: &mut dyn Read,
+    columns: &Columns,
+    on_page: &mut impl FnMut(Vec<ObjectSummary>) -> Result<()>,
+) -> Result<()> {
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut page = Vec::new();
+
+    for record in csv.records() {
+        let record = record.map_err(|e| read_error(path, e))?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|i| record.get(i))
+                .filter(|value| !value.is_empty())
+        };
+        if field(columns.is_latest).is_some_and(|v| v.eq_ignore_ascii_case("false"))
+            || field(columns.is_delete_marker).is_some_and(|v| v.eq_ignore_ascii_case("true"))
+        {
+            continue;
+        }
+
+        // CSV reports URL-encode keys
+        let key = field(Some(columns.key)).unwrap_or_default();
+        let key = urlencoding::decode(key).map_err(|e| read_error(path, e))?;
+        page.push(ObjectSummary {
+            key: key.into_owned(),
+            size: field(columns.size)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            last_modified: field(columns.last_modified)
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|m| m.with_timezone(&Utc)),
+            etag: field(columns.etag).map(quoted),
+            storage_class: field(columns.storage_class).map(str::to_string),
+        });
+
+        if page.len() >= PAGE_SIZE {
+            on_page(std::mem::take(&mut page))?;
+        }
+    }
+    if !page.is_empty() {
+        on_page(page)?;
+    }
+    Ok(())
+}
+
+fn read_batches(
+    path: &Path,
+    batches: impl Iterator<Item = std::result::Result<RecordBatch, ArrowError>>,
+    on_page: &mut impl FnMut(Vec<ObjectSummary>) -> Result<()>,
+) -> Result<()> {
+    for batch in batches {
+        let page = batch
+            .and_then(|batch| batch_objects(&batch))
+            .map_err(|e| read_error(path, e))?;
+        if !page.is_empty() {
+            on_page(page)?;
+        }
+    }
+    Ok(())
+}
+
+fn batch_objects(batch: &RecordBatch) -> std::result::Result<Vec<ObjectSummary>, ArrowError> {
+    let schema = batch.schema();
+    let columns = Columns::find(schema.fields().iter().map(|field| field.name().as_str()))
+        .map_err(|e| ArrowError::SchemaError(e.to_string()))?;
+    // Cast every column to one type, whatever encoding the writer chose
+    let column = |i: Option<usize>, data_type: &DataType| {
+        i.map(|i| cast(batch.column(i), data_type)).transpose()
+    };
+    let timestamp = DataType::Timestamp(TimeUnit::Millisecond, None);
+
+    let keys = cast(batch.column(columns.key), &DataType::Utf8)?;
+    let keys = keys.as_string::<i32>();
+    let sizes = column(columns.size, &DataType::Int64)?;
+    let modified = column(columns.last_modified, &timestamp)?;
+    let etags = column(columns.etag, &DataType::Utf8)?;
+    let classes = column(columns.storage_class, &DataType::Utf8)?;
+    let latest = column(columns.is_latest, &DataType::Boolean)?;
+    let delete_markers = column(columns.is_delete_marker, &DataType::Boolean)?;
+
+    let text = |array: &Option<ArrayRef>, row| {
+        present(array, row).map(|a| a.as_string::<i32>().value(row).to_string())
+    };
+    let flag =
+        |array: &Option<ArrayRef>, row| present(array, row).map(|a| a.as_boolean().value(row));
+
+    let mut objects = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        if keys.is_null(row)
+            || flag(&latest, row) == Some(false)
+            || flag(&delete_markers, row) == Some(true)
+        {
+            continue;
+        }
+        objects.push(ObjectSummary {
+            key: keys.value(row).to_string(),
+            size: present(&sizes, row)
+                .map(|a| a.as_primitive::<Int64Type>().value(row))
+                .unwrap_or(0),
+            last_modified: present(&modified, row).and_then(|a| {
+                DateTime::from_timestamp_millis(
+                    a.as_primitive::<TimestampMillisecondType>().value(row),
+                )
+            }),
+            etag: text(&etags, row).map(|etag| quoted(&etag)),
+            storage_class: text(&classes, row),
+        });
+    }
+    Ok(objects)
+}
+
+/// A column's array when it exists and holds a value at `row`
+fn present(array: &Option<ArrayRef>, row: usize) -> Option<&ArrayRef> {
+    array.as_ref().filter(|a| a.is_valid(row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{BooleanArray, Int64Array, StringArray, TimestampMillisecondArray};
+    use arrow::datatypes::{Field, Schema};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    fn manifest(format: &str, schema: &str) -> InventoryManifest {
+        InventoryManifest::parse(
+            serde_json::json!({
+                "sourceBucket": "datasets",
+                "destinationBucket": "arn:aws:s3:::inventories",
+                "version": "2016-11-30",
+                "creationTimestamp": "1710000000000",
+                "fileFormat": format,
+                "fileSchema": schema,
+                "files": [{
+                    "key": "datasets/daily/data/4f2a.csv.gz",
+                    "size": 120,
+                    "MD5checksum": "00"
+                }]
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn read_all(manifest: &InventoryManifest, path: &Path) -> Vec<ObjectSummary> {
+        let mut objects = Vec::new();
+        manifest
+            .read_file(path, |page| {
+                objects.extend(page);
+                Ok(())
+            })
+            .unwrap();
+        objects
+    }
+
+    #[test]
+    fn test_read_gzipped_csv() {
+        let dir = std::env::temp_dir().join(format!("s3deck-inventory-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        let manifest = manifest(
+            "CSV",
+            "Bucket, Key, VersionId, IsLatest, IsDeleteMarker, Size, LastModifiedDate, ETag, StorageClass",
+        );
+        assert_eq!(
+            manifest.created_at().unwrap(),
+            DateTime::from_timestamp(1_710_000_000, 0).unwrap()
+        );
+
+        let path = local_data_file(&dir.join("manifest.json"), &manifest.files[0]);
+        assert!(matches!(path, Err(S3DeckError::Io(_))));
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(
+            b"\"datasets\",\"train/a%20b.csv\",\"v2\",\"true\",\"false\",\"10\",\"2024-03-01T12:00:00.000Z\",\"abc\",\"STANDARD\"\n\
+              \"datasets\",\"train/a%20b.csv\",\"v1\",\"false\",\"false\",\"8\",\"2024-02-01T12:00:00.000Z\",\"old\",\"STANDARD\"\n\
+              \"datasets\",\"train/gone.csv\",\"v3\",\"true\",\"true\",\"\",\"2024-03-02T12:00:00.000Z\",\"\",\"\"\n\
+              \"datasets\",\"archive/x.bin\",\"v4\",\"true\",\"false\",\"99\",\"2023-01-01T00:00:00.000Z\",\"ff-3\",\"GLACIER\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("data/4f2a.csv.gz"), gz.finish().unwrap()).unwrap();
+        let path = local_data_file(&dir.join("manifest.json"), &manifest.files[0]).unwrap();
+
+        let objects = read_all(&manifest, &path);
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].key, "train/a b.csv");
+        assert_eq!(objects[0].size, 10);
+        assert_eq!(objects[0].etag.as_deref(), Some("\"abc\""));
+        assert_eq!(
+            objects[0].last_modified,
+            DateTime::from_timestamp(1_709_294_400, 0)
+        );
+        assert_eq!(objects[1].storage_class.as_deref(), Some("GLACIER"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_parquet_and_orc() {
+        let dir = std::env::temp_dir().join(format!("s3deck-inventory-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("bucket", DataType::Utf8, false),
+            Field::new("key", DataType::Utf8, false),
+            Field::new("size", DataType::Int64, true),
+            Field::new(
+                "last_modified_date",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new("e_tag", DataType::Utf8, true),
+            Field::new("storage_class", DataType::Utf8, true),
+            Field::new("is_delete_marker", DataType::Boolean, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["datasets"; 3])),
+                Arc::new(StringArray::from(vec!["a.parquet", "b/c d.orc", "gone"])),
+                Arc::new(Int64Array::from(vec![Some(5), Some(7), None])),
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    Some(1_700_000_000_000),
+                    None,
+                    Some(1_700_000_000_000),
+                ])),
+                Arc::new(StringArray::from(vec![Some("e1"), Some("e2"), None])),
+                Arc::new(StringArray::from(vec![Some("STANDARD"), None, None])),
+                Arc::new(BooleanArray::from(vec![false, false, true])),
+            ],
+        )
+        .unwrap();
+
+        let parquet_path = dir.join("report.parquet");
+        let mut writer = parquet::arrow::ArrowWriter::try_new(
+            File::create(&parquet_path).unwrap(),
+            schema.clone(),
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let orc_path = dir.join("report.orc");
+        let mut writer =
+            orc_rust::ArrowWriterBuilder::new(File::create(&orc_path).unwrap(), schema.clone())
+                .try_build()
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        for (format, path) in [("Parquet", parquet_path), ("ORC", orc_path)] {
+            let objects = read_all(&manifest(format, ""), &path);
+            let keys: Vec<&str> = objects.iter().map(|o| o.key.as_str()).collect();
+            assert_eq!(keys, ["a.parquet", "b/c d.orc"], "{}", format);
+            assert_eq!(objects[0].size, 5);
+            assert_eq!(
+                objects[0].last_modified,
+                DateTime::from_timestamp(1_700_000_000, 0)
+            );
+            assert_eq!(objects[0].etag.as_deref(), Some("\"e1\""));
+            assert_eq!(objects[1].last_modified, None);
+            assert_eq!(objects[1].storage_class, None);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use crate::models::{JobInfo, JobKind, JobStatus, Result, S3DeckError};
+use chrono::Utc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Finished jobs are kept for their status until there are more than this many
+const MAX_FINISHED: usize = 50;
+
+/// Batch jobs, listing exports, duplicate scans, archive restores and restore watches, kept in
+/// Tauri state so their status can be read and `cancel_job` can stop them
+#[derive(Default)]
+pub struct JobManager {
+    jobs: Mutex<Vec<(JobInfo, Arc<AtomicBool>)>>,
+}
+
+impl JobManager {
+    /// Register a job, returning the flag that is set when it is cancelled
+    pub fn start(
+        &self,
+        job_id: &str,
+        kind: JobKind,
+        bucket_id: Option<&str>,
+    ) -> Result<Arc<AtomicBool>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs
+            .iter()
+            .any(|(job, _)| job.id == job_id && job.finished_at.is_none())
+        {
+            return Err(S3DeckError::InvalidInput(format!(
+                "A job with id '{}' is already running",
+                job_id
+            )));
+        }
+        jobs.retain(|(job, _)| job.id != job_id);
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        jobs.push((
+            JobInfo {
+                id: job_id.to_string(),
+                kind,
+                bucket_id: bucket_id.map(str::to_string),
+                status: JobStatus::Running,
+                started_at: Utc::now(),
+                finished_at: None,
+                error: None,
+            },
+            cancelled.clone(),
+        ));
+        Ok(cancelled)
+    }
+
+    /// Record how a job ended. A job that returns after being cancelled counts as cancelled.
+    pub fn finish<T>(&self, job_id: &str, result: &Result<T>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some((job, cancelled)) = jobs.iter_mut().find(|(job, _)| job.id == job_id) {
+            job.finished_at = Some(Utc::now());
+            (job.status, job.error) = match result {
+                Err(e) => (JobStatus::Failed, Some(e.to_string())),
+                Ok(_) if cancelled.load(Ordering::Relaxed) => (JobStatus::Cancelled, None),
+                Ok(_) => (JobStatus::Completed, None),
+            };
+        }
+
+        // Jobs are in start order, so the oldest finished ones go first
+        let finished = jobs.iter().filter(|(job, _)| job.finished_at.is_some());
+        let mut excess = finished.count().saturating_sub(MAX_FINISHED);
+        jobs.retain(|(job, _)| {
+            if excess > 0 && job.finished_at.is_some() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Returns false when no job with this id is running
+    pub fn cancel(&self, job_id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs
+            .iter_mut()
+            .find(|(job, _)| job.id == job_id && job.finished_at.is_none())
+        {
+            Some((job, cancelled)) => {
+                cancelled.store(true, Ordering::Relaxed);
+                job.status = JobStatus::Cancelling;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .find(|(job, _)| job.id == job_id)
+            .map(|(job, _)| job.clone())
+    }
+
+    /// Most recently started first
+    pub fn list(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().rev().map(|(job, _)| job.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let jobs = JobManager::default();
+        let cancelled = jobs
+            .start("export-1", JobKind::ListingExport, Some("b1"))
+            .unwrap();
+        assert!(jobs
+            .start("export-1", JobKind::ListingExport, None)
+            .is_err());
+        assert_eq!(jobs.get("export-1").unwrap().status, JobStatus::Running);
+
+        assert!(jobs.cancel("export-1"));
+        assert!(cancelled.load(Ordering::Relaxed));
+        assert_eq!(jobs.get("export-1").unwrap().status, JobStatus::Cancelling);
+        jobs.finish("export-1", &Ok(()));
+        let job = jobs.get("export-1").unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert!(job.finished_at.is_some());
+        assert!(!jobs.cancel("export-1"));
+
+        jobs.start("scan-1", JobKind::DuplicateScan, None).unwrap();
+        jobs.finish::<()>("scan-1", &Err(S3DeckError::S3("boom".to_string())));
+        let job = jobs.get("scan-1").unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.is_some());
+
+        // An id can be reused once its job has finished
+        jobs.start("export-1", JobKind::ListingExport, None)
+            .unwrap();
+        assert_eq!(jobs.list().len(), 2);
+        assert_eq!(jobs.list()[0].id, "export-1");
+    }
+
+    #[test]
+    fn test_finished_jobs_are_capped() {
+        let jobs = JobManager::default();
+        jobs.start("running", JobKind::RestoreWatch, None).unwrap();
+        for n in 0..MAX_FINISHED + 5 {
+            let id = format!("job-{}", n);
+            jobs.start(&id, JobKind::BatchJob, None).unwrap();
+            jobs.finish(&id, &Ok(()));
+        }
+
+        let list = jobs.list();
+        assert_eq!(list.len(), MAX_FINISHED + 1);
+        assert!(jobs.get("running").is_some());
+        assert!(jobs.get("job-4").is_none());
+        assert!(jobs.get("job-5").is_some());
+    }
+}
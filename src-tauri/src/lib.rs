@@ -1,16 +1,48 @@
+mod audit;
 mod aws_profiles;
+mod batch;
+mod client_cache;
 mod commands;
 mod config;
 mod content_type;
+mod cost;
+mod duplicates;
+mod export;
+mod csv_format;
+mod history;
+mod http_client;
+mod index;
+mod inventory;
+mod jobs;
 mod models;
+mod providers;
+mod restore;
 mod s3_client;
+mod safeguards;
+mod search;
+mod stats;
+mod usage;
+#[cfg(test)]
+mod test_support;
 
+use client_cache::ClientCache;
 use commands::*;
+use history::OperationHistory;
+use jobs::JobManager;
+use restore::RestoreTracker;
+use search::SearchJobs;
+use stats::FolderStatsCache;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(ClientCache::default())
+        .manage(OperationHistory::default())
+        .manage(SearchJobs::default())
+        .manage(JobManager::default())
+        .manage(FolderStatsCache::default())
+        .manage(RestoreTracker::load())
         .invoke_handler(tauri::generate_handler![
             // Config management
             get_buckets,
@@ -18,19 +50,73 @@ pub fn run() {
             update_bucket,
             delete_bucket_config,
             get_bucket,
+            detect_bucket_region,
+            get_region_mismatches,
+            fix_bucket_region,
+            get_provider_presets,
+            get_network_settings,
+            update_network_settings,
             // S3 operations
             list_objects,
             delete_object,
             get_object_metadata,
+            update_object_metadata,
             get_folder_latest_modified,
+            get_folder_stats,
             create_folder,
+            copy_object,
             upload_files,
             count_files,
             rename_object,
+            // Usage reports
+            analyze_usage,
+            export_usage_report,
+            // Cost estimates
+            get_pricing_table,
+            update_pricing_table,
+            reset_pricing_table,
+            estimate_cost,
+            // Search
+            search_objects,
+            cancel_search,
+            // Jobs
+            list_jobs,
+            get_job_status,
+            cancel_job,
+            // Duplicates
+            find_duplicates,
+            resolve_duplicates,
+            // Listing export
+            export_listing,
+            // Batch jobs
+            run_batch_job,
+            // Archive restores
+            restore_archived_objects,
+            get_restore_status,
+            list_pending_restores,
+            check_pending_restores,
+            watch_pending_restores,
+            // Local index
+            build_index,
+            import_inventory,
+            get_index_status,
+            delete_index,
+            list_indexed_objects,
+            // Undo
+            get_operation_history,
+            undo_last_operation,
+            // Trash
+            list_trash,
+            restore_from_trash,
+            purge_trash,
+            // Audit log
+            query_audit_log,
+            export_audit_log,
             // AWS Profile operations
             get_aws_profiles,
             get_buckets_for_profile,
             validate_aws_profile,
+            get_profile_details,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,7 +1,13 @@
+use aws_credential_types::provider::error::CredentialsError;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::RequestId;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BucketConfig {
     pub id: String,
     pub name: String,
@@ -13,21 +19,111 @@ pub struct BucketConfig {
     #[serde(rename = "secretKey")]
     pub secret_key: String,
     pub endpoint: Option<String>,
+    /// Storage service behind the bucket; when unset, AWS or Custom depending on `endpoint`
+    pub provider: Option<StorageProvider>,
+    /// Overrides the provider's addressing style
+    #[serde(rename = "forcePathStyle")]
+    pub force_path_style: Option<bool>,
+    /// Overrides whether the provider honors `If-None-Match`/`If-Match` on writes
+    #[serde(rename = "conditionalWrites")]
+    pub conditional_writes: Option<bool>,
     #[serde(rename = "awsProfile")]
     pub aws_profile: Option<String>,
+    /// Key prefix treated as the bucket root, for users who may only list below it
+    #[serde(rename = "rootPrefix")]
+    pub root_prefix: Option<String>,
+    /// Send unsigned requests, for public buckets; the bucket is read-only in this mode
+    #[serde(default)]
+    pub anonymous: bool,
+    /// Move deleted objects to a hidden trash folder, or keep them as noncurrent versions
+    /// when the bucket is versioned, so they can be restored
+    #[serde(rename = "trashEnabled", default)]
+    pub trash_enabled: bool,
+    /// Refuse every operation that changes the bucket
+    #[serde(rename = "readOnly", default)]
+    pub read_only: bool,
+    /// Prefixes or globs such as `prod/**` that need a typed confirmation before destructive
+    /// operations
+    #[serde(rename = "protectedPrefixes", default)]
+    pub protected_prefixes: Vec<String>,
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: Option<u32>,
+    #[serde(rename = "retryMode")]
+    pub retry_mode: Option<RetryMode>,
+    #[serde(rename = "connectTimeoutMs")]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(rename = "readTimeoutMs")]
+    pub read_timeout_ms: Option<u64>,
+    #[serde(rename = "operationTimeoutMs")]
+    pub operation_timeout_ms: Option<u64>,
+    /// Overrides the global proxy settings for this bucket
+    pub proxy: Option<ProxySettings>,
+    /// PEM files trusted in addition to the global CA bundles
+    #[serde(rename = "caBundles", default)]
+    pub ca_bundles: Vec<String>,
+    /// Skip TLS certificate verification; only honored for buckets with a custom endpoint
+    #[serde(rename = "allowInvalidCertificates", default)]
+    pub allow_invalid_certificates: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub buckets: Vec<BucketConfig>,
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StorageProvider {
+    Aws,
+    Minio,
+    CloudflareR2,
+    BackblazeB2,
+    Wasabi,
+    DigitalOceanSpaces,
+    Ceph,
+    Custom,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            buckets: Vec::new(),
-        }
-    }
+/// What to do when a write's destination key already exists. Requests that don't pick one
+/// overwrite, as writes always did before the policy existed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    Fail,
+    Skip,
+    #[default]
+    Overwrite,
+    /// Write under a numbered key instead, e.g. `report (1).pdf`
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RetryMode {
+    Standard,
+    Adaptive,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProxySettings {
+    /// Proxy used for both HTTP and HTTPS; when empty, HTTPS_PROXY/HTTP_PROXY/NO_PROXY apply
+    pub url: Option<String>,
+    /// Comma separated hosts, domains or CIDRs that bypass the proxy
+    #[serde(rename = "noProxy")]
+    pub no_proxy: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Connect directly, ignoring both the configured and the environment proxy
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    /// PEM files with additional CA certificates, trusted alongside the system roots
+    #[serde(rename = "caBundles", default)]
+    pub ca_bundles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    pub buckets: Vec<BucketConfig>,
+    #[serde(default)]
+    pub network: NetworkSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +135,36 @@ pub struct FileItem {
     pub is_folder: bool,
     #[serde(rename = "lastModified")]
     pub last_modified: Option<DateTime<Utc>>,
+    /// As listed, e.g. `GLACIER` for objects that must be restored before download. `None` for
+    /// folders and providers that don't report it.
+    #[serde(rename = "storageClass")]
+    pub storage_class: Option<String>,
+}
+
+/// An object found by a recursive listing, with the fields a single-level listing leaves out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectSummary {
+    pub key: String,
+    pub size: i64,
+    #[serde(rename = "lastModified")]
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+    /// `None` for `STANDARD`, which listings leave out
+    #[serde(rename = "storageClass")]
+    pub storage_class: Option<String>,
+}
+
+impl ObjectSummary {
+    pub fn file_item(&self) -> FileItem {
+        FileItem {
+            key: self.key.clone(),
+            name: self.key.rsplit('/').next().unwrap_or_default().to_string(),
+            size: self.size,
+            is_folder: false,
+            last_modified: self.last_modified,
+            storage_class: self.storage_class.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +175,10 @@ pub struct UploadRequest {
     #[serde(rename = "currentPath")]
     pub current_path: String,
     pub files: Vec<String>,
+    #[serde(default)]
+    pub overwrite: OverwritePolicy,
+    /// Confirmation token for overwriting keys under a protected prefix
+    pub confirmation: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +205,33 @@ pub struct DeleteResponse {
     pub message: String,
     pub key: String,
     pub count: Option<i32>,
+    /// Whether the deleted objects can be restored from the trash
+    #[serde(default)]
+    pub trashed: bool,
+}
+
+/// A deleted object that can still be restored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    /// Key the object had before it was deleted
+    pub key: String,
+    /// Where the object sits in the trash folder, for buckets without versioning
+    #[serde(rename = "trashKey")]
+    pub trash_key: Option<String>,
+    /// Delete marker hiding the object, for versioned buckets
+    #[serde(rename = "versionId")]
+    pub version_id: Option<String>,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResponse {
+    pub message: String,
+    pub restored: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +257,10 @@ pub struct RenameRequest {
     pub old_key: String,
     pub new_key: String,
     pub is_folder: bool,
+    #[serde(default)]
+    pub overwrite: OverwritePolicy,
+    /// Confirmation token for moving keys out of or over a protected prefix
+    pub confirmation: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +270,706 @@ pub struct RenameResponse {
     pub new_key: String,
     pub moved_files: Option<Vec<String>>,
     pub total_moved: Option<i32>,
+    pub skipped_files: Option<Vec<String>>,
+    /// Every object actually moved, with the key it was written under
+    #[serde(default)]
+    pub moved_objects: Vec<MovedObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovedObject {
+    pub from: String,
+    pub to: String,
+}
+
+/// Editable headers of an object, replaced as a whole by a metadata edit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObjectHeaders {
+    #[serde(rename = "contentType")]
+    pub content_type: Option<String>,
+    #[serde(rename = "cacheControl")]
+    pub cache_control: Option<String>,
+    #[serde(rename = "contentDisposition")]
+    pub content_disposition: Option<String>,
+    #[serde(rename = "contentEncoding")]
+    pub content_encoding: Option<String>,
+    #[serde(rename = "contentLanguage")]
+    pub content_language: Option<String>,
+    /// User metadata, sent as `x-amz-meta-*` headers
+    #[serde(default)]
+    pub metadata: std::collections::HashMap<String, String>,
+}
+
+/// A completed operation the undo command can reverse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "bucketId")]
+    pub bucket_id: String,
+    pub description: String,
+    pub operation: ReversibleOperation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReversibleOperation {
+    /// Renames and moves, undone by moving each object back
+    Rename { moves: Vec<MovedObject> },
+    /// Deletes that went to the trash, undone by restoring them
+    Delete { items: Vec<TrashItem> },
+    /// Metadata edits, undone by writing back the previous headers
+    MetadataEdit {
+        key: String,
+        previous: ObjectHeaders,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoResponse {
+    pub message: String,
+    /// The operation that was reversed
+    pub undone: HistoryEntry,
+    pub restored: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// How a search query is compared with keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SearchMatchMode {
+    #[default]
+    Substring,
+    /// `*` and `?` also match `/`, so `*.csv` finds CSV files at any depth
+    Glob,
+    Regex,
+}
+
+/// A recursive search below a prefix. Every filter left empty matches all objects.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchRequest {
+    /// Chosen by the caller so the search can be cancelled while it runs
+    #[serde(rename = "searchId")]
+    pub search_id: String,
+    /// Defaults to the bucket's root prefix
+    pub prefix: Option<String>,
+    /// Matched against the key relative to the searched prefix
+    pub query: Option<String>,
+    #[serde(default, rename = "matchMode")]
+    pub match_mode: SearchMatchMode,
+    #[serde(default, rename = "caseSensitive")]
+    pub case_sensitive: bool,
+    #[serde(rename = "minSize")]
+    pub min_size: Option<i64>,
+    #[serde(rename = "maxSize")]
+    pub max_size: Option<i64>,
+    #[serde(rename = "modifiedAfter")]
+    pub modified_after: Option<DateTime<Utc>>,
+    #[serde(rename = "modifiedBefore")]
+    pub modified_before: Option<DateTime<Utc>>,
+    /// e.g. `STANDARD` or `GLACIER`
+    #[serde(default, rename = "storageClasses")]
+    pub storage_classes: Vec<String>,
+    /// Answer from the local index instead of listing the bucket
+    #[serde(default, rename = "useIndex")]
+    pub use_index: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchSummary {
+    /// Objects listed, matching or not
+    pub scanned: u64,
+    pub matched: u64,
+    #[serde(default)]
+    pub cancelled: bool,
+    /// When the index a search was answered from was last refreshed
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: Option<DateTime<Utc>>,
+    /// Set when that index data is an imported S3 Inventory report, to the report's date
+    #[serde(rename = "inventoryDate")]
+    pub inventory_date: Option<DateTime<Utc>>,
+}
+
+/// A single-level listing answered from the local index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedListing {
+    pub items: Vec<FileItem>,
+    /// When this level was last listed from the bucket, `None` if it never was
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: Option<DateTime<Utc>>,
+    /// Date of the S3 Inventory report every item comes from, when the level was last loaded
+    /// from one rather than listed
+    #[serde(rename = "inventoryDate")]
+    pub inventory_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    #[serde(rename = "bucketId")]
+    pub bucket_id: String,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+    /// Prefixes crawled in full, with when each was crawled
+    #[serde(rename = "crawledPrefixes")]
+    pub crawled_prefixes: Vec<IndexedPrefix>,
+    #[serde(rename = "sizeOnDisk")]
+    pub size_on_disk: u64,
+    /// S3 Inventory reports loaded into the index and not crawled over since
+    #[serde(default)]
+    pub inventories: Vec<IndexedInventory>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedPrefix {
+    pub prefix: String,
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedInventory {
+    pub prefix: String,
+    /// When S3 took the inventory snapshot
+    #[serde(rename = "inventoryDate")]
+    pub inventory_date: DateTime<Utc>,
+    /// Where the manifest was read from, as a path or `s3://bucket/key`
+    pub manifest: String,
+    #[serde(rename = "importedAt")]
+    pub imported_at: DateTime<Utc>,
+}
+
+/// Load an S3 Inventory report into a bucket's index, in place of a crawl
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryImportRequest {
+    /// Path of the report's `manifest.json`, or its key in `inventoryBucketId`
+    pub manifest: String,
+    /// Bucket the report was delivered to, when it is read from S3 rather than a local folder
+    #[serde(rename = "inventoryBucketId")]
+    pub inventory_bucket_id: Option<String>,
+    /// Only keys below this prefix are loaded. Defaults to the bucket's root prefix.
+    pub prefix: Option<String>,
+}
+
+/// Running totals sent while an inventory report is imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryImportProgress {
+    #[serde(rename = "filesRead")]
+    pub files_read: usize,
+    #[serde(rename = "fileCount")]
+    pub file_count: usize,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+}
+
+/// Totals for everything below a folder prefix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderStats {
+    pub prefix: String,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    /// Largest share first
+    #[serde(rename = "storageClasses")]
+    pub storage_classes: Vec<StorageClassUsage>,
+    pub newest: Option<ObjectSummary>,
+    pub oldest: Option<ObjectSummary>,
+    /// Largest first
+    pub largest: Vec<ObjectSummary>,
+    #[serde(rename = "computedAt")]
+    pub computed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageClassUsage {
+    #[serde(rename = "storageClass")]
+    pub storage_class: String,
+    pub bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+}
+
+/// Running totals sent while a folder is walked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderStatsProgress {
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageReportRequest {
+    /// Defaults to the bucket's root prefix
+    pub prefix: Option<String>,
+    /// Levels of sub-prefixes to break usage down into, 2 when unset
+    pub depth: Option<usize>,
+    /// Analyze the local index instead of listing the bucket
+    #[serde(default, rename = "useIndex")]
+    pub use_index: bool,
+}
+
+/// Storage usage below a prefix, for treemaps and cleanup reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub prefix: String,
+    pub depth: usize,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    /// The prefix itself, with its sub-prefixes nested down to `depth`
+    pub tree: PrefixUsage,
+    /// Largest first
+    pub largest: Vec<ObjectSummary>,
+    /// Youngest bucket first
+    #[serde(rename = "ageHistogram")]
+    pub age_histogram: Vec<AgeBucketUsage>,
+    /// Largest share first; `""` for keys without an extension
+    pub extensions: Vec<ExtensionUsage>,
+    #[serde(rename = "storageClasses")]
+    pub storage_classes: Vec<StorageClassUsage>,
+    #[serde(rename = "generatedAt")]
+    pub generated_at: DateTime<Utc>,
+    /// When the index was crawled, for reports made from it
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: Option<DateTime<Utc>>,
+    /// Date of the S3 Inventory report the index was loaded from, if it was
+    #[serde(rename = "inventoryDate")]
+    pub inventory_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefixUsage {
+    pub prefix: String,
+    pub name: String,
+    pub bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    /// Largest first
+    #[serde(default)]
+    pub children: Vec<PrefixUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgeBucketUsage {
+    pub label: String,
+    #[serde(rename = "minDays")]
+    pub min_days: u32,
+    /// `None` for the open-ended oldest bucket
+    #[serde(rename = "maxDays")]
+    pub max_days: Option<u32>,
+    pub bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    /// The bucket's usage split by storage class, largest share first
+    #[serde(default, rename = "storageClasses")]
+    pub storage_classes: Vec<StorageClassUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtensionUsage {
+    pub extension: String,
+    pub bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+}
+
+/// Monthly storage prices, editable by the user, in `currency` per GB-month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricingTable {
+    pub currency: String,
+    /// Used for buckets in regions missing from `regions`
+    #[serde(rename = "defaultRegion")]
+    pub default_region: String,
+    /// Billing quirks per storage class
+    #[serde(default)]
+    pub classes: HashMap<String, StorageClassBilling>,
+    /// Price per storage class, per region
+    pub regions: HashMap<String, HashMap<String, f64>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageClassBilling {
+    /// Extra bytes billed per object, e.g. the index data S3 keeps for archived objects
+    #[serde(default, rename = "overheadBytes")]
+    pub overhead_bytes: i64,
+}
+
+/// Move objects at least this old to a cheaper storage class, for savings projections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierTransition {
+    #[serde(rename = "olderThanDays")]
+    pub older_than_days: u32,
+    #[serde(rename = "storageClass")]
+    pub storage_class: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub prefix: String,
+    /// Region whose prices were used
+    pub region: String,
+    pub currency: String,
+    #[serde(rename = "monthlyCost")]
+    pub monthly_cost: f64,
+    /// Most expensive first
+    #[serde(rename = "storageClasses")]
+    pub storage_classes: Vec<StorageClassCost>,
+    /// Cost after the requested transitions, when any were given
+    #[serde(rename = "projectedMonthlyCost")]
+    pub projected_monthly_cost: Option<f64>,
+    #[serde(rename = "projectedSavings")]
+    pub projected_savings: Option<f64>,
+    /// Bytes the transitions would move
+    #[serde(rename = "transitionedBytes")]
+    pub transitioned_bytes: i64,
+    /// Storage classes without a price, left out of the totals
+    #[serde(default, rename = "unpricedClasses")]
+    pub unpriced_classes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageClassCost {
+    #[serde(rename = "storageClass")]
+    pub storage_class: String,
+    pub bytes: i64,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    #[serde(rename = "monthlyCost")]
+    pub monthly_cost: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateScanRequest {
+    /// Chosen by the caller so the scan can be cancelled with `cancel_job`
+    #[serde(rename = "scanId")]
+    pub scan_id: String,
+    /// Defaults to the bucket's root prefix
+    pub prefix: Option<String>,
+    /// Download the candidates and compare SHA-256 checksums instead of trusting ETags
+    #[serde(default, rename = "verifyChecksums")]
+    pub verify_checksums: bool,
+    /// Scan the local index instead of listing the bucket
+    #[serde(default, rename = "useIndex")]
+    pub use_index: bool,
+}
+
+/// Objects with the same content, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub size: i64,
+    pub etag: String,
+    pub objects: Vec<ObjectSummary>,
+    /// Bytes freed by keeping a single copy
+    #[serde(rename = "wastedBytes")]
+    pub wasted_bytes: i64,
+    /// Hex SHA-256 of the content, when the group was confirmed with checksums
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateReport {
+    pub prefix: String,
+    /// Most wasted bytes first
+    pub groups: Vec<DuplicateGroup>,
+    /// Objects listed, duplicated or not
+    pub scanned: u64,
+    /// Copies beyond the first in each group
+    #[serde(rename = "duplicateCount")]
+    pub duplicate_count: u64,
+    #[serde(rename = "wastedBytes")]
+    pub wasted_bytes: i64,
+    #[serde(default)]
+    pub cancelled: bool,
+    /// When the index a scan was answered from was last refreshed
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: Option<DateTime<Utc>>,
+    /// Date of the S3 Inventory report the index was loaded from, if it was
+    #[serde(rename = "inventoryDate")]
+    pub inventory_date: Option<DateTime<Utc>>,
+}
+
+/// What happens to the copies when duplicates are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DuplicateAction {
+    /// Delete the copies, through the trash when the bucket has it enabled
+    Delete,
+    /// Delete the copies and leave an empty placeholder redirecting to the kept object
+    Replace,
+}
+
+/// One copy to keep and the copies to get rid of, all expected to still have `etag`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateResolution {
+    pub keep: String,
+    pub etag: String,
+    pub duplicates: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveDuplicatesRequest {
+    pub action: DuplicateAction,
+    pub groups: Vec<DuplicateResolution>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveDuplicatesResponse {
+    pub message: String,
+    pub resolved: Vec<String>,
+    /// Copies left alone because they or the kept object changed since the scan, or the
+    /// request failed
+    pub failed: Vec<String>,
+    #[serde(default)]
+    pub trashed: bool,
+}
+
+/// File format of a listing export
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// A column of a listing export
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportField {
+    Key,
+    Size,
+    LastModified,
+    ETag,
+    StorageClass,
+    /// User metadata, read with one HEAD request per object
+    Metadata,
+    /// Object tags, read with one request per object
+    Tags,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListingExportRequest {
+    /// Chosen by the caller so the export can be cancelled with `cancel_job`
+    #[serde(rename = "exportId")]
+    pub export_id: String,
+    /// Defaults to the bucket's root prefix
+    pub prefix: Option<String>,
+    /// Local file to write
+    pub destination: String,
+    pub format: ExportFormat,
+    /// Columns in order; key, size, last modified, ETag and storage class when empty
+    #[serde(default)]
+    pub fields: Vec<ExportField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListingExportSummary {
+    pub destination: String,
+    #[serde(rename = "objectCount")]
+    pub object_count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+    /// Set when the export was stopped early; nothing is left at the destination then
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+/// Retrieval speed of a restore from Glacier or Deep Archive; faster tiers cost more
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RestoreTier {
+    /// Minutes, for Glacier Flexible Retrieval only
+    Expedited,
+    #[default]
+    Standard,
+    Bulk,
+}
+
+/// What a batch job does to every key in its manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BatchOperation {
+    /// Delete, through the trash when the bucket has it enabled
+    Delete,
+    /// Copy to `<destinationPrefix><key>`, in this bucket or another one. Cross-bucket copies
+    /// run with the destination bucket's credentials.
+    Copy {
+        #[serde(rename = "destinationBucketId")]
+        destination_bucket_id: Option<String>,
+        #[serde(default, rename = "destinationPrefix")]
+        destination_prefix: String,
+        #[serde(default)]
+        overwrite: OverwritePolicy,
+    },
+    SetStorageClass {
+        #[serde(rename = "storageClass")]
+        storage_class: String,
+    },
+    /// Replace the tags
+    SetTags { tags: HashMap<String, String> },
+    /// Add or change user metadata, keeping the other headers and metadata
+    SetMetadata { metadata: HashMap<String, String> },
+    /// Restore a temporary copy of archived objects
+    Restore {
+        days: i32,
+        #[serde(default)]
+        tier: RestoreTier,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJobRequest {
+    /// Chosen by the caller so the job can be cancelled with `cancel_job`
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+    /// Local CSV or text file listing the keys; see `batch::read_manifest`
+    pub manifest: Option<String>,
+    /// Keys to process on top of the manifest's, e.g. from a search
+    #[serde(default)]
+    pub keys: Vec<String>,
+    pub operation: BatchOperation,
+    /// Local CSV file that gets a `key,status,detail` row per key
+    #[serde(rename = "reportPath")]
+    pub report_path: String,
+    /// Requests in flight at once, defaults to `batch::DEFAULT_CONCURRENCY`
+    pub concurrency: Option<usize>,
+}
+
+/// Running totals of a batch job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchJobProgress {
+    pub total: u64,
+    pub succeeded: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJobSummary {
+    pub message: String,
+    pub total: u64,
+    pub succeeded: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    #[serde(rename = "reportPath")]
+    pub report_path: String,
+    /// Set when the job was stopped early; unprocessed keys are missing from the report
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+/// What a job tracked by the job manager does
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JobKind {
+    BatchJob,
+    ListingExport,
+    DuplicateScan,
+    ArchiveRestore,
+    RestoreWatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Running,
+    /// Cancel requested; the job stops once its current step is done
+    Cancelling,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// A running job, or one that finished recently
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    #[serde(rename = "bucketId")]
+    pub bucket_id: Option<String>,
+    pub status: JobStatus,
+    #[serde(rename = "startedAt")]
+    pub started_at: DateTime<Utc>,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Why a failed job failed
+    pub error: Option<String>,
+}
+
+/// Restore of archived objects, either listed keys or every archived object below a prefix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveRestoreRequest {
+    /// Chosen by the caller so the restore can be cancelled with `cancel_job`
+    #[serde(rename = "restoreId")]
+    pub restore_id: String,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// Restore every `GLACIER` and `DEEP_ARCHIVE` object below this prefix as well
+    pub prefix: Option<String>,
+    /// How long the restored copy stays available
+    pub days: i32,
+    #[serde(default)]
+    pub tier: RestoreTier,
+    /// Requests in flight at once, defaults to `batch::DEFAULT_CONCURRENCY`
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveRestoreResponse {
+    pub message: String,
+    pub requested: Vec<String>,
+    /// Keys whose restore was already in progress; they are tracked all the same
+    #[serde(rename = "inProgress")]
+    pub in_progress: Vec<String>,
+    pub failed: Vec<String>,
+    /// Set when the restore was stopped early; keys not yet requested are in no list
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+/// Where an object stands with regard to archive restores
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RestoreState {
+    /// Readable without a restore
+    NotArchived,
+    /// Archived with no restore requested
+    Archived,
+    InProgress,
+    /// A temporary copy is readable until `expiresAt`
+    Restored,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreStatus {
+    pub key: String,
+    #[serde(rename = "storageClass")]
+    pub storage_class: Option<String>,
+    pub state: RestoreState,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A requested restore that is checked until its object becomes readable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingRestore {
+    #[serde(rename = "bucketId")]
+    pub bucket_id: String,
+    pub key: String,
+    pub tier: RestoreTier,
+    pub days: i32,
+    #[serde(rename = "requestedAt")]
+    pub requested_at: DateTime<Utc>,
+    /// Set once the restore completed
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyResponse {
+    pub message: String,
+    #[serde(rename = "sourceKey")]
+    pub source_key: String,
+    /// Key actually written, which differs from the requested one under `KeepBoth`
+    #[serde(rename = "destinationKey")]
+    pub destination_key: String,
+    #[serde(default)]
+    pub skipped: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,7 +979,7 @@ pub struct AwsProfile {
     pub status: ProfileStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ProfileStatus {
     Valid,
     Expired,
@@ -126,6 +987,34 @@ pub enum ProfileStatus {
     Unknown,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CredentialSource {
+    Static,
+    Sso,
+    AssumeRole,
+    Process,
+    WebIdentity,
+    Environment,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileDetails {
+    pub name: String,
+    pub region: Option<String>,
+    #[serde(rename = "credentialSource")]
+    pub credential_source: CredentialSource,
+    #[serde(rename = "credentialsExpireAt")]
+    pub credentials_expire_at: Option<DateTime<Utc>>,
+    #[serde(rename = "accountId")]
+    pub account_id: Option<String>,
+    pub arn: Option<String>,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub status: ProfileStatus,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfileBucket {
     pub name: String,
@@ -134,7 +1023,104 @@ pub struct ProfileBucket {
     pub creation_date: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, thiserror::Error, Serialize)]
+/// Details of a failed S3 request, kept so the frontend can react to the error code
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct S3ErrorInfo {
+    pub message: String,
+    pub code: Option<String>,
+    #[serde(rename = "httpStatus")]
+    pub http_status: Option<u16>,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+    pub retryable: bool,
+    /// Region S3 reported for the bucket via `x-amz-bucket-region`
+    #[serde(rename = "bucketRegion")]
+    pub bucket_region: Option<String>,
+}
+
+/// A bucket whose stored region differs from the one S3 reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionMismatch {
+    #[serde(rename = "bucketId")]
+    pub bucket_id: String,
+    #[serde(rename = "configuredRegion")]
+    pub configured_region: String,
+    #[serde(rename = "detectedRegion")]
+    pub detected_region: String,
+}
+
+/// One line of the audit log at `~/.s3deck/audit.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    #[serde(rename = "bucketId")]
+    pub bucket_id: Option<String>,
+    #[serde(rename = "bucketName")]
+    pub bucket_name: Option<String>,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    pub bytes: Option<i64>,
+    pub outcome: AuditOutcome,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AuditAction {
+    Upload,
+    Delete,
+    Rename,
+    Copy,
+    CreateFolder,
+    UpdateMetadata,
+    /// Restore from the trash
+    Restore,
+    /// Restore request for an object in an archive storage class
+    RestoreArchive,
+    PurgeTrash,
+    Undo,
+    AddBucket,
+    UpdateBucket,
+    DeleteBucket,
+    UpdateNetworkSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AuditOutcome {
+    Success,
+    Skipped,
+    Failed,
+}
+
+/// Filters for reading the audit log; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(rename = "bucketId")]
+    pub bucket_id: Option<String>,
+    pub action: Option<AuditAction>,
+    pub outcome: Option<AuditOutcome>,
+    /// Matches entries with any key containing this text
+    pub key: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Newest entries first, at most this many
+    pub limit: Option<usize>,
+}
+
+/// What the user has to type before a destructive operation on a protected key goes ahead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmationInfo {
+    pub message: String,
+    pub key: String,
+    /// Empty when the confirmation is for deleting an object the trash can't take
+    #[serde(rename = "protectedPrefix")]
+    pub protected_prefix: String,
+    #[serde(rename = "confirmationToken")]
+    pub confirmation_token: String,
+}
+
+#[derive(Debug, thiserror::Error)]
 pub enum S3DeckError {
     #[error("Configuration error: {0}")]
     Config(String),
@@ -154,8 +1140,308 @@ pub enum S3DeckError {
     #[error("Invalid path: {0}")]
     InvalidPath(String),
 
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Index error: {0}")]
+    Index(String),
+
     #[error("AWS Profile error: {0}")]
     AwsProfile(String),
+
+    #[error("{0}")]
+    AlreadyExists(String),
+
+    #[error("{0}")]
+    ReadOnly(String),
+
+    #[error("{}", .0.message)]
+    ConfirmationRequired(ConfirmationInfo),
+
+    #[error("{}", .0.message)]
+    AccessDenied(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    NoSuchKey(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    NoSuchBucket(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    ExpiredToken(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    SlowDown(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    PreconditionFailed(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    WrongRegion(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    Network(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    Credentials(S3ErrorInfo),
+
+    #[error("{}", .0.message)]
+    Service(S3ErrorInfo),
+}
+
+impl S3DeckError {
+    /// Classify a failed SDK call by its S3 error code and HTTP status instead of its message
+    pub fn from_sdk<E>(context: &str, err: SdkError<E, HttpResponse>) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+    {
+        Self::classify(context, err, S3DeckError::NoSuchKey)
+    }
+
+    /// `from_sdk` for requests about the bucket itself, such as HEAD bucket or a listing, where
+    /// a 404 without an error code means the bucket is missing rather than a key
+    pub fn from_bucket_sdk<E>(context: &str, err: SdkError<E, HttpResponse>) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+    {
+        Self::classify(context, err, S3DeckError::NoSuchBucket)
+    }
+
+    fn classify<E>(
+        context: &str,
+        err: SdkError<E, HttpResponse>,
+        not_found: fn(S3ErrorInfo) -> S3DeckError,
+    ) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+    {
+        let http_status = err
+            .raw_response()
+            .map(|response| response.status().as_u16());
+        let request_id = err.request_id().map(|s| s.to_string());
+        let code = err.code().map(|s| s.to_string());
+        let detail = err
+            .message()
+            .or(code.as_deref())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| DisplayErrorContext(&err).to_string());
+
+        // S3 names the bucket's region in a header, or in the message of a mis-signed request
+        let bucket_region = err
+            .raw_response()
+            .and_then(|response| response.headers().get("x-amz-bucket-region"))
+            .map(|s| s.to_string())
+            .or_else(|| {
+                err.message()
+                    .and_then(|m| m.split("expecting '").nth(1))
+                    .and_then(|rest| rest.split('\'').next())
+                    .map(|s| s.to_string())
+            });
+
+        let mut info = S3ErrorInfo {
+            message: format!("{}: {}", context, detail),
+            code,
+            http_status,
+            request_id,
+            retryable: false,
+            bucket_region,
+        };
+
+        match &err {
+            SdkError::TimeoutError(_) => {
+                info.retryable = true;
+                S3DeckError::Network(info)
+            }
+            SdkError::DispatchFailure(failure) => {
+                if is_credentials_error(&err) {
+                    S3DeckError::Credentials(info)
+                } else {
+                    info.retryable = failure.is_timeout() || failure.is_io();
+                    S3DeckError::Network(info)
+                }
+            }
+            _ => {
+                info.retryable = matches!(
+                    info.code.as_deref(),
+                    Some("SlowDown" | "RequestTimeout" | "InternalError" | "ServiceUnavailable")
+                ) || matches!(info.http_status, Some(429 | 500..=599));
+
+                match info.code.as_deref() {
+                    Some(
+                        "AccessDenied"
+                        | "AllAccessDisabled"
+                        | "InvalidAccessKeyId"
+                        | "SignatureDoesNotMatch",
+                    ) => S3DeckError::AccessDenied(info),
+                    Some("NoSuchKey") => S3DeckError::NoSuchKey(info),
+                    Some("NoSuchBucket") => S3DeckError::NoSuchBucket(info),
+                    Some("PermanentRedirect" | "IllegalLocationConstraintException") => {
+                        wrong_region(context, info)
+                    }
+                    Some("AuthorizationHeaderMalformed") if info.bucket_region.is_some() => {
+                        wrong_region(context, info)
+                    }
+                    Some(
+                        "ExpiredToken"
+                        | "ExpiredTokenException"
+                        | "TokenRefreshRequired"
+                        | "RequestExpired",
+                    ) => S3DeckError::ExpiredToken(info),
+                    Some(
+                        "SlowDown"
+                        | "Throttling"
+                        | "ThrottlingException"
+                        | "RequestLimitExceeded"
+                        | "TooManyRequestsException",
+                    ) => S3DeckError::SlowDown(info),
+                    // A concurrent conditional write to the same key lost the race
+                    Some("PreconditionFailed" | "ConditionalRequestConflict") => {
+                        S3DeckError::PreconditionFailed(info)
+                    }
+                    // HEAD responses have no body, so only the status code is available
+                    _ => match info.http_status {
+                        Some(301) => wrong_region(context, info),
+                        Some(403) => S3DeckError::AccessDenied(info),
+                        Some(404) => not_found(info),
+                        Some(412) => S3DeckError::PreconditionFailed(info),
+                        Some(429 | 503) => S3DeckError::SlowDown(info),
+                        _ => S3DeckError::Service(info),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Name of the variant, used as the `kind` tag in serialized errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            S3DeckError::Config(_) => "Config",
+            S3DeckError::S3(_) => "S3",
+            S3DeckError::Io(_) => "Io",
+            S3DeckError::Serialization(_) => "Serialization",
+            S3DeckError::BucketNotFound(_) => "BucketNotFound",
+            S3DeckError::InvalidPath(_) => "InvalidPath",
+            S3DeckError::InvalidInput(_) => "InvalidInput",
+            S3DeckError::Index(_) => "Index",
+            S3DeckError::AwsProfile(_) => "AwsProfile",
+            S3DeckError::AlreadyExists(_) => "AlreadyExists",
+            S3DeckError::ReadOnly(_) => "ReadOnly",
+            S3DeckError::ConfirmationRequired(_) => "ConfirmationRequired",
+            S3DeckError::AccessDenied(_) => "AccessDenied",
+            S3DeckError::NoSuchKey(_) => "NoSuchKey",
+            S3DeckError::NoSuchBucket(_) => "NoSuchBucket",
+            S3DeckError::ExpiredToken(_) => "ExpiredToken",
+            S3DeckError::SlowDown(_) => "SlowDown",
+            S3DeckError::PreconditionFailed(_) => "PreconditionFailed",
+            S3DeckError::WrongRegion(_) => "WrongRegion",
+            S3DeckError::Network(_) => "Network",
+            S3DeckError::Credentials(_) => "Credentials",
+            S3DeckError::Service(_) => "Service",
+        }
+    }
+
+    /// S3 request details for errors that came back from the SDK
+    pub fn info(&self) -> Option<&S3ErrorInfo> {
+        match self {
+            S3DeckError::AccessDenied(info)
+            | S3DeckError::NoSuchKey(info)
+            | S3DeckError::NoSuchBucket(info)
+            | S3DeckError::ExpiredToken(info)
+            | S3DeckError::SlowDown(info)
+            | S3DeckError::PreconditionFailed(info)
+            | S3DeckError::WrongRegion(info)
+            | S3DeckError::Network(info)
+            | S3DeckError::Credentials(info)
+            | S3DeckError::Service(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.info().map(|info| info.retryable).unwrap_or(false)
+    }
+
+    /// Replace the human readable message while keeping the error kind and request details
+    pub fn with_message(self, message: String) -> Self {
+        match self {
+            S3DeckError::Config(_) => S3DeckError::Config(message),
+            S3DeckError::S3(_) => S3DeckError::S3(message),
+            S3DeckError::Io(_) => S3DeckError::Io(message),
+            S3DeckError::Serialization(_) => S3DeckError::Serialization(message),
+            S3DeckError::BucketNotFound(_) => S3DeckError::BucketNotFound(message),
+            S3DeckError::InvalidPath(_) => S3DeckError::InvalidPath(message),
+            S3DeckError::InvalidInput(_) => S3DeckError::InvalidInput(message),
+            S3DeckError::Index(_) => S3DeckError::Index(message),
+            S3DeckError::AwsProfile(_) => S3DeckError::AwsProfile(message),
+            S3DeckError::AlreadyExists(_) => S3DeckError::AlreadyExists(message),
+            S3DeckError::ReadOnly(_) => S3DeckError::ReadOnly(message),
+            S3DeckError::ConfirmationRequired(info) => {
+                S3DeckError::ConfirmationRequired(ConfirmationInfo { message, ..info })
+            }
+            S3DeckError::AccessDenied(info) => {
+                S3DeckError::AccessDenied(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::NoSuchKey(info) => S3DeckError::NoSuchKey(S3ErrorInfo { message, ..info }),
+            S3DeckError::NoSuchBucket(info) => {
+                S3DeckError::NoSuchBucket(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::ExpiredToken(info) => {
+                S3DeckError::ExpiredToken(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::SlowDown(info) => S3DeckError::SlowDown(S3ErrorInfo { message, ..info }),
+            S3DeckError::PreconditionFailed(info) => {
+                S3DeckError::PreconditionFailed(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::WrongRegion(info) => {
+                S3DeckError::WrongRegion(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::Network(info) => S3DeckError::Network(S3ErrorInfo { message, ..info }),
+            S3DeckError::Credentials(info) => {
+                S3DeckError::Credentials(S3ErrorInfo { message, ..info })
+            }
+            S3DeckError::Service(info) => S3DeckError::Service(S3ErrorInfo { message, ..info }),
+        }
+    }
+}
+
+/// Check whether a dispatch failure was caused by the credential provider rather than the network
+fn is_credentials_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = err.source();
+    while let Some(cause) = source {
+        if cause.downcast_ref::<CredentialsError>().is_some() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Redirect errors are opaque, so name the bucket's region when S3 told us
+fn wrong_region(context: &str, mut info: S3ErrorInfo) -> S3DeckError {
+    if let Some(region) = &info.bucket_region {
+        info.message = format!("{}: the bucket is in region '{}'", context, region);
+    }
+    S3DeckError::WrongRegion(info)
+}
+
+// Errors are serialized as tagged objects, e.g.
+// { "kind": "NoSuchKey", "message": "...", "details": { "code": "NoSuchKey", "httpStatus": 404, ... } }
+impl Serialize for S3DeckError {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("S3DeckError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            S3DeckError::ConfirmationRequired(confirmation) => {
+                state.serialize_field("details", confirmation)?
+            }
+            _ => state.serialize_field("details", &self.info())?,
+        }
+        state.end()
+    }
 }
 
 impl From<std::io::Error> for S3DeckError {
@@ -170,4 +1456,145 @@ impl From<serde_json::Error> for S3DeckError {
     }
 }
 
+impl From<rusqlite::Error> for S3DeckError {
+    fn from(err: rusqlite::Error) -> Self {
+        S3DeckError::Index(err.to_string())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, S3DeckError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::error::{ConnectorError, ErrorMetadata};
+    use aws_sdk_s3::operation::head_object::HeadObjectError;
+    use aws_sdk_s3::primitives::SdkBody;
+
+    type HeadError = SdkError<HeadObjectError, HttpResponse>;
+
+    fn service_error(status: u16, code: Option<&str>, message: Option<&str>) -> HeadError {
+        let mut metadata = ErrorMetadata::builder();
+        if let Some(code) = code {
+            metadata = metadata.code(code);
+        }
+        if let Some(message) = message {
+            metadata = metadata.message(message);
+        }
+        let mut response = HttpResponse::new(status.try_into().unwrap(), SdkBody::empty());
+        response
+            .headers_mut()
+            .insert("x-amz-request-id", "request-1");
+        SdkError::service_error(HeadObjectError::generic(metadata.build()), response)
+    }
+
+    fn classify(status: u16, code: &str) -> S3DeckError {
+        S3DeckError::from_sdk("Failed", service_error(status, Some(code), None))
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (403, "AccessDenied", "AccessDenied"),
+            (403, "SignatureDoesNotMatch", "AccessDenied"),
+            (404, "NoSuchKey", "NoSuchKey"),
+            (404, "NoSuchBucket", "NoSuchBucket"),
+            (301, "PermanentRedirect", "WrongRegion"),
+            (400, "ExpiredToken", "ExpiredToken"),
+            (503, "SlowDown", "SlowDown"),
+            (400, "ThrottlingException", "SlowDown"),
+            (412, "PreconditionFailed", "PreconditionFailed"),
+            (409, "ConditionalRequestConflict", "PreconditionFailed"),
+            (500, "InternalError", "Service"),
+            (409, "BucketNotEmpty", "Service"),
+        ];
+        for (status, code, kind) in cases {
+            let error = classify(status, code);
+            assert_eq!(error.kind(), kind, "{}", code);
+            let info = error.info().unwrap();
+            assert_eq!(info.code.as_deref(), Some(code));
+            assert_eq!(info.http_status, Some(status));
+            assert_eq!(info.request_id.as_deref(), Some("request-1"));
+        }
+
+        assert!(classify(503, "SlowDown").is_retryable());
+        assert!(classify(500, "InternalError").is_retryable());
+        assert!(!classify(403, "AccessDenied").is_retryable());
+        assert!(!classify(409, "BucketNotEmpty").is_retryable());
+    }
+
+    #[test]
+    fn test_status_without_code() {
+        let bare = |status| S3DeckError::from_sdk("Failed", service_error(status, None, None));
+        assert_eq!(bare(301).kind(), "WrongRegion");
+        assert_eq!(bare(403).kind(), "AccessDenied");
+        assert_eq!(bare(404).kind(), "NoSuchKey");
+        assert_eq!(bare(412).kind(), "PreconditionFailed");
+        assert_eq!(bare(429).kind(), "SlowDown");
+        assert!(bare(503).is_retryable());
+        assert_eq!(bare(400).kind(), "Service");
+
+        // A HEAD on a missing bucket only has the status to go by
+        let bucket = S3DeckError::from_bucket_sdk("Failed", service_error(404, None, None));
+        assert_eq!(bucket.kind(), "NoSuchBucket");
+        let bucket =
+            S3DeckError::from_bucket_sdk("Failed", service_error(404, Some("NoSuchKey"), None));
+        assert_eq!(bucket.kind(), "NoSuchKey");
+    }
+
+    #[test]
+    fn test_wrong_region() {
+        let mut response = HttpResponse::new(301.try_into().unwrap(), SdkBody::empty());
+        response
+            .headers_mut()
+            .insert("x-amz-bucket-region", "eu-central-1");
+        let redirect: HeadError = SdkError::service_error(
+            HeadObjectError::generic(ErrorMetadata::builder().build()),
+            response,
+        );
+        let error = S3DeckError::from_sdk("Failed to list objects", redirect);
+        assert_eq!(error.kind(), "WrongRegion");
+        assert_eq!(
+            error.to_string(),
+            "Failed to list objects: the bucket is in region 'eu-central-1'"
+        );
+
+        // Mis-signed requests name the region in the message instead
+        let malformed = service_error(
+            400,
+            Some("AuthorizationHeaderMalformed"),
+            Some("the region 'us-east-1' is wrong; expecting 'ap-south-1'"),
+        );
+        let error = S3DeckError::from_sdk("Failed", malformed);
+        assert_eq!(error.kind(), "WrongRegion");
+        assert_eq!(
+            error.info().unwrap().bucket_region.as_deref(),
+            Some("ap-south-1")
+        );
+
+        // Without a region it is just a bad request
+        let malformed = service_error(400, Some("AuthorizationHeaderMalformed"), Some("bad"));
+        assert_eq!(S3DeckError::from_sdk("Failed", malformed).kind(), "Service");
+    }
+
+    #[test]
+    fn test_transport_errors() {
+        let timeout: HeadError = SdkError::timeout_error("took too long");
+        let error = S3DeckError::from_sdk("Failed", timeout);
+        assert_eq!(error.kind(), "Network");
+        assert!(error.is_retryable());
+
+        let reset = std::io::Error::other("connection reset");
+        let dispatch: HeadError = SdkError::dispatch_failure(ConnectorError::io(Box::new(reset)));
+        let error = S3DeckError::from_sdk("Failed", dispatch);
+        assert_eq!(error.kind(), "Network");
+        assert!(error.is_retryable());
+
+        let missing = CredentialsError::not_loaded("no credentials in the profile");
+        let dispatch: HeadError =
+            SdkError::dispatch_failure(ConnectorError::other(Box::new(missing), None));
+        let error = S3DeckError::from_sdk("Failed", dispatch);
+        assert_eq!(error.kind(), "Credentials");
+        assert!(!error.is_retryable());
+    }
+}
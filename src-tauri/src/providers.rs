@@ -0,0 +1,256 @@
+use crate::models::{BucketConfig, Result, S3DeckError, StorageProvider};
+use serde::Serialize;
+
+/// Addressing, region and checksum quirks of an S3-compatible service
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderPreset {
+    pub provider: StorageProvider,
+    #[serde(rename = "displayName")]
+    pub display_name: &'static str,
+    #[serde(rename = "forcePathStyle")]
+    pub force_path_style: bool,
+    #[serde(rename = "requiresEndpoint")]
+    pub requires_endpoint: bool,
+    /// Endpoint derived from the bucket region when none is configured
+    #[serde(rename = "endpointTemplate")]
+    pub endpoint_template: Option<&'static str>,
+    /// Signing region used when the bucket leaves the region empty
+    #[serde(rename = "defaultRegion")]
+    pub default_region: Option<&'static str>,
+    /// Signing region the service insists on, whatever the bucket says
+    #[serde(rename = "fixedRegion")]
+    pub fixed_region: Option<&'static str>,
+    /// Only send and validate checksums when an operation requires them. Most S3-compatible
+    /// services reject the SDK's default CRC32 trailers and aws-chunked uploads.
+    #[serde(rename = "checksumsWhenRequired")]
+    pub checksums_when_required: bool,
+    /// Whether `If-None-Match`/`If-Match` on writes are enforced. Services that silently
+    /// ignore them get a check-before-write fallback instead.
+    #[serde(rename = "conditionalWrites")]
+    pub conditional_writes: bool,
+}
+
+impl StorageProvider {
+    pub fn preset(self) -> ProviderPreset {
+        let base = ProviderPreset {
+            provider: self,
+            display_name: "",
+            force_path_style: false,
+            requires_endpoint: false,
+            endpoint_template: None,
+            default_region: None,
+            fixed_region: None,
+            checksums_when_required: true,
+            conditional_writes: false,
+        };
+
+        match self {
+            StorageProvider::Aws => ProviderPreset {
+                display_name: "Amazon S3",
+                checksums_when_required: false,
+                conditional_writes: true,
+                ..base
+            },
+            // Self-hosted gateways rarely have wildcard DNS for virtual-hosted buckets
+            StorageProvider::Minio => ProviderPreset {
+                display_name: "MinIO",
+                force_path_style: true,
+                conditional_writes: true,
+                requires_endpoint: true,
+                default_region: Some("us-east-1"),
+                ..base
+            },
+            StorageProvider::CloudflareR2 => ProviderPreset {
+                display_name: "Cloudflare R2",
+                force_path_style: true,
+                conditional_writes: true,
+                requires_endpoint: true,
+                fixed_region: Some("auto"),
+                ..base
+            },
+            StorageProvider::BackblazeB2 => ProviderPreset {
+                display_name: "Backblaze B2",
+                endpoint_template: Some("https://s3.{region}.backblazeb2.com"),
+                ..base
+            },
+            StorageProvider::Wasabi => ProviderPreset {
+                display_name: "Wasabi",
+                endpoint_template: Some("https://s3.{region}.wasabisys.com"),
+                ..base
+            },
+            StorageProvider::DigitalOceanSpaces => ProviderPreset {
+                display_name: "DigitalOcean Spaces",
+                endpoint_template: Some("https://{region}.digitaloceanspaces.com"),
+                ..base
+            },
+            StorageProvider::Ceph => ProviderPreset {
+                display_name: "Ceph RGW",
+                force_path_style: true,
+                requires_endpoint: true,
+                default_region: Some("us-east-1"),
+                ..base
+            },
+            StorageProvider::Custom => ProviderPreset {
+                display_name: "Custom S3-compatible",
+                force_path_style: true,
+                requires_endpoint: true,
+                default_region: Some("us-east-1"),
+                ..base
+            },
+        }
+    }
+}
+
+pub fn all_presets() -> Vec<ProviderPreset> {
+    [
+        StorageProvider::Aws,
+        StorageProvider::Minio,
+        StorageProvider::CloudflareR2,
+        StorageProvider::BackblazeB2,
+        StorageProvider::Wasabi,
+        StorageProvider::DigitalOceanSpaces,
+        StorageProvider::Ceph,
+        StorageProvider::Custom,
+    ]
+    .into_iter()
+    .map(StorageProvider::preset)
+    .collect()
+}
+
+/// Connection settings for a bucket after applying its provider preset
+#[derive(Debug, Clone)]
+pub struct ResolvedEndpoint {
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub force_path_style: bool,
+    pub checksums_when_required: bool,
+    pub conditional_writes: bool,
+}
+
+/// Provider of a bucket; configs saved before providers existed are AWS unless they set an endpoint
+pub fn provider_for(bucket: &BucketConfig) -> StorageProvider {
+    bucket.provider.unwrap_or_else(|| {
+        if non_empty(bucket.endpoint.as_deref()).is_some() {
+            StorageProvider::Custom
+        } else {
+            StorageProvider::Aws
+        }
+    })
+}
+
+pub fn resolve(bucket: &BucketConfig) -> Result<ResolvedEndpoint> {
+    let preset = provider_for(bucket).preset();
+
+    let region = preset
+        .fixed_region
+        .or(non_empty(Some(&bucket.region)))
+        .or(preset.default_region)
+        .unwrap_or_default()
+        .to_string();
+
+    let endpoint = match (
+        non_empty(bucket.endpoint.as_deref()),
+        preset.endpoint_template,
+    ) {
+        (Some(endpoint), _) => Some(endpoint.to_string()),
+        (None, Some(template)) => {
+            if region.is_empty() {
+                return Err(S3DeckError::Config(format!(
+                    "{} buckets need a region to derive the endpoint",
+                    preset.display_name
+                )));
+            }
+            Some(template.replace("{region}", &region))
+        }
+        (None, None) if preset.requires_endpoint => {
+            return Err(S3DeckError::Config(format!(
+                "{} buckets need an endpoint URL",
+                preset.display_name
+            )));
+        }
+        (None, None) => None,
+    };
+
+    Ok(ResolvedEndpoint {
+        region,
+        endpoint,
+        force_path_style: bucket.force_path_style.unwrap_or(preset.force_path_style),
+        checksums_when_required: preset.checksums_when_required,
+        conditional_writes: bucket
+            .conditional_writes
+            .unwrap_or(preset.conditional_writes),
+    })
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(
+        provider: Option<StorageProvider>,
+        region: &str,
+        endpoint: Option<&str>,
+    ) -> BucketConfig {
+        BucketConfig {
+            name: "media".to_string(),
+            region: region.to_string(),
+            endpoint: endpoint.map(|e| e.to_string()),
+            provider,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_legacy_configs_pick_provider_from_endpoint() {
+        let aws = resolve(&bucket(None, "eu-west-1", None)).unwrap();
+        assert_eq!(aws.region, "eu-west-1");
+        assert!(aws.endpoint.is_none());
+        assert!(!aws.force_path_style);
+        assert!(!aws.checksums_when_required);
+
+        let custom = resolve(&bucket(None, "", Some("http://minio.lan:9000"))).unwrap();
+        assert_eq!(custom.region, "us-east-1");
+        assert_eq!(custom.endpoint.as_deref(), Some("http://minio.lan:9000"));
+        assert!(custom.force_path_style);
+        assert!(custom.checksums_when_required);
+    }
+
+    #[test]
+    fn test_presets_derive_endpoints_and_regions() {
+        let wasabi = resolve(&bucket(Some(StorageProvider::Wasabi), "eu-central-2", None)).unwrap();
+        assert_eq!(
+            wasabi.endpoint.as_deref(),
+            Some("https://s3.eu-central-2.wasabisys.com")
+        );
+        assert!(!wasabi.force_path_style);
+
+        let r2 = resolve(&bucket(
+            Some(StorageProvider::CloudflareR2),
+            "eu-west-1",
+            Some("https://account.r2.cloudflarestorage.com"),
+        ))
+        .unwrap();
+        assert_eq!(r2.region, "auto");
+    }
+
+    #[test]
+    fn test_self_hosted_providers_require_endpoint() {
+        let result = resolve(&bucket(Some(StorageProvider::Minio), "", None));
+        assert!(matches!(result, Err(S3DeckError::Config(_))));
+
+        let result = resolve(&bucket(Some(StorageProvider::BackblazeB2), "", None));
+        assert!(matches!(result, Err(S3DeckError::Config(_))));
+    }
+
+    #[test]
+    fn test_force_path_style_override() {
+        let mut config = bucket(Some(StorageProvider::Ceph), "", Some("https://rgw.lan"));
+        config.force_path_style = Some(false);
+
+        assert!(!resolve(&config).unwrap().force_path_style);
+    }
+}
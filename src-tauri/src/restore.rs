@@ -0,0 +1,256 @@
+use crate::client_cache::ClientCache;
+use crate::config::app_dir;
+use crate::models::{PendingRestore, RestoreState, RestoreTier, Result, S3DeckError};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How often `watch_pending_restores` checks by default. Standard restores take hours, so
+/// there's no point asking S3 much more often.
+pub const DEFAULT_CHECK_INTERVAL_SECONDS: u64 = 15 * 60;
+
+pub const MIN_CHECK_INTERVAL_SECONDS: u64 = 60;
+
+/// Storage classes whose objects can't be read until they are restored
+pub fn is_archive_class(storage_class: &str) -> bool {
+    matches!(storage_class, "GLACIER" | "DEEP_ARCHIVE")
+}
+
+/// Parse `x-amz-restore`, e.g. `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00
+/// GMT"`, into whether the restore is still running and when the restored copy expires. `None`
+/// when no restore was ever requested.
+pub fn parse_restore_header(header: Option<&str>) -> Option<(bool, Option<DateTime<Utc>>)> {
+    let header = header?;
+    let value = |name: &str| {
+        let start = header.find(&format!("{}=\"", name))? + name.len() + 2;
+        let end = header[start..].find('"')? + start;
+        Some(&header[start..end])
+    };
+
+    let ongoing = value("ongoing-request")? == "true";
+    let expires_at = value("expiry-date")
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| date.with_timezone(&Utc));
+    Some((ongoing, expires_at))
+}
+
+/// Restores that were requested but haven't completed yet, kept in `restores.json` so they are
+/// still checked after the app restarts
+pub struct RestoreTracker {
+    path: Option<PathBuf>,
+    pending: Mutex<Vec<PendingRestore>>,
+}
+
+impl RestoreTracker {
+    /// The tracked restores, or none when they can't be read. Tracking never blocks a restore,
+    /// so problems are only reported on stderr.
+    pub fn load() -> Self {
+        match app_dir() {
+            Ok(dir) => Self::at(dir.join("restores.json")),
+            Err(e) => {
+                eprintln!("Failed to load pending restores: {}", e);
+                Self {
+                    path: None,
+                    pending: Mutex::new(Vec::new()),
+                }
+            }
+        }
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        let pending = match read(&path) {
+            Ok(pending) => pending,
+            Err(e) => {
+                eprintln!("Failed to read pending restores: {}", e);
+                Vec::new()
+            }
+        };
+        Self {
+            path: Some(path),
+            pending: Mutex::new(pending),
+        }
+    }
+
+    /// Start tracking restores of `keys`, replacing earlier requests for the same objects
+    pub fn add(&self, bucket_id: &str, keys: &[String], tier: RestoreTier, days: i32) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|restore| restore.bucket_id != bucket_id || !keys.contains(&restore.key));
+        pending.extend(keys.iter().map(|key| PendingRestore {
+            bucket_id: bucket_id.to_string(),
+            key: key.clone(),
+            tier,
+            days,
+            requested_at: Utc::now(),
+            expires_at: None,
+        }));
+        self.save(&pending);
+    }
+
+    /// Tracked restores, of one bucket or of all of them
+    pub fn pending(&self, bucket_id: Option<&str>) -> Vec<PendingRestore> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|restore| bucket_id.is_none_or(|id| restore.bucket_id == id))
+            .cloned()
+            .collect()
+    }
+
+    /// Stop tracking restores of a bucket that finished or no longer apply
+    pub fn remove(&self, bucket_id: &str, keys: &[String]) {
+        if keys.is_empty() {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|restore| restore.bucket_id != bucket_id || !keys.contains(&restore.key));
+        self.save(&pending);
+    }
+
+    fn save(&self, pending: &[PendingRestore]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(pending)
+            .map_err(S3DeckError::from)
+            .and_then(|json| fs::write(path, json).map_err(S3DeckError::from));
+        if let Err(e) = result {
+            eprintln!("Failed to save pending restores: {}", e);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<PendingRestore>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Poll the status of every tracked restore, of one bucket or of all of them. Restores that
+/// completed are returned with their expiry and no longer tracked, as are objects that were
+/// deleted or aren't archived anymore. Objects that can't be checked right now stay tracked.
+pub async fn check_pending(
+    clients: &ClientCache,
+    tracker: &RestoreTracker,
+    bucket_id: Option<&str>,
+) -> Vec<PendingRestore> {
+    let mut available = Vec::new();
+    let mut restores = tracker.pending(bucket_id);
+    restores.sort_by(|a, b| a.bucket_id.cmp(&b.bucket_id));
+
+    for bucket_restores in restores.chunk_by(|a, b| a.bucket_id == b.bucket_id) {
+        let bucket_id = &bucket_restores[0].bucket_id;
+        let s3_client = match clients.get(bucket_id).await {
+            Ok(s3_client) => s3_client,
+            Err(S3DeckError::BucketNotFound(_)) => {
+                let keys: Vec<String> = bucket_restores.iter().map(|r| r.key.clone()).collect();
+                tracker.remove(bucket_id, &keys);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Failed to check restores in bucket {}: {}", bucket_id, e);
+                continue;
+            }
+        };
+
+        let mut finished = Vec::new();
+        for restore in bucket_restores {
+            match s3_client.restore_status(&restore.key).await {
+                Ok(status) if status.state == RestoreState::Restored => {
+                    available.push(PendingRestore {
+                        expires_at: status.expires_at,
+                        ..restore.clone()
+                    });
+                    finished.push(restore.key.clone());
+                }
+                Ok(status) if status.state == RestoreState::InProgress => {}
+                // No restore under way anymore, e.g. after a storage class change
+                Ok(_) | Err(S3DeckError::NoSuchKey(_)) => finished.push(restore.key.clone()),
+                Err(e) => eprintln!("Failed to check restore of {}: {}", restore.key, e),
+            }
+        }
+        tracker.remove(bucket_id, &finished);
+    }
+
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeS3;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_restore_header() {
+        assert_eq!(parse_restore_header(None), None);
+        assert_eq!(
+            parse_restore_header(Some("ongoing-request=\"true\"")),
+            Some((true, None))
+        );
+        assert_eq!(
+            parse_restore_header(Some(
+                "ongoing-request=\"false\", expiry-date=\"Fri, 21 Dec 2012 00:00:00 GMT\""
+            )),
+            Some((
+                false,
+                Some(Utc.with_ymd_and_hms(2012, 12, 21, 0, 0, 0).unwrap())
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_status() {
+        let fake = FakeS3::start().await;
+        fake.put("archive/model.bin", b"weights");
+        fake.put("current.csv", b"a,b");
+        fake.set_storage_class("archive/model.bin", "DEEP_ARCHIVE");
+        let client = fake.client().await;
+
+        let current = client.restore_status("current.csv").await.unwrap();
+        assert_eq!(current.state, RestoreState::NotArchived);
+        let status = client.restore_status("archive/model.bin").await.unwrap();
+        assert_eq!(status.state, RestoreState::Archived);
+        assert_eq!(status.storage_class.as_deref(), Some("DEEP_ARCHIVE"));
+
+        let requested = client.restore_object("archive/model.bin", 3, RestoreTier::Bulk);
+        assert!(requested.await.unwrap());
+        let again = client.restore_object("archive/model.bin", 3, RestoreTier::Bulk);
+        assert!(!again.await.unwrap());
+        let status = client.restore_status("archive/model.bin").await.unwrap();
+        assert_eq!(status.state, RestoreState::InProgress);
+
+        let expires_at = Utc.with_ymd_and_hms(2030, 1, 4, 0, 0, 0).unwrap();
+        fake.complete_restore("archive/model.bin", expires_at);
+        let status = client.restore_status("archive/model.bin").await.unwrap();
+        assert_eq!(status.state, RestoreState::Restored);
+        assert_eq!(status.expires_at, Some(expires_at));
+
+        let listed = client.list_objects(Some("archive/")).await.unwrap().0;
+        assert_eq!(listed[0].storage_class.as_deref(), Some("DEEP_ARCHIVE"));
+    }
+
+    #[test]
+    fn test_tracker_persists_pending_restores() {
+        let path =
+            std::env::temp_dir().join(format!("s3deck-restores-{}.json", uuid::Uuid::new_v4()));
+        let tracker = RestoreTracker::at(path.clone());
+        let keys = ["a.bin".to_string(), "b.bin".to_string()];
+        tracker.add("bucket-1", &keys, RestoreTier::Bulk, 7);
+        tracker.add("bucket-2", &keys[..1], RestoreTier::Standard, 1);
+        // Asking again replaces the earlier request
+        tracker.add("bucket-1", &keys[..1], RestoreTier::Expedited, 2);
+
+        let reloaded = RestoreTracker::at(path.clone());
+        let pending = reloaded.pending(Some("bucket-1"));
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].key, "b.bin");
+        assert_eq!(pending[1].tier, RestoreTier::Expedited);
+
+        reloaded.remove("bucket-1", &keys);
+        assert_eq!(RestoreTracker::at(path.clone()).pending(None).len(), 1);
+        fs::remove_file(path).unwrap();
+    }
+}
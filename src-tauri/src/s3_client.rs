@@ -1,28 +1,120 @@
 use crate::content_type::get_content_type_from_extension;
-use crate::models::{BucketConfig, FileItem, ObjectMetadata, RenameResponse, Result, S3DeckError};
+use crate::http_client::build_http_client;
+use crate::models::{
+    BucketConfig, FileItem, MovedObject, NetworkSettings, ObjectHeaders, ObjectMetadata,
+    ObjectSummary, OverwritePolicy, RenameResponse, RestoreState, RestoreStatus, RestoreTier,
+    Result, RetryMode, S3DeckError, S3ErrorInfo, SearchSummary, TrashItem,
+};
+use crate::providers;
+use crate::restore;
+use crate::safeguards::{self, ProtectedPrefixes};
+use crate::search::SearchFilter;
 use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::config::{RequestChecksumCalculation, ResponseChecksumValidation};
+use aws_sdk_s3::operation::delete_object::DeleteObjectOutput;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::types::{
+    BucketVersioningStatus, DeleteMarkerEntry, GlacierJobParameters, MetadataDirective,
+    ObjectVersion, RestoreRequest, StorageClass, Tag, Tagging, Tier,
+};
 use aws_sdk_s3::{config::Credentials, primitives::ByteStream, Client, Config};
-use chrono::DateTime;
-use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::OnceCell;
+
+/// Attempts the SDK makes per request when the bucket doesn't configure them
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Passes `retry_object` makes over one object. Each pass already includes the SDK's own
+/// retries, so this stays small regardless of `maxAttempts`.
+const OBJECT_RETRY_PASSES: u32 = 3;
+
+/// Highest suffix tried when keeping both copies, e.g. `report (99).pdf`
+const MAX_NUMBERED_COPIES: u32 = 99;
+
+/// Largest object a single CopyObject request can copy
+const MAX_COPY_SIZE: i64 = 5 * 1024 * 1024 * 1024;
+
+/// Hidden folder, below the root prefix, that deleted objects are moved into when a bucket has
+/// trash enabled but no versioning. Each delete gets its own `<timestamp>/` subfolder.
+pub const TRASH_FOLDER: &str = ".s3deck-trash/";
+
+const TRASH_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// How a delete is carried out for a given key
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DeleteMode {
+    Permanent,
+    /// Moved into the trash folder
+    TrashFolder,
+    /// Plain delete on a versioned bucket, which leaves a restorable delete marker
+    DeleteMarker,
+}
+
+/// What a delete removed and, where the trash kept them, how to restore the objects
+#[derive(Debug, Clone, Default)]
+pub struct Deletion {
+    pub count: i32,
+    /// The keys that were deleted
+    pub keys: Vec<String>,
+    /// Keys that couldn't be deleted, with the reason
+    pub failed: Vec<(String, String)>,
+    pub trashed: Vec<TrashItem>,
+}
+
+/// Result of a write made under an overwrite policy
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteOutcome {
+    /// Written under this key, which differs from the requested one for `KeepBoth`
+    Written(String),
+    /// Left alone because the destination exists and the policy is `Skip`
+    Skipped,
+}
 
 pub struct S3Client {
     client: Client,
     bucket_name: String,
+    anonymous: bool,
+    read_only: bool,
+    trash_enabled: bool,
+    /// Whether versioning is enabled, looked up on the first delete
+    versioning_enabled: OnceCell<bool>,
+    protected_prefixes: ProtectedPrefixes,
+    /// Normalized `rootPrefix`, ending in `/`, or empty when the whole bucket is visible
+    root_prefix: String,
+    /// Cleared once the endpoint answers a conditional request with 501
+    conditional_writes: AtomicBool,
+    conditional_deletes: AtomicBool,
 }
 
 impl S3Client {
-    pub async fn new(bucket_config: &BucketConfig) -> Result<Self> {
-        let region = Region::new(bucket_config.region.clone());
+    pub async fn new(bucket_config: &BucketConfig, network: &NetworkSettings) -> Result<Self> {
+        let resolved = providers::resolve(bucket_config)?;
+        let root_prefix = normalize_root_prefix(bucket_config.root_prefix.as_deref())?;
+        let protected_prefixes = ProtectedPrefixes::new(&bucket_config.protected_prefixes)?;
+        let region = Region::new(resolved.region.clone());
+        let http_client = build_http_client(network, Some(bucket_config))?;
 
         let mut config_builder = Config::builder()
             .region(region.clone())
-            .behavior_version(BehaviorVersion::latest());
+            .behavior_version(BehaviorVersion::latest())
+            .http_client(http_client.clone());
 
+        // Anonymous buckets send unsigned requests and never touch a credential chain.
         // If access_key and secret_key are provided, use them directly
         // Otherwise, use default AWS credential provider (supports AWS profiles)
-        if !bucket_config.access_key.is_empty() && !bucket_config.secret_key.is_empty() {
+        if bucket_config.anonymous {
+            config_builder = config_builder.allow_no_auth();
+        } else if !bucket_config.access_key.is_empty() && !bucket_config.secret_key.is_empty() {
             let credentials = Credentials::new(
                 &bucket_config.access_key,
                 &bucket_config.secret_key,
@@ -33,8 +125,10 @@ impl S3Client {
             config_builder = config_builder.credentials_provider(credentials);
         } else {
             // Use AWS profile if specified, otherwise use default credential provider chain
-            let mut aws_config_builder =
-                aws_config::defaults(BehaviorVersion::latest()).region(region.clone());
+            // SSO and STS calls made while resolving credentials go through the same proxy
+            let mut aws_config_builder = aws_config::defaults(BehaviorVersion::latest())
+                .region(region.clone())
+                .http_client(http_client);
 
             if let Some(profile_name) = &bucket_config.aws_profile {
                 aws_config_builder = aws_config_builder.profile_name(profile_name);
@@ -45,10 +139,39 @@ impl S3Client {
                 config_builder.credentials_provider(aws_config.credentials_provider().unwrap());
         }
 
-        // Set custom endpoint if provided
-        if let Some(endpoint) = &bucket_config.endpoint {
+        // Set custom endpoint if provided or implied by the provider
+        if let Some(endpoint) = &resolved.endpoint {
             config_builder = config_builder.endpoint_url(endpoint);
         }
+        config_builder = config_builder.force_path_style(resolved.force_path_style);
+        if resolved.checksums_when_required {
+            config_builder = config_builder
+                .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+                .response_checksum_validation(ResponseChecksumValidation::WhenRequired);
+        }
+
+        // Retry and timeout policy applies to every request made with this client
+        let max_attempts = bucket_config
+            .max_attempts
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+            .max(1);
+        let retry_config = match bucket_config.retry_mode {
+            Some(RetryMode::Adaptive) => RetryConfig::adaptive(),
+            _ => RetryConfig::standard(),
+        };
+        config_builder = config_builder.retry_config(retry_config.with_max_attempts(max_attempts));
+
+        let mut timeout_config = TimeoutConfig::builder();
+        if let Some(ms) = bucket_config.connect_timeout_ms {
+            timeout_config = timeout_config.connect_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = bucket_config.read_timeout_ms {
+            timeout_config = timeout_config.read_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = bucket_config.operation_timeout_ms {
+            timeout_config = timeout_config.operation_timeout(Duration::from_millis(ms));
+        }
+        config_builder = config_builder.timeout_config(timeout_config.build());
 
         let config = config_builder.build();
         let client = Client::from_conf(config);
@@ -56,30 +179,258 @@ impl S3Client {
         Ok(Self {
             client,
             bucket_name: bucket_config.name.clone(),
+            anonymous: bucket_config.anonymous,
+            read_only: bucket_config.read_only,
+            trash_enabled: bucket_config.trash_enabled,
+            versioning_enabled: OnceCell::new(),
+            protected_prefixes,
+            root_prefix,
+            conditional_writes: AtomicBool::new(resolved.conditional_writes),
+            conditional_deletes: AtomicBool::new(resolved.conditional_writes),
         })
     }
 
-    pub async fn list_objects(&self, prefix: Option<&str>) -> Result<Vec<FileItem>> {
+    /// Retry a single object operation inside a long-running job, so one object that keeps
+    /// failing after the SDK's own retries doesn't abort or silently drop out of the whole job
+    pub async fn retry_object<T, F, Fut>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if e.is_retryable() && attempt < OBJECT_RETRY_PASSES => {
+                    // Back off exponentially between passes: 500ms, 1s, 2s, ... capped at 16s
+                    let delay = Duration::from_millis(500 * 2u64.pow((attempt - 1).min(5)));
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Ask S3 which region the bucket lives in. S3 reports it in `x-amz-bucket-region` even
+    /// when it refuses the request, e.g. with a redirect or access denied.
+    pub async fn detect_region(&self) -> Result<Option<String>> {
+        match self
+            .client
+            .head_bucket()
+            .bucket(&self.bucket_name)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(output.bucket_region().map(|s| s.to_string())),
+            Err(e) => {
+                let error = S3DeckError::from_bucket_sdk("Failed to detect bucket region", e);
+                match error.info().and_then(|info| info.bucket_region.clone()) {
+                    Some(region) => Ok(Some(region)),
+                    None => Err(error),
+                }
+            }
+        }
+    }
+
+    /// Reject operations that change the bucket when it can't be written to
+    pub fn ensure_writable(&self) -> Result<()> {
+        if self.anonymous {
+            return Err(S3DeckError::ReadOnly(format!(
+                "Bucket '{}' is accessed anonymously, so it is read-only",
+                self.bucket_name
+            )));
+        }
+        if self.read_only {
+            return Err(S3DeckError::ReadOnly(format!(
+                "Bucket '{}' is marked read-only",
+                self.bucket_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Require the typed confirmation token before a destructive operation on a protected key.
+    /// Read-only buckets refuse outright rather than asking for a confirmation first.
+    pub fn ensure_confirmed(&self, key: &str, confirmation: Option<&str>) -> Result<()> {
+        self.ensure_writable()?;
+        safeguards::ensure_confirmed(
+            &self.protected_prefixes,
+            &self.bucket_name,
+            key,
+            confirmation,
+        )
+    }
+
+    /// The prefix to list or walk: the given one, or the root prefix when none is given
+    pub fn scoped_prefix<'a>(&'a self, prefix: Option<&'a str>) -> Result<&'a str> {
+        let prefix = prefix
+            .filter(|p| !p.is_empty())
+            .unwrap_or(&self.root_prefix);
+        self.ensure_in_scope(prefix)?;
+        Ok(prefix)
+    }
+
+    /// A key or folder path from the frontend, placed below the root prefix unless it already
+    /// is. The bucket root is shown as `""`, so keys created there arrive without the prefix.
+    pub fn scoped_key(&self, key: &str) -> String {
+        if self.root_prefix.is_empty() || key.starts_with(&self.root_prefix) {
+            key.to_string()
+        } else if key == self.root_prefix.trim_end_matches('/') {
+            self.root_prefix.clone()
+        } else {
+            format!("{}{}", self.root_prefix, key.trim_start_matches('/'))
+        }
+    }
+
+    /// Reject keys and prefixes outside the bucket's root prefix. Commands take raw keys from
+    /// the frontend, so this is the only place the scope is actually enforced.
+    fn ensure_in_scope(&self, key: &str) -> Result<()> {
+        if self.root_prefix.is_empty() {
+            return Ok(());
+        }
+
+        // S3 keys are literal, but some gateways and proxies normalize dot segments in paths
+        if !key.starts_with(&self.root_prefix) || has_dot_segments(key) {
+            return Err(S3DeckError::InvalidPath(format!(
+                "'{}' is outside the bucket root '{}'",
+                key, self.root_prefix
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write a key under an overwrite policy. `write(key, if_absent)` sends the request and must
+    /// add `If-None-Match: *` when `if_absent` is set, so the existence check and the write
+    /// happen atomically on the server.
+    async fn write_with_policy<F, Fut>(
+        &self,
+        key: &str,
+        policy: OverwritePolicy,
+        write: F,
+    ) -> Result<WriteOutcome>
+    where
+        F: Fn(String, bool) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if policy == OverwritePolicy::Overwrite {
+            write(key.to_string(), false).await?;
+            return Ok(WriteOutcome::Written(key.to_string()));
+        }
+
+        for n in 0..=MAX_NUMBERED_COPIES {
+            let candidate = if n == 0 {
+                key.to_string()
+            } else {
+                numbered_key(key, n)
+            };
+
+            match self.write_if_absent(&candidate, &write).await {
+                Ok(()) => return Ok(WriteOutcome::Written(candidate)),
+                Err(S3DeckError::PreconditionFailed(_)) => match policy {
+                    OverwritePolicy::Skip => return Ok(WriteOutcome::Skipped),
+                    OverwritePolicy::KeepBoth => continue,
+                    _ => {
+                        return Err(S3DeckError::AlreadyExists(format!(
+                            "'{}' already exists",
+                            key
+                        )))
+                    }
+                },
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(S3DeckError::AlreadyExists(format!(
+            "No free numbered name left for '{}'",
+            key
+        )))
+    }
+
+    async fn write_if_absent<F, Fut>(&self, key: &str, write: &F) -> Result<()>
+    where
+        F: Fn(String, bool) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if self.conditional_writes.load(Ordering::Relaxed) {
+            match write(key.to_string(), true).await {
+                Err(e) if is_not_implemented(&e) => {
+                    self.conditional_writes.store(false, Ordering::Relaxed);
+                }
+                result => return result,
+            }
+        }
+
+        // Without conditional writes this is still check-then-act, but it is the best the
+        // endpoint allows
+        if self.object_exists(key).await? {
+            return Err(already_exists(key));
+        }
+        write(key.to_string(), false).await
+    }
+
+    pub async fn object_exists(&self, key: &str) -> Result<bool> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => match S3DeckError::from_sdk("Failed to check existing object", e) {
+                S3DeckError::NoSuchKey(_) => Ok(false),
+                e => Err(e),
+            },
+        }
+    }
+
+    /// A folder exists implicitly as soon as any object lives under its prefix
+    async fn prefix_has_objects(&self, prefix: &str) -> Result<bool> {
+        let response = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .prefix(prefix)
+            .max_keys(1)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_bucket_sdk("Failed to check existing objects", e))?;
+
+        Ok(!response.contents().is_empty() || !response.common_prefixes().is_empty())
+    }
+
+    /// List one page of the folders and files directly below a prefix, and whether that page
+    /// holds the whole level. Without a prefix this lists the bucket root, which is the root
+    /// prefix when one is configured.
+    pub async fn list_objects(&self, prefix: Option<&str>) -> Result<(Vec<FileItem>, bool)> {
+        let prefix = self.scoped_prefix(prefix)?;
+
         let mut request = self
             .client
             .list_objects_v2()
             .bucket(&self.bucket_name)
             .delimiter("/");
 
-        if let Some(prefix) = prefix {
+        if !prefix.is_empty() {
             request = request.prefix(prefix);
         }
 
         let response = request
             .send()
             .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to list objects: {}", e)))?;
+            .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list objects", e))?;
 
         let mut items = Vec::new();
 
         // Add folders (common prefixes)
+        let trash_prefix = self.trash_prefix();
         for prefix in response.common_prefixes() {
             if let Some(prefix_str) = prefix.prefix() {
+                if prefix_str == trash_prefix {
+                    continue;
+                }
+
                 let name = prefix_str.trim_end_matches('/');
                 let display_name = if let Some(pos) = name.rfind('/') {
                     &name[pos + 1..]
@@ -93,6 +444,7 @@ impl S3Client {
                     size: 0,
                     is_folder: true,
                     last_modified: None,
+                    storage_class: None,
                 });
             }
         }
@@ -121,62 +473,246 @@ impl S3Client {
                     size: object.size().unwrap_or(0),
                     is_folder: false,
                     last_modified,
+                    storage_class: object.storage_class().map(|c| c.as_str().to_string()),
                 });
             }
         }
 
-        Ok(items)
+        Ok((items, !response.is_truncated().unwrap_or(false)))
     }
 
-    pub async fn upload_file(&self, key: &str, file_path: &Path) -> Result<i64> {
+    pub async fn upload_file(
+        &self,
+        key: &str,
+        file_path: &Path,
+        policy: OverwritePolicy,
+    ) -> Result<(WriteOutcome, i64)> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
         let file_content = fs::read(file_path).await?;
         let file_size = file_content.len() as i64;
 
         let content_type = get_content_type_from_extension(key);
-        let body = ByteStream::from(file_content);
 
-        self.client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(key)
-            .content_type(content_type)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to upload file: {}", e)))?;
+        let outcome = self
+            .write_with_policy(key, policy, |key, if_absent| {
+                let body = ByteStream::from(file_content.clone());
+                let content_type = &content_type;
+                async move {
+                    self.client
+                        .put_object()
+                        .bucket(&self.bucket_name)
+                        .key(key)
+                        .content_type(content_type)
+                        .body(body)
+                        .set_if_none_match(if_absent.then(|| "*".to_string()))
+                        .send()
+                        .await
+                        .map_err(|e| S3DeckError::from_sdk("Failed to upload file", e))?;
+                    Ok(())
+                }
+            })
+            .await?;
+
+        Ok((outcome, file_size))
+    }
+
+    /// Delete an object, moving it to the trash instead when the bucket has trash enabled.
+    /// Objects the trash can't take are only deleted permanently with the confirmation token.
+    pub async fn delete_object(&self, key: &str, confirmation: Option<&str>) -> Result<Deletion> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
+        let mode = self.delete_mode(key).await;
+        let trashed = self
+            .delete_with_mode(key, mode, &trash_timestamp(), confirmation, None)
+            .await?;
+        Ok(Deletion {
+            count: 1,
+            keys: vec![key.to_string()],
+            failed: Vec::new(),
+            trashed: trashed.into_iter().collect(),
+        })
+    }
+
+    /// Delete a key the given way, returning where it can be restored from. With `if_match`
+    /// set, the key is only deleted while it still has that ETag.
+    async fn delete_with_mode(
+        &self,
+        key: &str,
+        mode: DeleteMode,
+        timestamp: &str,
+        confirmation: Option<&str>,
+        if_match: Option<&str>,
+    ) -> Result<Option<TrashItem>> {
+        let trashed = |trash_key: Option<String>, version_id: Option<String>| TrashItem {
+            key: key.to_string(),
+            trash_key,
+            version_id,
+            deleted_at: Some(Utc::now()),
+            size: 0,
+        };
+
+        match mode {
+            DeleteMode::TrashFolder => {
+                let source = self.get_object_metadata(key).await?;
+                if if_match.is_some_and(|etag| source.etag.as_deref() != Some(etag)) {
+                    return Err(S3DeckError::PreconditionFailed(S3ErrorInfo {
+                        message: format!("'{}' has changed", key),
+                        ..Default::default()
+                    }));
+                }
+                if let Some(reason) =
+                    untrashable_reason(source.storage_class.as_deref(), source.content_length)
+                {
+                    safeguards::ensure_permanent_delete_confirmed(
+                        &self.bucket_name,
+                        key,
+                        &reason,
+                        confirmation,
+                    )?;
+                    self.delete_if_unchanged(key, source.etag.as_deref())
+                        .await?;
+                    return Ok(None);
+                }
 
-        Ok(file_size)
+                let trash_key = self.trash_key(key, timestamp);
+                self.move_with_metadata(key, &source, &trash_key, OverwritePolicy::Overwrite)
+                    .await?;
+                Ok(Some(trashed(Some(trash_key), None)))
+            }
+            DeleteMode::DeleteMarker => {
+                let response = self.send_delete(key, if_match).await?;
+                Ok(response
+                    .version_id()
+                    .filter(|_| response.delete_marker() == Some(true))
+                    .map(|version_id| trashed(None, Some(version_id.to_string()))))
+            }
+            DeleteMode::Permanent => {
+                self.send_delete(key, if_match).await?;
+                Ok(None)
+            }
+        }
     }
 
-    pub async fn delete_object(&self, key: &str) -> Result<()> {
+    async fn remove_object(&self, key: &str) -> Result<()> {
         self.client
             .delete_object()
             .bucket(&self.bucket_name)
             .key(key)
             .send()
             .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to delete object: {}", e)))?;
+            .map_err(|e| S3DeckError::from_sdk("Failed to delete object", e))?;
+
+        Ok(())
+    }
 
+    /// Delete an object only while it still has the given ETag, so a source rewritten in the
+    /// middle of a move is kept rather than lost
+    async fn delete_if_unchanged(&self, key: &str, etag: Option<&str>) -> Result<()> {
+        self.send_delete(key, etag).await?;
         Ok(())
     }
 
-    pub async fn delete_folder(&self, prefix: &str) -> Result<i32> {
+    /// Delete an object, conditional on its ETag when one is given and the provider supports it
+    async fn send_delete(&self, key: &str, etag: Option<&str>) -> Result<DeleteObjectOutput> {
+        let if_match = etag.filter(|_| self.conditional_deletes.load(Ordering::Relaxed));
+        let send = |if_match: Option<&str>| {
+            self.client
+                .delete_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .set_if_match(if_match.map(|etag| etag.to_string()))
+                .send()
+        };
+
+        match send(if_match).await {
+            Ok(response) => Ok(response),
+            Err(e) => match S3DeckError::from_sdk("Failed to delete object", e) {
+                e if if_match.is_some() && is_not_implemented(&e) => {
+                    self.conditional_deletes.store(false, Ordering::Relaxed);
+                    send(None)
+                        .await
+                        .map_err(|e| S3DeckError::from_sdk("Failed to delete object", e))
+                }
+                e => Err(e),
+            },
+        }
+    }
+
+    pub async fn delete_folder(
+        &self,
+        prefix: &str,
+        confirmation: Option<&str>,
+    ) -> Result<Deletion> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(prefix)?;
+
+        // Ask before anything is deleted rather than trashing half the folder first
+        if self.delete_mode(prefix).await == DeleteMode::TrashFolder {
+            self.ensure_folder_trashable(prefix, confirmation).await?;
+        }
+
         let objects = self.list_all_objects_with_prefix(prefix).await?;
-        let mut deleted_count = 0;
+        let mut deletion = Deletion::default();
+        // One trash subfolder for the whole folder, so it can be restored together
+        let timestamp = trash_timestamp();
 
         for object_key in objects {
-            match self.delete_object(&object_key).await {
-                Ok(_) => deleted_count += 1,
+            let mode = self.delete_mode(&object_key).await;
+            match self
+                .retry_object(|| {
+                    self.delete_with_mode(&object_key, mode, &timestamp, confirmation, None)
+                })
+                .await
+            {
+                Ok(trashed) => {
+                    deletion.count += 1;
+                    deletion.keys.push(object_key);
+                    deletion.trashed.extend(trashed);
+                }
                 Err(e) => {
                     eprintln!("Failed to delete object {}: {}", object_key, e);
+                    deletion.failed.push((object_key, e.to_string()));
                 }
             }
         }
 
-        Ok(deleted_count)
+        Ok(deletion)
+    }
+
+    /// Require the confirmation token when any object below a folder can't go to the trash
+    async fn ensure_folder_trashable(
+        &self,
+        prefix: &str,
+        confirmation: Option<&str>,
+    ) -> Result<()> {
+        let mut continuation_token = None;
+        loop {
+            let (page, next_token) = self.list_page(prefix, continuation_token).await?;
+            for object in page {
+                if let Some(reason) =
+                    untrashable_reason(object.storage_class.as_deref(), object.size)
+                {
+                    return safeguards::ensure_permanent_delete_confirmed(
+                        &self.bucket_name,
+                        &object.key,
+                        &reason,
+                        confirmation,
+                    );
+                }
+            }
+            match next_token {
+                Some(token) => continuation_token = Some(token),
+                None => return Ok(()),
+            }
+        }
     }
 
     pub async fn get_object_metadata(&self, key: &str) -> Result<ObjectMetadata> {
+        self.ensure_in_scope(key)?;
+
         let response = self
             .client
             .head_object()
@@ -184,7 +720,7 @@ impl S3Client {
             .key(key)
             .send()
             .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to get object metadata: {}", e)))?;
+            .map_err(|e| S3DeckError::from_sdk("Failed to get object metadata", e))?;
 
         let content_length = response.content_length().unwrap_or(0);
         let size_formatted = self.format_file_size(content_length);
@@ -212,6 +748,201 @@ impl S3Client {
         })
     }
 
+    /// An object's tags
+    pub async fn get_object_tags(&self, key: &str) -> Result<HashMap<String, String>> {
+        self.ensure_in_scope(key)?;
+
+        let response = self
+            .client
+            .get_object_tagging()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to get object tags", e))?;
+
+        Ok(response
+            .tag_set()
+            .iter()
+            .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+            .collect())
+    }
+
+    /// Current editable headers of an object
+    pub async fn get_object_headers(&self, key: &str) -> Result<ObjectHeaders> {
+        self.ensure_in_scope(key)?;
+        Ok(object_headers(&self.head_object(key).await?))
+    }
+
+    async fn head_object(&self, key: &str) -> Result<HeadObjectOutput> {
+        self.client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to get object metadata", e))
+    }
+
+    /// Replace an object's headers by copying it onto itself, returning the previous headers.
+    /// The copy only goes ahead while the object is unchanged, so a concurrent upload isn't
+    /// overwritten with stale content, and keeps the storage class, which would otherwise
+    /// fall back to `STANDARD`.
+    pub async fn update_object_metadata(
+        &self,
+        key: &str,
+        headers: &ObjectHeaders,
+    ) -> Result<ObjectHeaders> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
+        let current = self.head_object(key).await?;
+
+        self.client
+            .copy_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .copy_source(copy_source(&self.bucket_name, key))
+            .metadata_directive(MetadataDirective::Replace)
+            .set_copy_source_if_match(current.e_tag().map(|etag| etag.to_string()))
+            .set_storage_class(current.storage_class().cloned())
+            .set_content_type(headers.content_type.clone())
+            .set_cache_control(headers.cache_control.clone())
+            .set_content_disposition(headers.content_disposition.clone())
+            .set_content_encoding(headers.content_encoding.clone())
+            .set_content_language(headers.content_language.clone())
+            .set_metadata(Some(headers.metadata.clone()))
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to update object metadata", e))?;
+
+        Ok(object_headers(&current))
+    }
+
+    /// Move an object to another storage class by copying it onto itself. The copy only goes
+    /// ahead while the object is unchanged.
+    pub async fn set_storage_class(&self, key: &str, storage_class: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
+        let etag = self.get_object_metadata(key).await?.etag;
+        self.client
+            .copy_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .copy_source(copy_source(&self.bucket_name, key))
+            .metadata_directive(MetadataDirective::Copy)
+            .storage_class(StorageClass::from(storage_class))
+            .set_copy_source_if_match(etag)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to change storage class", e))?;
+        Ok(())
+    }
+
+    /// Replace an object's tags
+    pub async fn set_object_tags(&self, key: &str, tags: &HashMap<String, String>) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
+        let tag_set = tags
+            .iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| S3DeckError::InvalidInput(format!("Invalid tag: {}", e)))?;
+        let tagging = Tagging::builder()
+            .set_tag_set(Some(tag_set))
+            .build()
+            .map_err(|e| S3DeckError::InvalidInput(format!("Invalid tags: {}", e)))?;
+
+        self.client
+            .put_object_tagging()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .tagging(tagging)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to set object tags", e))?;
+        Ok(())
+    }
+
+    /// Ask S3 to restore a temporary copy of an archived object for `days`. Asking again for an
+    /// object that is already restored extends its expiry. Returns false when a restore of the
+    /// object is already in progress.
+    pub async fn restore_object(&self, key: &str, days: i32, tier: RestoreTier) -> Result<bool> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+
+        let tier = match tier {
+            RestoreTier::Expedited => Tier::Expedited,
+            RestoreTier::Standard => Tier::Standard,
+            RestoreTier::Bulk => Tier::Bulk,
+        };
+        let parameters = GlacierJobParameters::builder()
+            .tier(tier)
+            .build()
+            .map_err(|e| S3DeckError::InvalidInput(format!("Invalid restore tier: {}", e)))?;
+        let result = self
+            .client
+            .restore_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .restore_request(
+                RestoreRequest::builder()
+                    .days(days)
+                    .glacier_job_parameters(parameters)
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to restore object", e));
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.info().and_then(|info| info.code.as_deref())
+                    == Some("RestoreAlreadyInProgress") =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether an object is archived and how far along its restore is, from `x-amz-restore`
+    pub async fn restore_status(&self, key: &str) -> Result<RestoreStatus> {
+        self.ensure_in_scope(key)?;
+
+        let response = self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to get restore status", e))?;
+
+        let storage_class = response.storage_class().map(|s| s.as_str().to_string());
+        // Intelligent-Tiering reports its archive tiers separately from the storage class
+        let archived = storage_class
+            .as_deref()
+            .is_some_and(restore::is_archive_class)
+            || response.archive_status().is_some();
+        let (state, expires_at) = match restore::parse_restore_header(response.restore()) {
+            Some((true, _)) => (RestoreState::InProgress, None),
+            Some((false, expires_at)) => (RestoreState::Restored, expires_at),
+            None if archived => (RestoreState::Archived, None),
+            None => (RestoreState::NotArchived, None),
+        };
+
+        Ok(RestoreStatus {
+            key: key.to_string(),
+            storage_class,
+            state,
+            expires_at,
+        })
+    }
+
     async fn list_all_objects_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
         let mut objects = Vec::new();
         let mut continuation_token = None;
@@ -230,7 +961,7 @@ impl S3Client {
             let response = request
                 .send()
                 .await
-                .map_err(|e| S3DeckError::S3(format!("Failed to list objects: {}", e)))?;
+                .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list objects", e))?;
 
             for object in response.contents() {
                 if let Some(key) = object.key() {
@@ -270,66 +1001,89 @@ impl S3Client {
         old_key: &str,
         new_key: &str,
         is_folder: bool,
+        policy: OverwritePolicy,
     ) -> Result<RenameResponse> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(old_key)?;
+        self.ensure_in_scope(new_key)?;
+
         if is_folder {
-            self.rename_folder(old_key, new_key).await
+            self.rename_folder(old_key, new_key, policy).await
         } else {
-            self.rename_file(old_key, new_key).await
+            self.rename_file(old_key, new_key, policy).await
         }
     }
 
-    async fn rename_file(&self, old_key: &str, new_key: &str) -> Result<RenameResponse> {
+    async fn rename_file(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        policy: OverwritePolicy,
+    ) -> Result<RenameResponse> {
         // Check if source file exists
-        let _metadata = self
-            .get_object_metadata(old_key)
-            .await
-            .map_err(|e| S3DeckError::S3(format!("Source file '{}' not found: {}", old_key, e)))?;
-
-        // Check if destination file already exists
-        if let Ok(_) = self.get_object_metadata(new_key).await {
-            return Err(S3DeckError::S3(format!(
-                "Destination file '{}' already exists",
-                new_key
-            )));
-        }
-
-        // Get the new content type based on the new file extension
-        let content_type = get_content_type_from_extension(new_key);
-
-        let copy_source = format!("{}/{}", self.bucket_name, old_key);
-
-        let copy_request = self
-            .client
-            .copy_object()
-            .bucket(&self.bucket_name)
-            .key(new_key)
-            .copy_source(&copy_source)
-            .content_type(&content_type);
-
-        copy_request.send().await.map_err(|e| {
-            S3DeckError::S3(format!(
-                "Failed to copy object from '{}' to '{}': {}. Copy source: '{}'",
-                old_key, new_key, e, copy_source
-            ))
+        let source = self.get_object_metadata(old_key).await.map_err(|e| {
+            let message = format!("Source file '{}' not found: {}", old_key, e);
+            e.with_message(message)
         })?;
 
-        // Delete original object
-        self.delete_object(old_key).await?;
-
         // Extract just the filename for display
         let old_filename = old_key.split('/').last().unwrap_or(old_key);
-        let new_filename = new_key.split('/').last().unwrap_or(new_key);
+
+        // The destination check is part of the copy itself, so nothing can sneak in between
+        let outcome = self
+            .move_with_metadata(old_key, &source, new_key, policy)
+            .await
+            .map_err(|e| match e {
+                S3DeckError::AlreadyExists(_) => S3DeckError::AlreadyExists(format!(
+                    "Destination file '{}' already exists",
+                    new_key
+                )),
+                e => e,
+            })?;
+
+        let new_key = match outcome {
+            WriteOutcome::Written(key) => key,
+            WriteOutcome::Skipped => {
+                return Ok(RenameResponse {
+                    message: format!(
+                        "Skipped renaming '{}': destination already exists",
+                        old_filename
+                    ),
+                    old_key: old_key.to_string(),
+                    new_key: new_key.to_string(),
+                    moved_files: None,
+                    total_moved: None,
+                    skipped_files: Some(vec![old_key.to_string()]),
+                    moved_objects: Vec::new(),
+                })
+            }
+        };
+
+        let new_filename = new_key.split('/').last().unwrap_or(&new_key);
 
         Ok(RenameResponse {
             message: format!("File renamed from '{}' to '{}'", old_filename, new_filename),
             old_key: old_key.to_string(),
-            new_key: new_key.to_string(),
+            new_key: new_key.clone(),
             moved_files: None,
             total_moved: None,
+            skipped_files: None,
+            moved_objects: vec![MovedObject {
+                from: old_key.to_string(),
+                to: new_key.clone(),
+            }],
         })
     }
 
-    pub async fn create_folder(&self, folder_path: &str) -> Result<String> {
+    pub async fn create_folder(
+        &self,
+        folder_path: &str,
+        policy: OverwritePolicy,
+    ) -> Result<String> {
+        self.ensure_writable()?;
+        let folder_path = &self.scoped_key(folder_path);
+        self.ensure_in_scope(folder_path)?;
+
         // Ensure the folder path ends with '/'
         let folder_key = if folder_path.ends_with('/') {
             folder_path.to_string()
@@ -337,70 +1091,54 @@ impl S3Client {
             format!("{}/", folder_path)
         };
 
-        // Check if folder already exists by trying to head the object
-        match self
-            .client
-            .head_object()
-            .bucket(&self.bucket_name)
-            .key(&folder_key)
-            .send()
-            .await
-        {
-            Ok(_) => {
-                // Folder already exists
-                return Err(S3DeckError::S3(format!(
-                    "Folder '{}' already exists",
-                    folder_key.trim_end_matches('/')
-                )));
-            }
-            Err(_) => {
-                // Folder doesn't exist, we can proceed to create it
-            }
-        }
+        let outcome = self
+            .write_with_policy(&folder_key, policy, |key, if_absent| async move {
+                // Also treat the folder as existing when objects live under its prefix
+                // without a folder marker
+                if policy != OverwritePolicy::Overwrite && self.prefix_has_objects(&key).await? {
+                    return Err(already_exists(&key));
+                }
 
-        // Also check if there's a folder with the same name by listing objects
-        // This handles cases where the folder might exist as a common prefix
-        let list_response = self
-            .client
-            .list_objects_v2()
-            .bucket(&self.bucket_name)
-            .prefix(&folder_key)
-            .max_keys(1)
-            .send()
+                // Create an empty object with the folder key to represent the folder
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket_name)
+                    .key(&key)
+                    .content_type("application/x-directory")
+                    .body(ByteStream::from(vec![]))
+                    .set_if_none_match(if_absent.then(|| "*".to_string()))
+                    .send()
+                    .await
+                    .map_err(|e| S3DeckError::from_sdk("Failed to create folder", e))?;
+                Ok(())
+            })
             .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to check existing objects: {}", e)))?;
-
-        // Check if there are any objects with this prefix (meaning folder exists)
-        if list_response.contents().len() > 0 || list_response.common_prefixes().len() > 0 {
-            return Err(S3DeckError::S3(format!(
-                "Folder '{}' already exists",
+            .map_err(|e| match e {
+                S3DeckError::AlreadyExists(_) => S3DeckError::AlreadyExists(format!(
+                    "Folder '{}' already exists",
+                    folder_key.trim_end_matches('/')
+                )),
+                e => e,
+            })?;
+
+        match outcome {
+            WriteOutcome::Written(key) => Ok(format!(
+                "Folder '{}' created successfully",
+                key.trim_end_matches('/')
+            )),
+            WriteOutcome::Skipped => Ok(format!(
+                "Folder '{}' already exists, skipped",
                 folder_key.trim_end_matches('/')
-            )));
+            )),
         }
-
-        // Create an empty object with the folder key to represent the folder
-        let body = ByteStream::from(vec![]);
-
-        self.client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(&folder_key)
-            .content_type("application/x-directory")
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| S3DeckError::S3(format!("Failed to create folder: {}", e)))?;
-
-        Ok(format!(
-            "Folder '{}' created successfully",
-            folder_key.trim_end_matches('/')
-        ))
     }
 
     pub async fn get_folder_latest_modified(
         &self,
         folder_prefix: &str,
     ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        self.ensure_in_scope(folder_prefix)?;
+
         let mut latest_modified: Option<chrono::DateTime<chrono::Utc>> = None;
         let mut continuation_token = None;
 
@@ -418,7 +1156,7 @@ impl S3Client {
             let response = request
                 .send()
                 .await
-                .map_err(|e| S3DeckError::S3(format!("Failed to list objects: {}", e)))?;
+                .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list objects", e))?;
 
             for object in response.contents() {
                 if let (Some(key), Some(last_modified)) = (object.key(), object.last_modified()) {
@@ -453,7 +1191,348 @@ impl S3Client {
         Ok(latest_modified)
     }
 
-    async fn rename_folder(&self, old_prefix: &str, new_prefix: &str) -> Result<RenameResponse> {
+    /// List every object below a prefix, handing each page to `on_page` as soon as it arrives.
+    /// Folder markers and trashed objects are left out. Returns false when `cancelled` was
+    /// raised before the walk finished.
+    pub async fn walk_objects(
+        &self,
+        prefix: Option<&str>,
+        cancelled: &AtomicBool,
+        mut on_page: impl FnMut(Vec<ObjectSummary>),
+    ) -> Result<bool> {
+        let prefix = self.scoped_prefix(prefix)?;
+        let mut continuation_token = None;
+
+        loop {
+            if cancelled.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+
+            let (page, next_token) = self.list_page(prefix, continuation_token).await?;
+            on_page(page);
+
+            match next_token {
+                Some(token) => continuation_token = Some(token),
+                None => return Ok(true),
+            }
+        }
+    }
+
+    /// One page of objects below an already scoped prefix, without folder markers and trashed
+    /// objects, and the token for the next page if there is one
+    pub async fn list_page(
+        &self,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<(Vec<ObjectSummary>, Option<String>)> {
+        let trash_prefix = self.trash_prefix();
+        let response = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .prefix(prefix)
+            .set_continuation_token(continuation_token)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list objects", e))?;
+
+        let page = response
+            .contents()
+            .iter()
+            .filter_map(|object| {
+                let key = object.key()?;
+                if key.ends_with('/') || key.starts_with(&trash_prefix) {
+                    return None;
+                }
+
+                Some(ObjectSummary {
+                    key: key.to_string(),
+                    size: object.size().unwrap_or(0),
+                    last_modified: object.last_modified().map(|dt| {
+                        DateTime::from_timestamp(dt.secs(), dt.subsec_nanos()).unwrap_or_default()
+                    }),
+                    etag: object.e_tag().map(|s| s.to_string()),
+                    storage_class: object.storage_class().map(|c| c.as_str().to_string()),
+                })
+            })
+            .collect();
+
+        let next_token = if response.is_truncated().unwrap_or(false) {
+            response.next_continuation_token().map(|s| s.to_string())
+        } else {
+            None
+        };
+        Ok((page, next_token))
+    }
+
+    /// Walk every object below a prefix and hand each page's matches to `on_matches` as soon as
+    /// it is listed. Stops early, with `cancelled` set in the summary, once `cancelled` is raised.
+    pub async fn search_objects(
+        &self,
+        prefix: Option<&str>,
+        filter: &SearchFilter,
+        cancelled: &AtomicBool,
+        mut on_matches: impl FnMut(Vec<FileItem>),
+    ) -> Result<SearchSummary> {
+        let prefix = self.scoped_prefix(prefix)?;
+        let mut summary = SearchSummary::default();
+
+        let completed = self
+            .walk_objects(Some(prefix), cancelled, |page| {
+                summary.scanned += page.len() as u64;
+                let matches = filter.select(prefix, &page);
+                if !matches.is_empty() {
+                    summary.matched += matches.len() as u64;
+                    on_matches(matches);
+                }
+            })
+            .await?;
+        summary.cancelled = !completed;
+
+        Ok(summary)
+    }
+
+    /// Hex SHA-256 of an object's content, read as a stream
+    pub async fn object_checksum(&self, key: &str) -> Result<String> {
+        self.ensure_in_scope(key)?;
+
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to download object", e))?;
+
+        let mut body = response.body;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = body
+            .try_next()
+            .await
+            .map_err(|e| S3DeckError::S3(format!("Failed to read '{}': {}", key, e)))?
+        {
+            hasher.update(&chunk);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Save an object's content to a local file, read as a stream
+    pub async fn download_object(&self, key: &str, destination: &Path) -> Result<()> {
+        self.ensure_in_scope(key)?;
+
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to download object", e))?;
+
+        let mut body = response.body;
+        let mut file = fs::File::create(destination).await?;
+        while let Some(chunk) = body
+            .try_next()
+            .await
+            .map_err(|e| S3DeckError::S3(format!("Failed to read '{}': {}", key, e)))?
+        {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Get rid of a copy of `original`, as long as it still has the ETag it was scanned with.
+    /// With `placeholder` set, an empty object redirecting to `original` takes its place.
+    /// Copies the trash can't take are only deleted with the confirmation token.
+    pub async fn remove_duplicate(
+        &self,
+        key: &str,
+        etag: &str,
+        original: &str,
+        placeholder: bool,
+        confirmation: Option<&str>,
+    ) -> Result<Deletion> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(key)?;
+        if key == original {
+            return Err(S3DeckError::InvalidInput(format!(
+                "'{}' can't be a duplicate of itself",
+                key
+            )));
+        }
+
+        let current = self.get_object_metadata(key).await?;
+        if current.etag.as_deref().map(|e| e.trim_matches('"')) != Some(etag.trim_matches('"')) {
+            return Err(S3DeckError::PreconditionFailed(S3ErrorInfo {
+                message: format!("'{}' changed since it was scanned", key),
+                ..Default::default()
+            }));
+        }
+
+        // Conditional on the ETag checked above, so a copy rewritten since isn't lost
+        let mode = self.delete_mode(key).await;
+        let trashed = self
+            .delete_with_mode(
+                key,
+                mode,
+                &trash_timestamp(),
+                confirmation,
+                current.etag.as_deref(),
+            )
+            .await?;
+
+        if placeholder {
+            // Written only if nothing has taken the key since it was deleted
+            self.write_with_policy(key, OverwritePolicy::Fail, |key, if_absent| async move {
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket_name)
+                    .key(key)
+                    .body(ByteStream::from(vec![]))
+                    .website_redirect_location(format!("/{}", original))
+                    .metadata("s3deck-duplicate-of", original)
+                    .set_if_none_match(if_absent.then(|| "*".to_string()))
+                    .send()
+                    .await
+                    .map_err(|e| S3DeckError::from_sdk("Failed to write placeholder", e))?;
+                Ok(())
+            })
+            .await?;
+        }
+
+        Ok(Deletion {
+            count: 1,
+            keys: vec![key.to_string()],
+            failed: Vec::new(),
+            trashed: trashed.into_iter().collect(),
+        })
+    }
+
+    /// Copy an object within the bucket, taking the content type from the destination's extension
+    pub async fn copy_object(
+        &self,
+        source_key: &str,
+        destination_key: &str,
+        policy: OverwritePolicy,
+    ) -> Result<WriteOutcome> {
+        self.copy_object_to(self, source_key, destination_key, policy)
+            .await
+    }
+
+    /// Copy an object into `destination`, which may be another bucket. The copy happens server
+    /// side with the destination's credentials, so they must be allowed to read the source.
+    pub async fn copy_object_to(
+        &self,
+        destination: &S3Client,
+        source_key: &str,
+        destination_key: &str,
+        policy: OverwritePolicy,
+    ) -> Result<WriteOutcome> {
+        self.ensure_in_scope(source_key)?;
+        destination
+            .copy_object_from(&self.bucket_name, source_key, destination_key, policy, None)
+            .await
+    }
+
+    /// Copy into this bucket. Without a `storage_class` the copy is stored as `STANDARD`.
+    async fn copy_object_from(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        destination_key: &str,
+        policy: OverwritePolicy,
+        storage_class: Option<&str>,
+    ) -> Result<WriteOutcome> {
+        self.ensure_writable()?;
+        self.ensure_in_scope(destination_key)?;
+
+        let content_type = get_content_type_from_extension(destination_key);
+        let copy_source = copy_source(source_bucket, source_key);
+
+        self.write_with_policy(destination_key, policy, |key, if_absent| {
+            let content_type = &content_type;
+            let copy_source = &copy_source;
+            async move {
+                self.client
+                    .copy_object()
+                    .bucket(&self.bucket_name)
+                    .key(&key)
+                    .copy_source(copy_source)
+                    .content_type(content_type)
+                    .set_storage_class(storage_class.map(StorageClass::from))
+                    .set_if_none_match(if_absent.then(|| "*".to_string()))
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        S3DeckError::from_sdk(
+                            &format!("Failed to copy object from '{}' to '{}'", source_key, key),
+                            e,
+                        )
+                    })?;
+                Ok(())
+            }
+        })
+        .await
+    }
+
+    /// Copy a single object to its new key and delete the original
+    async fn move_object(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        policy: OverwritePolicy,
+    ) -> Result<WriteOutcome> {
+        let source = self.get_object_metadata(old_key).await?;
+        self.move_with_metadata(old_key, &source, new_key, policy)
+            .await
+    }
+
+    /// `move_object` for a source that was just looked up. The copy keeps the storage class,
+    /// and the original is only deleted if it is still what was copied.
+    async fn move_with_metadata(
+        &self,
+        old_key: &str,
+        source: &ObjectMetadata,
+        new_key: &str,
+        policy: OverwritePolicy,
+    ) -> Result<WriteOutcome> {
+        self.ensure_in_scope(old_key)?;
+        let outcome = self
+            .copy_object_from(
+                &self.bucket_name,
+                old_key,
+                new_key,
+                policy,
+                source.storage_class.as_deref(),
+            )
+            .await?;
+        if let WriteOutcome::Written(copy) = &outcome {
+            self.delete_if_unchanged(old_key, source.etag.as_deref())
+                .await
+                .map_err(|e| match e {
+                    S3DeckError::PreconditionFailed(info) => {
+                        S3DeckError::PreconditionFailed(S3ErrorInfo {
+                            message: format!(
+                                "'{}' changed while moving, so it was kept next to its copy '{}'",
+                                old_key, copy
+                            ),
+                            ..info
+                        })
+                    }
+                    e => e,
+                })?;
+        }
+        Ok(outcome)
+    }
+
+    async fn rename_folder(
+        &self,
+        old_prefix: &str,
+        new_prefix: &str,
+        policy: OverwritePolicy,
+    ) -> Result<RenameResponse> {
         // List all objects under the old prefix
         let objects = self.list_all_objects_with_prefix(old_prefix).await?;
 
@@ -462,6 +1541,8 @@ impl S3Client {
         }
 
         let mut moved_files = Vec::new();
+        let mut moved_objects = Vec::new();
+        let mut skipped_files = Vec::new();
         let mut failed_files = Vec::new();
 
         // Move each object
@@ -470,37 +1551,22 @@ impl S3Client {
             let relative_path = object_key.strip_prefix(old_prefix).unwrap_or(object_key);
             let new_object_key = format!("{}{}", new_prefix, relative_path);
 
-            // Get content type for the new key
-            let content_type = get_content_type_from_extension(&new_object_key);
-
-            // Copy object to new location
-            let copy_source = format!("{}/{}", self.bucket_name, object_key);
-
-            let copy_result = self
-                .client
-                .copy_object()
-                .bucket(&self.bucket_name)
-                .key(&new_object_key)
-                .copy_source(&copy_source)
-                .content_type(&content_type)
-                .send()
-                .await;
-
-            match copy_result {
-                Ok(_) => {
-                    // Delete original object
-                    match self.delete_object(object_key).await {
-                        Ok(_) => {
-                            moved_files.push(format!("{} -> {}", object_key, new_object_key));
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to delete original object {}: {}", object_key, e);
-                            failed_files.push(object_key.clone());
-                        }
-                    }
+            match self
+                .retry_object(|| self.move_object(object_key, &new_object_key, policy))
+                .await
+            {
+                Ok(WriteOutcome::Written(written_key)) => {
+                    moved_files.push(format!("{} -> {}", object_key, written_key));
+                    moved_objects.push(MovedObject {
+                        from: object_key.clone(),
+                        to: written_key,
+                    });
+                }
+                Ok(WriteOutcome::Skipped) => {
+                    skipped_files.push(object_key.clone());
                 }
                 Err(e) => {
-                    eprintln!("Failed to copy object {}: {}", object_key, e);
+                    eprintln!("Failed to move object {}: {}", object_key, e);
                     failed_files.push(object_key.clone());
                 }
             }
@@ -518,11 +1584,19 @@ impl S3Client {
             .last()
             .unwrap_or(new_prefix);
 
-        let message = if failed_files.is_empty() {
+        let message = if failed_files.is_empty() && skipped_files.is_empty() {
             format!(
                 "Folder renamed from '{}' to '{}'. Moved {} files.",
                 old_folder_name, new_folder_name, total_moved
             )
+        } else if failed_files.is_empty() {
+            format!(
+                "Folder partially renamed from '{}' to '{}'. Moved {} files, {} skipped because they already exist.",
+                old_folder_name,
+                new_folder_name,
+                total_moved,
+                skipped_files.len()
+            )
         } else {
             format!(
                 "Folder partially renamed from '{}' to '{}'. Moved {} files, {} failed.",
@@ -539,6 +1613,1066 @@ impl S3Client {
             new_key: new_prefix.to_string(),
             moved_files: Some(moved_files),
             total_moved: Some(total_moved),
+            skipped_files: Some(skipped_files),
+            moved_objects,
         })
     }
+
+    fn trash_prefix(&self) -> String {
+        format!("{}{}", self.root_prefix, TRASH_FOLDER)
+    }
+
+    fn trash_key(&self, key: &str, timestamp: &str) -> String {
+        let relative_key = key.strip_prefix(&self.root_prefix).unwrap_or(key);
+        format!("{}{}/{}", self.trash_prefix(), timestamp, relative_key)
+    }
+
+    async fn versioning_enabled(&self) -> bool {
+        *self
+            .versioning_enabled
+            .get_or_init(|| async {
+                // Without permission to read the versioning status, use the trash folder
+                self.client
+                    .get_bucket_versioning()
+                    .bucket(&self.bucket_name)
+                    .send()
+                    .await
+                    .is_ok_and(|output| output.status() == Some(&BucketVersioningStatus::Enabled))
+            })
+            .await
+    }
+
+    async fn delete_mode(&self, key: &str) -> DeleteMode {
+        // Deleting from the trash itself is how items are purged early
+        if !self.trash_enabled || key.starts_with(&self.trash_prefix()) {
+            DeleteMode::Permanent
+        } else if self.versioning_enabled().await {
+            DeleteMode::DeleteMarker
+        } else {
+            DeleteMode::TrashFolder
+        }
+    }
+
+    /// Deleted objects that can still be restored, most recently deleted first
+    pub async fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let trash_prefix = self.trash_prefix();
+        let mut items = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let response = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket_name)
+                .prefix(&trash_prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list trash", e))?;
+
+            for object in response.contents() {
+                let Some(trash_key) = object.key() else {
+                    continue;
+                };
+                let Some((timestamp, relative_key)) = trash_key
+                    .strip_prefix(&trash_prefix)
+                    .and_then(|rest| rest.split_once('/'))
+                else {
+                    continue;
+                };
+
+                items.push(TrashItem {
+                    key: format!("{}{}", self.root_prefix, relative_key),
+                    trash_key: Some(trash_key.to_string()),
+                    version_id: None,
+                    deleted_at: NaiveDateTime::parse_from_str(timestamp, TRASH_TIMESTAMP_FORMAT)
+                        .ok()
+                        .map(|dt| dt.and_utc()),
+                    size: object.size().unwrap_or(0),
+                });
+            }
+
+            if response.is_truncated().unwrap_or(false) {
+                continuation_token = response.next_continuation_token().map(|s| s.to_string());
+            } else {
+                break;
+            }
+        }
+
+        if self.trash_enabled && self.versioning_enabled().await {
+            // Only keys hidden by a delete marker are kept while the versions stream past.
+            // A key's entries are listed newest first, so its current delete marker comes
+            // before the version it hides, and that version's size is the item's size.
+            let mut deleted: HashMap<String, TrashItem> = HashMap::new();
+            let mut sized = HashSet::new();
+            self.walk_versions(&self.root_prefix, |versions, delete_markers| {
+                for marker in delete_markers
+                    .iter()
+                    .filter(|m| m.is_latest() == Some(true))
+                {
+                    let Some(key) = marker.key() else {
+                        continue;
+                    };
+                    deleted.insert(
+                        key.to_string(),
+                        TrashItem {
+                            key: key.to_string(),
+                            trash_key: None,
+                            version_id: marker.version_id().map(|s| s.to_string()),
+                            deleted_at: marker.last_modified().map(|dt| {
+                                DateTime::from_timestamp(dt.secs(), dt.subsec_nanos())
+                                    .unwrap_or_default()
+                            }),
+                            size: 0,
+                        },
+                    );
+                }
+                for version in versions {
+                    let Some(item) = version.key().and_then(|key| deleted.get_mut(key)) else {
+                        continue;
+                    };
+                    if sized.insert(item.key.clone()) {
+                        item.size = version.size().unwrap_or(0);
+                    }
+                }
+            })
+            .await?;
+            items.extend(deleted.into_values());
+        }
+
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+        Ok(items)
+    }
+
+    /// Put a trashed object back under its original key
+    pub async fn restore_trash_item(
+        &self,
+        item: &TrashItem,
+        policy: OverwritePolicy,
+    ) -> Result<WriteOutcome> {
+        self.ensure_writable()?;
+
+        if let Some(trash_key) = &item.trash_key {
+            // Derive the destination from the trash key rather than trusting the item's key
+            let relative_key = trash_key
+                .strip_prefix(&self.trash_prefix())
+                .and_then(|rest| rest.split_once('/'))
+                .map(|(_, relative_key)| relative_key)
+                .ok_or_else(|| {
+                    S3DeckError::InvalidPath(format!("'{}' is not in the trash", trash_key))
+                })?;
+            let key = format!("{}{}", self.root_prefix, relative_key);
+            return self.move_object(trash_key, &key, policy).await;
+        }
+
+        let version_id = item.version_id.as_deref().ok_or_else(|| {
+            S3DeckError::InvalidPath(format!("'{}' has no trash location", item.key))
+        })?;
+        self.ensure_in_scope(&item.key)?;
+
+        // Deleting any other version would destroy data, so make sure this one is the
+        // delete marker hiding the object
+        let (_, delete_markers) = self.key_versions(&item.key).await?;
+        let is_current_marker = delete_markers.iter().any(|m| {
+            m.key() == Some(item.key.as_str())
+                && m.version_id() == Some(version_id)
+                && m.is_latest() == Some(true)
+        });
+        if !is_current_marker {
+            return Err(S3DeckError::InvalidPath(format!(
+                "'{}' is no longer in the trash",
+                item.key
+            )));
+        }
+
+        self.client
+            .delete_object()
+            .bucket(&self.bucket_name)
+            .key(&item.key)
+            .version_id(version_id)
+            .send()
+            .await
+            .map_err(|e| S3DeckError::from_sdk("Failed to restore object", e))?;
+        Ok(WriteOutcome::Written(item.key.clone()))
+    }
+
+    /// Permanently delete trashed objects deleted more than `older_than_days` days ago,
+    /// returning how many were removed
+    pub async fn purge_trash(
+        &self,
+        older_than_days: u32,
+        confirmation: Option<&str>,
+    ) -> Result<i32> {
+        self.ensure_writable()?;
+
+        let cutoff = Utc::now() - chrono::Duration::days(older_than_days.into());
+        let expired: Vec<TrashItem> = self
+            .list_trash()
+            .await?
+            .into_iter()
+            // Items without a readable deletion date can't be shown to be old enough
+            .filter(|item| {
+                item.deleted_at
+                    .is_some_and(|deleted_at| deleted_at < cutoff)
+            })
+            .collect();
+        // Purging destroys objects for good, so protected keys need the confirmation up front
+        for item in &expired {
+            self.ensure_confirmed(&item.key, confirmation)?;
+        }
+
+        let mut purged = 0;
+        for item in expired {
+            let result = match &item.trash_key {
+                Some(trash_key) => self.retry_object(|| self.remove_object(trash_key)).await,
+                None => self.purge_versions(&item).await,
+            };
+            match result {
+                Ok(()) => purged += 1,
+                Err(e) => eprintln!("Failed to purge {} from trash: {}", item.key, e),
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Delete every version and delete marker of a trashed key, as long as the delete marker
+    /// it was listed with still hides it. A key written again since then is left alone.
+    async fn purge_versions(&self, item: &TrashItem) -> Result<()> {
+        let key = item.key.as_str();
+        let (versions, delete_markers) = self.key_versions(key).await?;
+        let still_deleted = delete_markers
+            .iter()
+            .any(|m| m.is_latest() == Some(true) && m.version_id() == item.version_id.as_deref());
+        if !still_deleted {
+            return Err(S3DeckError::PreconditionFailed(S3ErrorInfo {
+                message: format!("'{}' was written again since it was listed", key),
+                ..Default::default()
+            }));
+        }
+
+        let version_ids = versions
+            .iter()
+            .filter_map(|v| v.version_id())
+            .chain(delete_markers.iter().filter_map(|m| m.version_id()));
+
+        for version_id in version_ids {
+            self.retry_object(|| async {
+                self.client
+                    .delete_object()
+                    .bucket(&self.bucket_name)
+                    .key(key)
+                    .version_id(version_id)
+                    .send()
+                    .await
+                    .map_err(|e| S3DeckError::from_sdk("Failed to purge object version", e))?;
+                Ok(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// The versions and delete markers of exactly this key, newest first
+    async fn key_versions(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<ObjectVersion>, Vec<DeleteMarkerEntry>)> {
+        let mut versions = Vec::new();
+        let mut delete_markers = Vec::new();
+        self.walk_versions(key, |page_versions, page_markers| {
+            versions.extend(
+                page_versions
+                    .iter()
+                    .filter(|v| v.key() == Some(key))
+                    .cloned(),
+            );
+            delete_markers.extend(
+                page_markers
+                    .iter()
+                    .filter(|m| m.key() == Some(key))
+                    .cloned(),
+            );
+        })
+        .await?;
+        Ok((versions, delete_markers))
+    }
+
+    /// Hand each page of versions and delete markers below a prefix to `on_page`
+    async fn walk_versions(
+        &self,
+        prefix: &str,
+        mut on_page: impl FnMut(&[ObjectVersion], &[DeleteMarkerEntry]),
+    ) -> Result<()> {
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+
+        loop {
+            let response = self
+                .client
+                .list_object_versions()
+                .bucket(&self.bucket_name)
+                .prefix(prefix)
+                .set_key_marker(key_marker)
+                .set_version_id_marker(version_id_marker)
+                .send()
+                .await
+                .map_err(|e| S3DeckError::from_bucket_sdk("Failed to list object versions", e))?;
+
+            on_page(response.versions(), response.delete_markers());
+
+            if response.is_truncated().unwrap_or(false) {
+                key_marker = response.next_key_marker().map(|s| s.to_string());
+                version_id_marker = response.next_version_id_marker().map(|s| s.to_string());
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Detect the region of a bucket that may not be saved yet, probing from us-east-1 when the
+/// config has no region
+pub async fn detect_region_for(
+    bucket_config: &BucketConfig,
+    network: &NetworkSettings,
+) -> Result<Option<String>> {
+    let mut probe_config = bucket_config.clone();
+    if probe_config.region.trim().is_empty() {
+        probe_config.region = "us-east-1".to_string();
+    }
+
+    S3Client::new(&probe_config, network)
+        .await?
+        .detect_region()
+        .await
+}
+
+/// Normalize a configured root prefix to `teams/alice/` form, or empty for the whole bucket
+pub fn normalize_root_prefix(root_prefix: Option<&str>) -> Result<String> {
+    let trimmed = root_prefix.unwrap_or_default().trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    if has_dot_segments(trimmed) {
+        return Err(S3DeckError::Config(format!(
+            "Root prefix '{}' must not contain '.' or '..' segments",
+            trimmed
+        )));
+    }
+    Ok(format!("{}/", trimmed))
+}
+
+fn has_dot_segments(key: &str) -> bool {
+    key.split('/')
+        .any(|segment| segment == "." || segment == "..")
+}
+
+/// The editable headers and user metadata from a HEAD response
+fn object_headers(response: &HeadObjectOutput) -> ObjectHeaders {
+    ObjectHeaders {
+        content_type: response.content_type().map(|s| s.to_string()),
+        cache_control: response.cache_control().map(|s| s.to_string()),
+        content_disposition: response.content_disposition().map(|s| s.to_string()),
+        content_encoding: response.content_encoding().map(|s| s.to_string()),
+        content_language: response.content_language().map(|s| s.to_string()),
+        metadata: response.metadata().cloned().unwrap_or_default(),
+    }
+}
+
+/// Why CopyObject, and so the trash, can't take an object: archived objects can't be read
+/// until restored, and anything over 5 GiB needs a multipart copy
+fn untrashable_reason(storage_class: Option<&str>, size: i64) -> Option<String> {
+    match storage_class {
+        Some(class) if restore::is_archive_class(class) => {
+            Some(format!("it is archived in {}", class))
+        }
+        _ if size > MAX_COPY_SIZE => Some("it is larger than 5 GiB".to_string()),
+        _ => None,
+    }
+}
+
+/// Name of the trash subfolder for a delete happening now; sorts chronologically
+fn trash_timestamp() -> String {
+    Utc::now().format(TRASH_TIMESTAMP_FORMAT).to_string()
+}
+
+/// `dir/report.pdf` becomes `dir/report (n).pdf` and `dir/photos/` becomes `dir/photos (n)/`
+fn numbered_key(key: &str, n: u32) -> String {
+    let (body, trailing_slash) = match key.strip_suffix('/') {
+        Some(body) => (body, "/"),
+        None => (key, ""),
+    };
+    let (dir, name) = body.split_at(body.rfind('/').map(|i| i + 1).unwrap_or(0));
+    let (stem, extension) = match name.rfind('.') {
+        Some(i) if i > 0 && trailing_slash.is_empty() => name.split_at(i),
+        _ => (name, ""),
+    };
+
+    format!("{}{} ({}){}{}", dir, stem, n, extension, trailing_slash)
+}
+
+/// The error a failed `If-None-Match: *` would produce, for the check-before-write fallback
+fn already_exists(key: &str) -> S3DeckError {
+    S3DeckError::PreconditionFailed(S3ErrorInfo {
+        message: format!("'{}' already exists", key),
+        ..Default::default()
+    })
+}
+
+/// Endpoints that don't support a conditional header answer with 501 Not Implemented
+fn is_not_implemented(error: &S3DeckError) -> bool {
+    error.info().is_some_and(|info| {
+        info.code.as_deref() == Some("NotImplemented") || info.http_status == Some(501)
+    })
+}
+
+/// Build the `x-amz-copy-source` value for a key. S3 percent-decodes this header and treats
+/// `?` as the start of a version ID, so every path segment is encoded while `/` is kept.
+fn copy_source(bucket: &str, key: &str) -> String {
+    let encoded_key = key
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    format!("{}/{}", bucket, encoded_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{direct_network, FakeS3};
+    use std::collections::BTreeSet;
+
+    /// Keys that break when copy sources or request paths are not encoded correctly
+    const NASTY_KEYS: &[&str] = &[
+        "plain.txt",
+        "with space.txt",
+        "plus+sign.txt",
+        "percent%20literal.txt",
+        "hash#tag.txt",
+        "question?versionId=1.txt",
+        "ampersand&equals=.txt",
+        "zażółć gęślą jaźń.txt",
+        "日本語/ファイル.txt",
+        "emoji 🎉.txt",
+        "dir with space/nested+file%.txt",
+    ];
+
+    #[test]
+    fn test_copy_source_encodes_segments_but_keeps_slashes() {
+        assert_eq!(
+            copy_source("bucket", "a b/c+d%.txt"),
+            "bucket/a%20b/c%2Bd%25.txt"
+        );
+        assert_eq!(copy_source("bucket", "q?#.txt"), "bucket/q%3F%23.txt");
+        assert_eq!(copy_source("bucket", "ż.txt"), "bucket/%C5%BC.txt");
+    }
+
+    #[tokio::test]
+    async fn test_nasty_keys_round_trip_through_rename_copy_and_delete() {
+        let fake = FakeS3::start().await;
+        let client = fake.client().await;
+
+        for key in NASTY_KEYS {
+            fake.put(key, key.as_bytes());
+
+            let renamed = format!("renamed/{}", key);
+            client
+                .rename_object(key, &renamed, false, OverwritePolicy::Fail)
+                .await
+                .unwrap();
+            assert!(fake.get(key).is_none(), "{} was not removed", key);
+            assert_eq!(fake.get(&renamed).unwrap().body, key.as_bytes());
+
+            let copied = format!("copied/{}", key);
+            client
+                .copy_object(&renamed, &copied, OverwritePolicy::Fail)
+                .await
+                .unwrap();
+            assert_eq!(fake.get(&copied).unwrap().body, key.as_bytes());
+
+            client.delete_object(&renamed, None).await.unwrap();
+            client.delete_object(&copied, None).await.unwrap();
+            assert!(fake.get(&renamed).is_none() && fake.get(&copied).is_none());
+        }
+
+        assert!(fake.keys().is_empty(), "leftover keys: {:?}", fake.keys());
+    }
+
+    #[tokio::test]
+    async fn test_folder_rename_moves_nasty_keys() {
+        let fake = FakeS3::start().await;
+        let client = fake.client().await;
+
+        for key in NASTY_KEYS {
+            fake.put(&format!("src #1+/{}", key), key.as_bytes());
+        }
+
+        let response = client
+            .rename_object("src #1+/", "dst ü%/", true, OverwritePolicy::Fail)
+            .await
+            .unwrap();
+
+        assert_eq!(response.total_moved, Some(NASTY_KEYS.len() as i32));
+        for key in NASTY_KEYS {
+            assert_eq!(
+                fake.get(&format!("dst ü%/{}", key)).unwrap().body,
+                key.as_bytes()
+            );
+        }
+        assert!(fake.keys().iter().all(|k| k.starts_with("dst ü%/")));
+        // Originals are only deleted if they still match what was copied
+        assert!(fake.last_header("if-match").is_some());
+    }
+
+    #[test]
+    fn test_numbered_key_keeps_extension_and_folder_slash() {
+        assert_eq!(numbered_key("docs/report.pdf", 1), "docs/report (1).pdf");
+        assert_eq!(
+            numbered_key("docs/archive.tar.gz", 2),
+            "docs/archive.tar (2).gz"
+        );
+        assert_eq!(numbered_key(".env", 1), ".env (1)");
+        assert_eq!(numbered_key("photos/2024.v2/", 3), "photos/2024.v2 (3)/");
+    }
+
+    #[tokio::test]
+    async fn test_copy_honors_overwrite_policy() {
+        let fake = FakeS3::start().await;
+        let client = fake.client().await;
+        fake.put("a.txt", b"new");
+        fake.put("b.txt", b"old");
+
+        let result = client
+            .copy_object("a.txt", "b.txt", OverwritePolicy::Fail)
+            .await;
+        assert!(matches!(result, Err(S3DeckError::AlreadyExists(_))));
+        assert_eq!(fake.get("b.txt").unwrap().body, b"old");
+
+        let outcome = client
+            .copy_object("a.txt", "b.txt", OverwritePolicy::Skip)
+            .await
+            .unwrap();
+        assert_eq!(outcome, WriteOutcome::Skipped);
+        assert_eq!(fake.get("b.txt").unwrap().body, b"old");
+
+        for expected in ["b (1).txt", "b (2).txt"] {
+            let outcome = client
+                .copy_object("a.txt", "b.txt", OverwritePolicy::KeepBoth)
+                .await
+                .unwrap();
+            assert_eq!(outcome, WriteOutcome::Written(expected.to_string()));
+        }
+
+        client
+            .copy_object("a.txt", "b.txt", OverwritePolicy::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(fake.get("b.txt").unwrap().body, b"new");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_policy_falls_back_without_conditional_writes() {
+        let fake = FakeS3::start().await;
+        fake.disable_conditional_writes();
+        let client = fake.client().await;
+        fake.put("a.txt", b"new");
+        fake.put("b.txt", b"old");
+
+        let result = client
+            .copy_object("a.txt", "b.txt", OverwritePolicy::Fail)
+            .await;
+        assert!(matches!(result, Err(S3DeckError::AlreadyExists(_))));
+
+        let outcome = client
+            .copy_object("a.txt", "c.txt", OverwritePolicy::Fail)
+            .await
+            .unwrap();
+        assert_eq!(outcome, WriteOutcome::Written("c.txt".to_string()));
+
+        // Rename deletes the source with If-Match, which must fall back as well
+        client
+            .rename_object("c.txt", "d.txt", false, OverwritePolicy::Fail)
+            .await
+            .unwrap();
+        assert_eq!(fake.keys(), vec!["a.txt", "b.txt", "d.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_rename_keeps_storage_class() {
+        let fake = FakeS3::start().await;
+        let client = fake.client().await;
+        fake.put("reports/q1.csv", b"q1");
+        fake.set_storage_class("reports/q1.csv", "STANDARD_IA");
+
+        client
+            .rename_object(
+                "reports/q1.csv",
+                "reports/2024-q1.csv",
+                false,
+                OverwritePolicy::Fail,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(fake.keys(), vec!["reports/2024-q1.csv"]);
+        let renamed = fake.get("reports/2024-q1.csv").unwrap();
+        assert_eq!(renamed.storage_class(), "STANDARD_IA");
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_create_folder_honor_overwrite_policy() {
+        let fake = FakeS3::start().await;
+        let client = fake.client().await;
+        fake.put("photos/cat.jpg", b"cat");
+
+        let path = std::env::temp_dir().join(format!("s3deck-upload-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"dog").unwrap();
+
+        let (outcome, size) = client
+            .upload_file("photos/cat.jpg", &path, OverwritePolicy::KeepBoth)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            outcome,
+            WriteOutcome::Written("photos/cat (1).jpg".to_string())
+        );
+        assert_eq!(size, 3);
+        assert_eq!(fake.get("photos/cat.jpg").unwrap().body, b"cat");
+
+        // The folder exists implicitly through its objects, without a marker
+        let result = client.create_folder("photos", OverwritePolicy::Fail).await;
+        assert!(matches!(result, Err(S3DeckError::AlreadyExists(_))));
+
+        client
+            .create_folder("photos", OverwritePolicy::KeepBoth)
+            .await
+            .unwrap();
+        assert!(fake.get("photos (1)/").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_buckets_are_unsigned_and_read_only() {
+        let fake = FakeS3::start().await;
+        fake.put("public/data.csv", b"a,b");
+
+        let bucket_config = BucketConfig {
+            access_key: String::new(),
+            secret_key: String::new(),
+            anonymous: true,
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&bucket_config, &direct_network())
+            .await
+            .unwrap();
+
+        let (items, _) = client.list_objects(Some("public/")).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(fake.last_header("authorization"), None);
+
+        let result = client.delete_object("public/data.csv", None).await;
+        assert!(matches!(result, Err(S3DeckError::ReadOnly(_))));
+        let result = client.create_folder("new", OverwritePolicy::Fail).await;
+        assert!(matches!(result, Err(S3DeckError::ReadOnly(_))));
+        assert_eq!(fake.keys(), vec!["public/data.csv"]);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_region_is_reported_and_detected() {
+        let fake = FakeS3::start().await;
+        fake.set_region("eu-west-1");
+        fake.put("a.txt", b"a");
+
+        let client = fake.client().await;
+        match client.list_objects(None).await {
+            Err(S3DeckError::WrongRegion(info)) => {
+                assert_eq!(info.bucket_region.as_deref(), Some("eu-west-1"));
+            }
+            other => panic!(
+                "expected WrongRegion, got {:?}",
+                other.map(|(items, _)| items.len())
+            ),
+        }
+
+        assert_eq!(
+            client.detect_region().await.unwrap().as_deref(),
+            Some("eu-west-1")
+        );
+
+        let unsaved = BucketConfig {
+            region: String::new(),
+            ..fake.bucket_config()
+        };
+        let detected = detect_region_for(&unsaved, &direct_network())
+            .await
+            .unwrap();
+        assert_eq!(detected.as_deref(), Some("eu-west-1"));
+
+        let corrected = BucketConfig {
+            region: "eu-west-1".to_string(),
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&corrected, &direct_network()).await.unwrap();
+        assert_eq!(client.list_objects(None).await.unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_root_prefix() {
+        assert_eq!(normalize_root_prefix(None).unwrap(), "");
+        assert_eq!(normalize_root_prefix(Some(" / ")).unwrap(), "");
+        assert_eq!(
+            normalize_root_prefix(Some("/teams/alice")).unwrap(),
+            "teams/alice/"
+        );
+        assert!(matches!(
+            normalize_root_prefix(Some("teams/../admin")),
+            Err(S3DeckError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_root_prefix_scopes_listing_and_rejects_escapes() {
+        let fake = FakeS3::start().await;
+        fake.put("teams/alice/notes.txt", b"a");
+        fake.put("teams/alice/docs/plan.md", b"p");
+        fake.put("teams/bob/secret.txt", b"s");
+
+        let config = BucketConfig {
+            root_prefix: Some("teams/alice".to_string()),
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+
+        let mut keys: Vec<String> = client
+            .list_objects(None)
+            .await
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|item| item.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["teams/alice/docs/", "teams/alice/notes.txt"]);
+
+        for key in [
+            "teams/bob/secret.txt",
+            "teams/alice/../bob/secret.txt",
+            "teams/alicex/file.txt",
+        ] {
+            assert!(matches!(
+                client.get_object_metadata(key).await,
+                Err(S3DeckError::InvalidPath(_))
+            ));
+            assert!(matches!(
+                client.delete_object(key, None).await,
+                Err(S3DeckError::InvalidPath(_))
+            ));
+        }
+        assert!(matches!(
+            client.list_objects(Some("teams/")).await,
+            Err(S3DeckError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            client
+                .copy_object(
+                    "teams/alice/notes.txt",
+                    "teams/bob/notes.txt",
+                    OverwritePolicy::Fail
+                )
+                .await,
+            Err(S3DeckError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            client
+                .rename_object(
+                    "teams/alice/docs/",
+                    "shared/docs/",
+                    true,
+                    OverwritePolicy::Fail
+                )
+                .await,
+            Err(S3DeckError::InvalidPath(_))
+        ));
+
+        client
+            .rename_object(
+                "teams/alice/notes.txt",
+                "teams/alice/docs/notes.txt",
+                false,
+                OverwritePolicy::Fail,
+            )
+            .await
+            .unwrap();
+        assert!(fake.get("teams/alice/docs/notes.txt").is_some());
+        assert!(fake.get("teams/bob/secret.txt").is_some());
+
+        // Folders created at the root arrive without the prefix
+        assert_eq!(client.scoped_key(""), "teams/alice/");
+        assert_eq!(client.scoped_key("teams/alice"), "teams/alice/");
+        assert_eq!(client.scoped_key("teams/alice/docs/"), "teams/alice/docs/");
+        assert_eq!(client.scoped_key("teams/bob/"), "teams/alice/teams/bob/");
+        client
+            .create_folder("drafts", OverwritePolicy::Fail)
+            .await
+            .unwrap();
+        assert!(fake.get("teams/alice/drafts/").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_read_only_and_protected_prefixes() {
+        let fake = FakeS3::start().await;
+        fake.put("prod/app.json", b"{}");
+
+        let read_only = BucketConfig {
+            read_only: true,
+            protected_prefixes: vec!["prod/**".to_string()],
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&read_only, &direct_network()).await.unwrap();
+        assert!(matches!(
+            client.ensure_confirmed("prod/app.json", Some(&read_only.name)),
+            Err(S3DeckError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            client.delete_object("prod/app.json", None).await,
+            Err(S3DeckError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            client.create_folder("sandbox", OverwritePolicy::Fail).await,
+            Err(S3DeckError::ReadOnly(_))
+        ));
+
+        let protected = BucketConfig {
+            read_only: false,
+            ..read_only
+        };
+        let client = S3Client::new(&protected, &direct_network()).await.unwrap();
+        assert!(matches!(
+            client.ensure_confirmed("prod/app.json", None),
+            Err(S3DeckError::ConfirmationRequired(_))
+        ));
+        assert!(client.ensure_confirmed("sandbox/a.txt", None).is_ok());
+        client
+            .ensure_confirmed("prod/app.json", Some(&protected.name))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trash_folder_delete_restore_and_purge() {
+        let fake = FakeS3::start().await;
+        fake.put("teams/alice/report.pdf", b"report");
+        fake.put("teams/alice/photos/a.jpg", b"a");
+        fake.put("teams/alice/photos/b.jpg", b"b");
+
+        let config = BucketConfig {
+            root_prefix: Some("teams/alice/".to_string()),
+            trash_enabled: true,
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+
+        let deletion = client
+            .delete_object("teams/alice/report.pdf", None)
+            .await
+            .unwrap();
+        assert_eq!(deletion.trashed.len(), 1);
+        let deletion = client
+            .delete_folder("teams/alice/photos/", None)
+            .await
+            .unwrap();
+        assert_eq!(deletion.count, 2);
+        assert_eq!(deletion.trashed.len(), 2);
+        assert!(fake.get("teams/alice/report.pdf").is_none());
+
+        // The trash folder lives under the root prefix but is never listed
+        let (listed, _) = client.list_objects(None).await.unwrap();
+        assert!(listed.is_empty(), "{:?}", listed);
+
+        let trash = client.list_trash().await.unwrap();
+        let mut keys: Vec<&str> = trash.iter().map(|item| item.key.as_str()).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "teams/alice/photos/a.jpg",
+                "teams/alice/photos/b.jpg",
+                "teams/alice/report.pdf"
+            ]
+        );
+        assert!(trash.iter().all(|item| item.deleted_at.is_some()));
+        // A folder delete shares one timestamped trash subfolder
+        let photo_timestamps: BTreeSet<&str> = trash
+            .iter()
+            .filter(|item| item.key.contains("photos/"))
+            .map(|item| {
+                item.trash_key
+                    .as_deref()
+                    .unwrap()
+                    .split('/')
+                    .nth(3)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(photo_timestamps.len(), 1);
+
+        // The destination comes from the trash key, not from the item's claimed key
+        let report = trash
+            .iter()
+            .find(|item| item.key.ends_with("report.pdf"))
+            .unwrap();
+        let tampered = TrashItem {
+            key: "teams/bob/report.pdf".to_string(),
+            ..report.clone()
+        };
+        assert_eq!(
+            client
+                .restore_trash_item(&tampered, OverwritePolicy::Fail)
+                .await
+                .unwrap(),
+            WriteOutcome::Written("teams/alice/report.pdf".to_string())
+        );
+        assert_eq!(fake.get("teams/alice/report.pdf").unwrap().body, b"report");
+
+        // Deleting inside the trash is permanent
+        let trashed_photo = trash
+            .iter()
+            .find(|item| item.key.ends_with("a.jpg"))
+            .and_then(|item| item.trash_key.clone())
+            .unwrap();
+        let deletion = client.delete_object(&trashed_photo, None).await.unwrap();
+        assert!(deletion.trashed.is_empty());
+
+        // Without a readable deletion date an item is never old enough to purge
+        let undated = "teams/alice/.s3deck-trash/not-a-date/notes.txt";
+        fake.put(undated, b"notes");
+
+        assert_eq!(client.purge_trash(1, None).await.unwrap(), 0);
+        assert_eq!(client.purge_trash(0, None).await.unwrap(), 1);
+        let trash = client.list_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].deleted_at, None);
+        assert_eq!(fake.keys(), vec![undated, "teams/alice/report.pdf"]);
+    }
+
+    #[tokio::test]
+    async fn test_trash_keeps_storage_class_and_confirms_permanent_deletes() {
+        let fake = FakeS3::start().await;
+        fake.put("logs/app.log", b"log");
+        fake.put("logs/2019.tar", b"old logs");
+        fake.put("cold.bin", b"cold");
+        fake.set_storage_class("logs/app.log", "STANDARD_IA");
+        fake.set_storage_class("logs/2019.tar", "DEEP_ARCHIVE");
+        fake.set_storage_class("cold.bin", "GLACIER");
+
+        let config = BucketConfig {
+            trash_enabled: true,
+            protected_prefixes: vec!["logs/".to_string()],
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+
+        // Archived objects can't be copied into the trash, so nothing is deleted unconfirmed
+        let refused = client.delete_folder("logs/", None).await;
+        assert!(matches!(refused, Err(S3DeckError::ConfirmationRequired(_))));
+        let refused = client.delete_object("cold.bin", None).await;
+        assert!(matches!(refused, Err(S3DeckError::ConfirmationRequired(_))));
+        assert_eq!(fake.keys().len(), 3);
+
+        let deletion = client
+            .delete_folder("logs/", Some(&config.name))
+            .await
+            .unwrap();
+        assert_eq!(deletion.count, 2);
+        assert_eq!(deletion.trashed.len(), 1);
+        let deletion = client
+            .delete_object("cold.bin", Some(&config.name))
+            .await
+            .unwrap();
+        assert!(deletion.trashed.is_empty());
+
+        // Objects keep their storage class in the trash and when restored from it
+        let trashed = &client.list_trash().await.unwrap()[0];
+        let trash_key = trashed.trash_key.as_deref().unwrap();
+        assert_eq!(fake.get(trash_key).unwrap().storage_class(), "STANDARD_IA");
+        client
+            .restore_trash_item(trashed, OverwritePolicy::Fail)
+            .await
+            .unwrap();
+        assert_eq!(fake.keys(), vec!["logs/app.log"]);
+        assert_eq!(
+            fake.get("logs/app.log").unwrap().storage_class(),
+            "STANDARD_IA"
+        );
+
+        // Purging a protected key from the trash needs the confirmation too
+        client.delete_object("logs/app.log", None).await.unwrap();
+        let refused = client.purge_trash(0, None).await;
+        assert!(matches!(refused, Err(S3DeckError::ConfirmationRequired(_))));
+        assert_eq!(client.list_trash().await.unwrap().len(), 1);
+        let purged = client.purge_trash(0, Some(&config.name)).await.unwrap();
+        assert_eq!(purged, 1);
+        assert!(fake.keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_objects_recursively() {
+        let fake = FakeS3::start().await;
+        fake.put("teams/alice/2024/03/orders.csv", b"orders");
+        fake.put("teams/alice/2024/03/notes.txt", b"notes");
+        fake.put("teams/alice/2023/archive/orders.csv", b"old orders");
+        fake.put(
+            "teams/alice/.s3deck-trash/20240301T000000000Z/deleted.csv",
+            b"gone",
+        );
+        fake.put("teams/bob/orders.csv", b"bob");
+
+        let config = BucketConfig {
+            root_prefix: Some("teams/alice/".to_string()),
+            ..fake.bucket_config()
+        };
+        let client = S3Client::new(&config, &direct_network()).await.unwrap();
+        let filter = SearchFilter::new(&crate::models::SearchRequest {
+            query: Some("*.csv".to_string()),
+            match_mode: crate::models::SearchMatchMode::Glob,
+            min_size: Some(7),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut found = Vec::new();
+        let summary = client
+            .search_objects(None, &filter, &AtomicBool::new(false), |items| {
+                found.extend(items.into_iter().map(|item| item.key))
+            })
+            .await
+            .unwrap();
+        assert_eq!(found, vec!["teams/alice/2023/archive/orders.csv"]);
+        assert_eq!(
+            summary,
+            SearchSummary {
+                scanned: 3,
+                matched: 1,
+                ..Default::default()
+            }
+        );
+
+        let summary = client
+            .search_objects(None, &filter, &AtomicBool::new(true), |_| {
+                panic!("a cancelled search lists nothing")
+            })
+            .await
+            .unwrap();
+        assert!(summary.cancelled);
+
+        let outside = client
+            .search_objects(Some("teams/bob/"), &filter, &AtomicBool::new(false), |_| {})
+            .await;
+        assert!(matches!(outside, Err(S3DeckError::InvalidPath(_))));
+    }
 }
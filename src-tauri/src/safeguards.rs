@@ -0,0 +1,198 @@
+use crate::models::{ConfirmationInfo, Result, S3DeckError};
+use glob::{MatchOptions, Pattern};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Protected prefix globs of a bucket. Plain entries such as `backups/` protect every key
+/// starting with them; entries with `*`, `?` or `[` are globs, e.g. `prod/**` or `*/secrets/*`.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedPrefixes {
+    entries: Vec<ProtectedPrefix>,
+}
+
+#[derive(Debug, Clone)]
+struct ProtectedPrefix {
+    source: String,
+    /// Compiled glob, or `None` for a plain prefix
+    pattern: Option<Pattern>,
+}
+
+impl ProtectedPrefixes {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let entries = patterns
+            .iter()
+            .map(|source| source.trim().trim_start_matches('/'))
+            .filter(|source| !source.is_empty())
+            .map(|source| {
+                let pattern = if source.contains(['*', '?', '[']) {
+                    Some(Pattern::new(source).map_err(|e| {
+                        S3DeckError::Config(format!("Invalid protected prefix '{}': {}", source, e))
+                    })?)
+                } else {
+                    None
+                };
+
+                Ok(ProtectedPrefix {
+                    source: source.to_string(),
+                    pattern,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// The entry protecting a key, if any. A folder key (ending in `/`) is protected when any
+    /// key below it might be, since operations on it reach everything underneath.
+    pub fn matching(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.protects(key))
+            .map(|entry| entry.source.as_str())
+    }
+}
+
+impl ProtectedPrefix {
+    fn protects(&self, key: &str) -> bool {
+        let is_folder = key.is_empty() || key.ends_with('/');
+
+        match &self.pattern {
+            None => key.starts_with(&self.source) || (is_folder && self.source.starts_with(key)),
+            Some(pattern) => {
+                pattern.matches_with(key, MATCH_OPTIONS)
+                    || pattern.matches_with(key.trim_end_matches('/'), MATCH_OPTIONS)
+                    || (is_folder && self.may_match_below(key))
+            }
+        }
+    }
+
+    /// Whether the glob could match a key below a folder, comparing it one path segment
+    /// at a time since the folder's contents aren't known without listing them
+    fn may_match_below(&self, folder: &str) -> bool {
+        let mut pattern_segments = self.source.split('/');
+        for segment in folder.split('/').filter(|s| !s.is_empty()) {
+            match pattern_segments.next() {
+                Some("**") => return true,
+                Some(pattern) if Pattern::new(pattern).is_ok_and(|p| p.matches(segment)) => {}
+                _ => return false,
+            }
+        }
+        pattern_segments.next().is_some()
+    }
+}
+
+/// Check that a destructive operation on a protected key carries the bucket's confirmation
+/// token, which is the bucket name typed by the user
+pub fn ensure_confirmed(
+    protected: &ProtectedPrefixes,
+    bucket_name: &str,
+    key: &str,
+    confirmation: Option<&str>,
+) -> Result<()> {
+    let Some(protected_prefix) = protected.matching(key) else {
+        return Ok(());
+    };
+    if confirmation.map(str::trim) == Some(bucket_name) {
+        return Ok(());
+    }
+
+    Err(S3DeckError::ConfirmationRequired(ConfirmationInfo {
+        message: format!(
+            "'{}' is protected by '{}'. Type the bucket name '{}' to confirm.",
+            key, protected_prefix, bucket_name
+        ),
+        key: key.to_string(),
+        protected_prefix: protected_prefix.to_string(),
+        confirmation_token: bucket_name.to_string(),
+    }))
+}
+
+/// Check that a permanent delete of an object the trash can't take, e.g. an archived one,
+/// carries the bucket's confirmation token
+pub fn ensure_permanent_delete_confirmed(
+    bucket_name: &str,
+    key: &str,
+    reason: &str,
+    confirmation: Option<&str>,
+) -> Result<()> {
+    if confirmation.map(str::trim) == Some(bucket_name) {
+        return Ok(());
+    }
+
+    Err(S3DeckError::ConfirmationRequired(ConfirmationInfo {
+        message: format!(
+            "'{}' can't be moved to the trash because {}. Type the bucket name '{}' to delete it permanently.",
+            key, reason, bucket_name
+        ),
+        key: key.to_string(),
+        protected_prefix: String::new(),
+        confirmation_token: bucket_name.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protected(patterns: &[&str]) -> ProtectedPrefixes {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        ProtectedPrefixes::new(&patterns).unwrap()
+    }
+
+    #[test]
+    fn test_plain_entries_protect_keys_and_parent_folders() {
+        let protected = protected(&["backups/"]);
+
+        assert_eq!(protected.matching("backups/db.sql"), Some("backups/"));
+        assert_eq!(protected.matching("backups/"), Some("backups/"));
+        // Deleting the bucket root or a parent folder reaches the protected keys
+        assert_eq!(protected.matching(""), Some("backups/"));
+        assert_eq!(protected.matching("sandbox/report.pdf"), None);
+        assert_eq!(protected.matching("sandbox/"), None);
+    }
+
+    #[test]
+    fn test_glob_entries() {
+        let protected = protected(&["prod/**", "*/secrets/*"]);
+
+        assert_eq!(protected.matching("prod/app/config.json"), Some("prod/**"));
+        assert_eq!(protected.matching("prod/"), Some("prod/**"));
+        assert_eq!(
+            protected.matching("team/secrets/key.pem"),
+            Some("*/secrets/*")
+        );
+        assert_eq!(protected.matching("team/secrets/nested/key.pem"), None);
+        assert_eq!(protected.matching("production/app.log"), None);
+        // Any top-level folder may hold a `secrets/` folder, but not one nested deeper
+        assert_eq!(protected.matching("staging/"), Some("*/secrets/*"));
+        assert_eq!(protected.matching("staging/app/"), None);
+    }
+
+    #[test]
+    fn test_invalid_glob_is_a_config_error() {
+        let result = ProtectedPrefixes::new(&["prod/[".to_string()]);
+        assert!(matches!(result, Err(S3DeckError::Config(_))));
+    }
+
+    #[test]
+    fn test_confirmation_token_is_the_bucket_name() {
+        let protected = protected(&["prod/**"]);
+
+        let error = ensure_confirmed(&protected, "media", "prod/a.txt", None).unwrap_err();
+        match error {
+            S3DeckError::ConfirmationRequired(info) => {
+                assert_eq!(info.protected_prefix, "prod/**");
+                assert_eq!(info.confirmation_token, "media");
+            }
+            other => panic!("expected ConfirmationRequired, got {:?}", other),
+        }
+
+        assert!(ensure_confirmed(&protected, "media", "prod/a.txt", Some("prod")).is_err());
+        assert!(ensure_confirmed(&protected, "media", "prod/a.txt", Some("media")).is_ok());
+        assert!(ensure_confirmed(&protected, "media", "dev/a.txt", None).is_ok());
+    }
+}
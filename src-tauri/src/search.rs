@@ -0,0 +1,259 @@
+use crate::models::{FileItem, ObjectSummary, Result, S3DeckError, SearchMatchMode, SearchRequest};
+use chrono::{DateTime, Utc};
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The filters of a search request, compiled once before listing starts
+#[derive(Debug)]
+pub struct SearchFilter {
+    query: Option<KeyMatcher>,
+    min_size: Option<i64>,
+    max_size: Option<i64>,
+    modified_after: Option<DateTime<Utc>>,
+    modified_before: Option<DateTime<Utc>>,
+    /// Upper-cased storage classes, empty for any
+    storage_classes: Vec<String>,
+}
+
+#[derive(Debug)]
+enum KeyMatcher {
+    /// Lower-cased unless the search is case sensitive
+    Substring {
+        needle: String,
+        case_sensitive: bool,
+    },
+    Glob {
+        pattern: Pattern,
+        options: MatchOptions,
+    },
+    Regex(Regex),
+}
+
+impl SearchFilter {
+    pub fn new(request: &SearchRequest) -> Result<Self> {
+        let query = match request.query.as_deref().filter(|q| !q.is_empty()) {
+            None => None,
+            Some(query) => Some(match request.match_mode {
+                SearchMatchMode::Substring => KeyMatcher::Substring {
+                    needle: if request.case_sensitive {
+                        query.to_string()
+                    } else {
+                        query.to_lowercase()
+                    },
+                    case_sensitive: request.case_sensitive,
+                },
+                SearchMatchMode::Glob => KeyMatcher::Glob {
+                    pattern: Pattern::new(query).map_err(|e| {
+                        S3DeckError::InvalidInput(format!("Invalid glob '{}': {}", query, e))
+                    })?,
+                    options: MatchOptions {
+                        case_sensitive: request.case_sensitive,
+                        require_literal_separator: false,
+                        require_literal_leading_dot: false,
+                    },
+                },
+                SearchMatchMode::Regex => KeyMatcher::Regex(
+                    RegexBuilder::new(query)
+                        .case_insensitive(!request.case_sensitive)
+                        .build()
+                        .map_err(|e| {
+                            S3DeckError::InvalidInput(format!("Invalid regex '{}': {}", query, e))
+                        })?,
+                ),
+            }),
+        };
+
+        if let (Some(min), Some(max)) = (request.min_size, request.max_size) {
+            if min > max {
+                return Err(S3DeckError::InvalidInput(format!(
+                    "Minimum size {} is larger than maximum size {}",
+                    min, max
+                )));
+            }
+        }
+
+        Ok(Self {
+            query,
+            min_size: request.min_size,
+            max_size: request.max_size,
+            modified_after: request.modified_after,
+            modified_before: request.modified_before,
+            storage_classes: request
+                .storage_classes
+                .iter()
+                .map(|class| class.trim().to_uppercase())
+                .filter(|class| !class.is_empty())
+                .collect(),
+        })
+    }
+
+    /// Whether an object matches, given its key relative to the searched prefix. Listings
+    /// leave out the storage class of `STANDARD` objects, so `None` counts as `STANDARD`.
+    pub fn matches(
+        &self,
+        relative_key: &str,
+        size: i64,
+        last_modified: Option<DateTime<Utc>>,
+        storage_class: Option<&str>,
+    ) -> bool {
+        let storage_class = storage_class.unwrap_or("STANDARD");
+
+        self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self
+                .modified_after
+                .is_none_or(|after| last_modified.is_some_and(|m| m >= after))
+            && self
+                .modified_before
+                .is_none_or(|before| last_modified.is_some_and(|m| m <= before))
+            && (self.storage_classes.is_empty()
+                || self
+                    .storage_classes
+                    .iter()
+                    .any(|class| class.eq_ignore_ascii_case(storage_class)))
+            && self
+                .query
+                .as_ref()
+                .is_none_or(|query| query.matches(relative_key))
+    }
+
+    /// The objects of a page below `prefix` that match, ready to show
+    pub fn select(&self, prefix: &str, page: &[ObjectSummary]) -> Vec<FileItem> {
+        page.iter()
+            .filter(|object| {
+                self.matches(
+                    object.key.strip_prefix(prefix).unwrap_or(&object.key),
+                    object.size,
+                    object.last_modified,
+                    object.storage_class.as_deref(),
+                )
+            })
+            .map(ObjectSummary::file_item)
+            .collect()
+    }
+}
+
+impl KeyMatcher {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            KeyMatcher::Substring {
+                needle,
+                case_sensitive: true,
+            } => key.contains(needle.as_str()),
+            KeyMatcher::Substring { needle, .. } => key.to_lowercase().contains(needle.as_str()),
+            KeyMatcher::Glob { pattern, options } => pattern.matches_with(key, *options),
+            KeyMatcher::Regex(regex) => regex.is_match(key),
+        }
+    }
+}
+
+/// Searches in progress, kept in Tauri state so `cancel_search` can stop them
+#[derive(Default)]
+pub struct SearchJobs {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl SearchJobs {
+    /// Register a search, returning the flag that is set when it is cancelled
+    pub fn start(&self, search_id: &str) -> Arc<AtomicBool> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running
+            .lock()
+            .unwrap()
+            .insert(search_id.to_string(), cancelled.clone());
+        cancelled
+    }
+
+    pub fn finish(&self, search_id: &str) {
+        self.running.lock().unwrap().remove(search_id);
+    }
+
+    /// Returns false when no search with this id is running
+    pub fn cancel(&self, search_id: &str) -> bool {
+        match self.running.lock().unwrap().get(search_id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn filter(request: SearchRequest) -> SearchFilter {
+        SearchFilter::new(&request).unwrap()
+    }
+
+    #[test]
+    fn test_query_match_modes() {
+        let substring = filter(SearchRequest {
+            query: Some("report".into()),
+            ..Default::default()
+        });
+        assert!(substring.matches("2024/Q1-Report.csv", 1, None, None));
+        assert!(!substring.matches("2024/summary.csv", 1, None, None));
+
+        let glob = filter(SearchRequest {
+            query: Some("*.csv".into()),
+            match_mode: SearchMatchMode::Glob,
+            case_sensitive: true,
+            ..Default::default()
+        });
+        assert!(glob.matches("exports/2024/03/orders.csv", 1, None, None));
+        assert!(!glob.matches("exports/2024/03/orders.CSV", 1, None, None));
+
+        let regex = filter(SearchRequest {
+            query: Some(r"^logs/2024-03-\d{2}\.gz$".into()),
+            match_mode: SearchMatchMode::Regex,
+            ..Default::default()
+        });
+        assert!(regex.matches("logs/2024-03-17.gz", 1, None, None));
+        assert!(!regex.matches("old/logs/2024-03-17.gz", 1, None, None));
+    }
+
+    #[test]
+    fn test_size_date_and_storage_class_filters() {
+        let march = |day| Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap();
+        let search = filter(SearchRequest {
+            min_size: Some(10),
+            max_size: Some(100),
+            modified_after: Some(march(1)),
+            modified_before: Some(march(31)),
+            storage_classes: vec!["standard".into(), "GLACIER".into()],
+            ..Default::default()
+        });
+
+        assert!(search.matches("a.csv", 50, Some(march(15)), None));
+        assert!(search.matches("a.csv", 50, Some(march(15)), Some("GLACIER")));
+        assert!(!search.matches("a.csv", 50, Some(march(15)), Some("DEEP_ARCHIVE")));
+        assert!(!search.matches("a.csv", 5, Some(march(15)), None));
+        assert!(!search.matches("a.csv", 500, Some(march(15)), None));
+        assert!(!search.matches(
+            "a.csv",
+            50,
+            Some(march(1) - chrono::Duration::days(1)),
+            None
+        ));
+        assert!(!search.matches("a.csv", 50, None, None));
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected() {
+        for (query, match_mode) in [("[", SearchMatchMode::Glob), ("(", SearchMatchMode::Regex)] {
+            let result = SearchFilter::new(&SearchRequest {
+                query: Some(query.into()),
+                match_mode,
+                ..Default::default()
+            });
+            assert!(matches!(result, Err(S3DeckError::InvalidInput(_))));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::models::{FolderStats, FolderStatsProgress, ObjectSummary, StorageClassUsage};
+use chrono::{TimeDelta, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Number of largest objects listed in folder stats
+const LARGEST_COUNT: usize = 10;
+
+/// How long computed folder stats are served from the cache
+const CACHE_TTL: TimeDelta = TimeDelta::minutes(5);
+
+/// Accumulates folder stats one listing page at a time
+pub struct FolderStatsBuilder {
+    largest_count: usize,
+    total_bytes: i64,
+    object_count: u64,
+    /// Bytes and object count per storage class
+    storage_classes: HashMap<String, (i64, u64)>,
+    newest: Option<ObjectSummary>,
+    oldest: Option<ObjectSummary>,
+    /// Candidates for the largest objects, trimmed to `largest_count` now and then
+    largest: Vec<ObjectSummary>,
+}
+
+impl Default for FolderStatsBuilder {
+    fn default() -> Self {
+        Self::with_largest(LARGEST_COUNT)
+    }
+}
+
+impl FolderStatsBuilder {
+    /// A builder keeping the `largest_count` largest objects
+    pub fn with_largest(largest_count: usize) -> Self {
+        Self {
+            largest_count,
+            total_bytes: 0,
+            object_count: 0,
+            storage_classes: HashMap::new(),
+            newest: None,
+            oldest: None,
+            largest: Vec::new(),
+        }
+    }
+
+    pub fn add_page(&mut self, page: &[ObjectSummary]) {
+        for object in page {
+            self.total_bytes += object.size;
+            self.object_count += 1;
+
+            let class = object.storage_class.as_deref().unwrap_or("STANDARD");
+            let usage = self.storage_classes.entry(class.to_string()).or_default();
+            usage.0 += object.size;
+            usage.1 += 1;
+
+            if let Some(modified) = object.last_modified {
+                if self
+                    .newest
+                    .as_ref()
+                    .is_none_or(|newest| newest.last_modified < Some(modified))
+                {
+                    self.newest = Some(object.clone());
+                }
+                if self
+                    .oldest
+                    .as_ref()
+                    .is_none_or(|oldest| oldest.last_modified > Some(modified))
+                {
+                    self.oldest = Some(object.clone());
+                }
+            }
+
+            self.largest.push(object.clone());
+        }
+
+        if self.largest.len() > self.largest_count * 4 {
+            self.trim_largest();
+        }
+    }
+
+    pub fn progress(&self) -> FolderStatsProgress {
+        FolderStatsProgress {
+            object_count: self.object_count,
+            total_bytes: self.total_bytes,
+        }
+    }
+
+    pub fn finish(mut self, prefix: &str) -> FolderStats {
+        self.trim_largest();
+
+        let storage_classes = storage_class_usage(self.storage_classes);
+
+        FolderStats {
+            prefix: prefix.to_string(),
+            total_bytes: self.total_bytes,
+            object_count: self.object_count,
+            storage_classes,
+            newest: self.newest,
+            oldest: self.oldest,
+            largest: self.largest,
+            computed_at: Utc::now(),
+        }
+    }
+
+    fn trim_largest(&mut self) {
+        self.largest
+            .sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.key.cmp(&b.key)));
+        self.largest.truncate(self.largest_count);
+    }
+}
+
+/// Bytes and object counts per storage class, largest share first
+pub fn storage_class_usage(usage: HashMap<String, (i64, u64)>) -> Vec<StorageClassUsage> {
+    let mut storage_classes: Vec<StorageClassUsage> = usage
+        .into_iter()
+        .map(|(storage_class, (bytes, object_count))| StorageClassUsage {
+            storage_class,
+            bytes,
+            object_count,
+        })
+        .collect();
+    storage_classes.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.storage_class.cmp(&b.storage_class))
+    });
+    storage_classes
+}
+
+/// Folder stats computed recently, per bucket and prefix, kept in Tauri state
+#[derive(Default)]
+pub struct FolderStatsCache {
+    entries: Mutex<HashMap<(String, String), FolderStats>>,
+}
+
+impl FolderStatsCache {
+    /// Cached stats for a prefix, if they are younger than the TTL
+    pub fn get(&self, bucket_id: &str, prefix: &str) -> Option<FolderStats> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(bucket_id.to_string(), prefix.to_string()))
+            .filter(|stats| Utc::now() - stats.computed_at < CACHE_TTL)
+            .cloned()
+    }
+
+    pub fn insert(&self, bucket_id: &str, stats: FolderStats) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, cached| Utc::now() - cached.computed_at < CACHE_TTL);
+        entries.insert((bucket_id.to_string(), stats.prefix.clone()), stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn object(key: &str, size: i64, modified: i64, class: Option<&str>) -> ObjectSummary {
+        ObjectSummary {
+            key: key.to_string(),
+            size,
+            last_modified: DateTime::from_timestamp(modified, 0),
+            etag: None,
+            storage_class: class.map(|c| c.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_stats_across_pages() {
+        let mut builder = FolderStatsBuilder::default();
+        builder.add_page(&[
+            object("data/a.bin", 100, 2_000, None),
+            object("data/b.bin", 300, 1_000, Some("GLACIER")),
+        ]);
+        assert_eq!(builder.progress().object_count, 2);
+        let many: Vec<ObjectSummary> = (0..50)
+            .map(|i| object(&format!("data/small/{}", i), 1, 1_500, None))
+            .collect();
+        builder.add_page(&many);
+        builder.add_page(&[object("data/c.bin", 200, 3_000, Some("STANDARD"))]);
+
+        let stats = builder.finish("data/");
+        assert_eq!(stats.total_bytes, 650);
+        assert_eq!(stats.object_count, 53);
+        assert_eq!(
+            stats.storage_classes,
+            vec![
+                StorageClassUsage {
+                    storage_class: "STANDARD".to_string(),
+                    bytes: 350,
+                    object_count: 52,
+                },
+                StorageClassUsage {
+                    storage_class: "GLACIER".to_string(),
+                    bytes: 300,
+                    object_count: 1,
+                },
+            ]
+        );
+        assert_eq!(stats.newest.unwrap().key, "data/c.bin");
+        assert_eq!(stats.oldest.unwrap().key, "data/b.bin");
+
+        let largest: Vec<&str> = stats.largest.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(largest.len(), LARGEST_COUNT);
+        assert_eq!(
+            &largest[..4],
+            ["data/b.bin", "data/c.bin", "data/a.bin", "data/small/0"]
+        );
+    }
+
+    #[test]
+    fn test_cache_expires_after_ttl() {
+        let cache = FolderStatsCache::default();
+        let mut stats = FolderStatsBuilder::default().finish("logs/");
+        cache.insert("b1", stats.clone());
+        assert!(cache.get("b1", "logs/").is_some());
+        assert!(cache.get("b2", "logs/").is_none());
+
+        stats.computed_at = Utc::now() - CACHE_TTL - TimeDelta::seconds(1);
+        cache.insert("b1", stats);
+        assert!(cache.get("b1", "logs/").is_none());
+    }
+}
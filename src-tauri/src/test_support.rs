@@ -0,0 +1,674 @@
+//! In-memory S3 stand-in for exercising `S3Client` end to end. It speaks just enough of the
+//! path-style REST API (list, head, get, put, copy, delete, tags, restore, versioning status) over
+//! plain HTTP/1.1.
+
+use crate::models::{BucketConfig, NetworkSettings, ProxySettings, StorageProvider};
+use crate::s3_client::S3Client;
+use chrono::{DateTime, Utc};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const BUCKET: &str = "deck-test";
+
+#[derive(Debug, Clone)]
+pub struct FakeObject {
+    pub body: Vec<u8>,
+    pub content_type: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    /// Cache-Control, Content-Disposition and similar headers plus `x-amz-meta-*`
+    pub headers: Vec<(String, String)>,
+    pub tags: Vec<(String, String)>,
+}
+
+impl FakeObject {
+    fn new(body: Vec<u8>, content_type: String) -> Self {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+
+        Self {
+            etag: format!("\"{:016x}\"", hasher.finish()),
+            body,
+            content_type,
+            last_modified: Utc::now(),
+            headers: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn storage_class(&self) -> &str {
+        self.header("x-amz-storage-class").unwrap_or("STANDARD")
+    }
+
+    fn restore_header(&self) -> Option<&str> {
+        self.header("x-amz-restore")
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(header, _)| header != name);
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+}
+
+const ONGOING_RESTORE: &str = "ongoing-request=\"true\"";
+
+#[derive(Default)]
+struct Store {
+    objects: Mutex<BTreeMap<String, FakeObject>>,
+    /// When false, conditional writes are rejected with 501 like on older gateways
+    conditional_writes: AtomicBool,
+    last_headers: Mutex<HashMap<String, String>>,
+    /// Region the bucket lives in; signed requests for other regions get redirected
+    region: Mutex<Option<String>>,
+}
+
+pub struct FakeS3 {
+    pub endpoint: String,
+    store: Arc<Store>,
+}
+
+impl FakeS3 {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let store = Arc::new(Store::default());
+        store.conditional_writes.store(true, Ordering::Relaxed);
+
+        let shared = store.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(socket, shared.clone()));
+            }
+        });
+
+        Self { endpoint, store }
+    }
+
+    /// Bucket config pointing at this stand-in
+    pub fn bucket_config(&self) -> BucketConfig {
+        BucketConfig {
+            id: "fake".to_string(),
+            name: BUCKET.to_string(),
+            display_name: "Fake".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "secret".to_string(),
+            endpoint: Some(self.endpoint.clone()),
+            provider: Some(StorageProvider::Custom),
+            conditional_writes: Some(true),
+            ..Default::default()
+        }
+    }
+
+    pub async fn client(&self) -> S3Client {
+        S3Client::new(&self.bucket_config(), &direct_network())
+            .await
+            .unwrap()
+    }
+
+    pub fn put(&self, key: &str, body: &[u8]) {
+        let object = FakeObject::new(body.to_vec(), "application/octet-stream".to_string());
+        self.store
+            .objects
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), object);
+    }
+
+    pub fn get(&self, key: &str) -> Option<FakeObject> {
+        self.store.objects.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn set_tags(&self, key: &str, tags: &[(&str, &str)]) {
+        if let Some(object) = self.store.objects.lock().unwrap().get_mut(key) {
+            object.tags = tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+        }
+    }
+
+    pub fn set_storage_class(&self, key: &str, storage_class: &str) {
+        if let Some(object) = self.store.objects.lock().unwrap().get_mut(key) {
+            object.set_header("x-amz-storage-class", storage_class);
+        }
+    }
+
+    /// Finish a requested restore, leaving a copy readable until `expires_at`
+    pub fn complete_restore(&self, key: &str, expires_at: DateTime<Utc>) {
+        if let Some(object) = self.store.objects.lock().unwrap().get_mut(key) {
+            let header = format!(
+                "ongoing-request=\"false\", expiry-date=\"{}\"",
+                expires_at.format("%a, %d %b %Y %H:%M:%S GMT")
+            );
+            object.set_header("x-amz-restore", &header);
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.store.objects.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Header of the most recent request, e.g. to check whether it was signed
+    pub fn last_header(&self, name: &str) -> Option<String> {
+        self.store.last_headers.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn set_region(&self, region: &str) {
+        *self.store.region.lock().unwrap() = Some(region.to_string());
+    }
+
+    pub fn disable_conditional_writes(&self) {
+        self.store
+            .conditional_writes
+            .store(false, Ordering::Relaxed);
+    }
+}
+
+/// Network settings that ignore any proxy configured in the test environment
+pub fn direct_network() -> NetworkSettings {
+    NetworkSettings {
+        proxy: ProxySettings {
+            disabled: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+struct Request {
+    method: String,
+    key: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn xml(status: u16, body: String) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/xml".to_string())],
+            body: body.into_bytes(),
+        }
+    }
+
+    fn error(status: u16, code: &str, message: &str) -> Self {
+        Self::xml(
+            status,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message><RequestId>fake</RequestId></Error>",
+                code,
+                xml_escape(message)
+            ),
+        )
+    }
+}
+
+async fn serve_connection(socket: TcpStream, store: Arc<Store>) {
+    let mut stream = BufReader::new(socket);
+
+    while let Some(request) = read_request(&mut stream).await {
+        let is_head = request.method == "HEAD";
+        let response = handle(request, &store);
+
+        let reason = match response.status {
+            200 => "OK",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            404 => "Not Found",
+            409 => "Conflict",
+            412 => "Precondition Failed",
+            501 => "Not Implemented",
+            _ => "Error",
+        };
+        let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !response
+            .headers
+            .iter()
+            .any(|(name, _)| *name == "Content-Length")
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+        }
+        head.push_str("\r\n");
+
+        let socket = stream.get_mut();
+        if socket.write_all(head.as_bytes()).await.is_err() {
+            return;
+        }
+        if !is_head && socket.write_all(&response.body).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut request_line = String::new();
+    if stream.read_line(&mut request_line).await.ok()? == 0 {
+        return None;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).await.ok()?;
+
+    let (path, query_string) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query_string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                decode(name).unwrap_or_default(),
+                decode(value).unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    // Path-style addressing: /<bucket>/<key>
+    let raw_key = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .map(|(_, key)| key)
+        .unwrap_or("");
+
+    Some(Request {
+        method,
+        key: decode(raw_key)?,
+        query,
+        headers,
+        body,
+    })
+}
+
+fn handle(request: Request, store: &Store) -> Response {
+    *store.last_headers.lock().unwrap() = request.headers.clone();
+
+    let bucket_region = store
+        .region
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| "us-east-1".to_string());
+    // SigV4 credential scope: Credential=<key>/<date>/<region>/s3/aws4_request
+    let signed_region = request
+        .headers
+        .get("authorization")
+        .and_then(|auth| auth.split("Credential=").nth(1))
+        .and_then(|scope| scope.split('/').nth(2))
+        .map(|region| region.to_string());
+    if signed_region.is_some_and(|region| region != bucket_region) {
+        let mut response = Response::error(
+            301,
+            "PermanentRedirect",
+            "The bucket you are attempting to access must be addressed using the specified endpoint.",
+        );
+        response
+            .headers
+            .push(("x-amz-bucket-region".to_string(), bucket_region));
+        return response;
+    }
+    if request.method == "HEAD" && request.key.is_empty() {
+        let mut response = Response::new(200);
+        response
+            .headers
+            .push(("x-amz-bucket-region".to_string(), bucket_region));
+        return response;
+    }
+    let mut objects = store.objects.lock().unwrap();
+
+    let if_none_match = request.headers.get("if-none-match");
+    let if_match = request.headers.get("if-match");
+    if matches!(request.method.as_str(), "PUT" | "DELETE")
+        && (if_none_match.is_some() || if_match.is_some())
+    {
+        if !store.conditional_writes.load(Ordering::Relaxed) {
+            return Response::error(
+                501,
+                "NotImplemented",
+                "A header you provided implies functionality that is not implemented",
+            );
+        }
+
+        let current = objects.get(&request.key);
+        if if_none_match.map(|v| v == "*").unwrap_or(false) && current.is_some() {
+            return Response::error(
+                412,
+                "PreconditionFailed",
+                "At least one of the pre-conditions you specified did not hold",
+            );
+        }
+        if let Some(etag) = if_match {
+            match current {
+                None => {
+                    return Response::error(404, "NoSuchKey", "The specified key does not exist.")
+                }
+                Some(object) if &object.etag != etag => {
+                    return Response::error(
+                        412,
+                        "PreconditionFailed",
+                        "At least one of the pre-conditions you specified did not hold",
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+
+    match request.method.as_str() {
+        // Versioning was never enabled on the stand-in bucket
+        "GET" if request.key.is_empty() && request.query.contains_key("versioning") => {
+            Response::xml(
+                200,
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><VersioningConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"/>".to_string(),
+            )
+        }
+        "GET" if request.key.is_empty() => list_objects(&request, &objects),
+        "GET" if request.query.contains_key("tagging") => match objects.get(&request.key) {
+            Some(object) => {
+                let tags: String = object
+                    .tags
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "<Tag><Key>{}</Key><Value>{}</Value></Tag>",
+                            xml_escape(key),
+                            xml_escape(value)
+                        )
+                    })
+                    .collect();
+                Response::xml(
+                    200,
+                    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Tagging><TagSet>{}</TagSet></Tagging>", tags),
+                )
+            }
+            None => Response::error(404, "NoSuchKey", "The specified key does not exist."),
+        },
+        "GET" | "HEAD" => match objects.get(&request.key) {
+            Some(object) => {
+                let mut response = Response::new(200);
+                response.headers = object_headers(object);
+                response.body = object.body.clone();
+                response
+            }
+            None => Response::error(404, "NoSuchKey", "The specified key does not exist."),
+        },
+        "PUT" if request.query.contains_key("tagging") => match objects.get_mut(&request.key) {
+            Some(object) => {
+                let body = String::from_utf8_lossy(&request.body);
+                let pattern = regex::Regex::new("<Key>(.*?)</Key><Value>(.*?)</Value>").unwrap();
+                object.tags = pattern
+                    .captures_iter(&body)
+                    .map(|tag| (tag[1].to_string(), tag[2].to_string()))
+                    .collect();
+                Response::new(200)
+            }
+            None => Response::error(404, "NoSuchKey", "The specified key does not exist."),
+        },
+        "PUT" => {
+            let object = match request.headers.get("x-amz-copy-source") {
+                Some(source) => {
+                    // Like S3, a '?' starts the versionId and the rest must be percent-decoded
+                    let source = source.split('?').next().unwrap_or_default();
+                    let source_key = match decode(source.trim_start_matches('/'))
+                        .and_then(|s| s.split_once('/').map(|(_, key)| key.to_string()))
+                    {
+                        Some(key) => key,
+                        None => {
+                            return Response::error(400, "InvalidArgument", "Invalid copy source")
+                        }
+                    };
+                    match objects.get(&source_key) {
+                        Some(source)
+                            if request
+                                .headers
+                                .get("x-amz-copy-source-if-match")
+                                .is_some_and(|etag| etag != &source.etag) =>
+                        {
+                            return Response::error(
+                                412,
+                                "PreconditionFailed",
+                                "At least one of the pre-conditions you specified did not hold",
+                            )
+                        }
+                        Some(source) => {
+                            let content_type = request
+                                .headers
+                                .get("content-type")
+                                .cloned()
+                                .unwrap_or_else(|| source.content_type.clone());
+                            let mut object = FakeObject::new(source.body.clone(), content_type);
+                            object.headers = match request.headers.get("x-amz-metadata-directive")
+                            {
+                                Some(directive) if directive == "REPLACE" => {
+                                    stored_headers(&request)
+                                }
+                                _ => source.headers.clone(),
+                            };
+                            // Like S3, a copy is STANDARD unless it asks for a storage class
+                            object.headers.retain(|(header, _)| header != "x-amz-storage-class");
+                            if let Some(class) = request.headers.get("x-amz-storage-class") {
+                                object.set_header("x-amz-storage-class", class);
+                            }
+                            object.tags = source.tags.clone();
+                            object
+                        }
+                        None => {
+                            return Response::error(
+                                404,
+                                "NoSuchKey",
+                                "The specified key does not exist.",
+                            )
+                        }
+                    }
+                }
+                None => {
+                    let headers = stored_headers(&request);
+                    let mut object = FakeObject::new(
+                        request.body,
+                        request
+                            .headers
+                            .get("content-type")
+                            .cloned()
+                            .unwrap_or_else(|| "binary/octet-stream".to_string()),
+                    );
+                    object.headers = headers;
+                    object
+                }
+            };
+
+            let is_copy = request.headers.contains_key("x-amz-copy-source");
+            let etag = object.etag.clone();
+            objects.insert(request.key, object);
+
+            if is_copy {
+                Response::xml(
+                    200,
+                    format!(
+                        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><CopyObjectResult><ETag>{}</ETag><LastModified>{}</LastModified></CopyObjectResult>",
+                        xml_escape(&etag),
+                        Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                    ),
+                )
+            } else {
+                let mut response = Response::new(200);
+                response.headers.push(("ETag".to_string(), etag));
+                response
+            }
+        }
+        // Any object can be restored; the restore stays in progress until `complete_restore`
+        "POST" if request.query.contains_key("restore") => match objects.get_mut(&request.key) {
+            Some(object) if object.restore_header() == Some(ONGOING_RESTORE) => Response::error(
+                409,
+                "RestoreAlreadyInProgress",
+                "Object restore is already in progress",
+            ),
+            Some(object) => {
+                object.set_header("x-amz-restore", ONGOING_RESTORE);
+                Response::new(202)
+            }
+            None => Response::error(404, "NoSuchKey", "The specified key does not exist."),
+        },
+        "DELETE" => {
+            objects.remove(&request.key);
+            Response::new(204)
+        }
+        _ => Response::error(400, "NotImplemented", "Operation not supported by FakeS3"),
+    }
+}
+
+fn list_objects(request: &Request, objects: &BTreeMap<String, FakeObject>) -> Response {
+    let prefix = request.query.get("prefix").cloned().unwrap_or_default();
+    let delimiter = request.query.get("delimiter").filter(|d| !d.is_empty());
+
+    let mut contents = String::new();
+    let mut common_prefixes = BTreeSet::new();
+    let mut key_count = 0;
+
+    for (key, object) in objects.range(prefix.clone()..) {
+        let Some(rest) = key.strip_prefix(&prefix) else {
+            break;
+        };
+
+        if let Some(delimiter) = delimiter {
+            if let Some(position) = rest.find(delimiter.as_str()) {
+                common_prefixes.insert(format!(
+                    "{}{}",
+                    prefix,
+                    &rest[..position + delimiter.len()]
+                ));
+                continue;
+            }
+        }
+
+        key_count += 1;
+        contents.push_str(&format!(
+            "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>{}</StorageClass></Contents>",
+            xml_escape(key),
+            object.last_modified.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            xml_escape(&object.etag),
+            object.body.len(),
+            object.storage_class()
+        ));
+    }
+
+    for common_prefix in &common_prefixes {
+        contents.push_str(&format!(
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            xml_escape(common_prefix)
+        ));
+    }
+
+    Response::xml(
+        200,
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>1000</MaxKeys><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+            BUCKET,
+            xml_escape(&prefix),
+            key_count + common_prefixes.len(),
+            contents
+        ),
+    )
+}
+
+fn object_headers(object: &FakeObject) -> Vec<(String, String)> {
+    let mut headers = vec![
+        ("Content-Type".to_string(), object.content_type.clone()),
+        ("Content-Length".to_string(), object.body.len().to_string()),
+        ("ETag".to_string(), object.etag.clone()),
+        (
+            "Last-Modified".to_string(),
+            object
+                .last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        ),
+    ];
+    headers.extend(object.headers.iter().cloned());
+    headers
+}
+
+/// The headers of a PUT that S3 stores with the object
+fn stored_headers(request: &Request) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            matches!(
+                name.as_str(),
+                "cache-control"
+                    | "content-disposition"
+                    | "content-encoding"
+                    | "content-language"
+                    | "x-amz-storage-class"
+            ) || name.starts_with("x-amz-meta-")
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    headers.sort();
+    headers
+}
+
+fn decode(value: &str) -> Option<String> {
+    urlencoding::decode(value).ok().map(|s| s.into_owned())
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,387 @@
+use crate::csv_format;
+use crate::models::{
+    AgeBucketUsage, ExtensionUsage, FolderStatsProgress, ObjectSummary, PrefixUsage, Result,
+    S3DeckError, UsageReport,
+};
+use crate::stats::{storage_class_usage, FolderStatsBuilder};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Sub-prefix levels broken down when the request doesn't say
+pub const DEFAULT_DEPTH: usize = 2;
+
+/// Number of largest objects listed in a usage report
+const LARGEST_COUNT: usize = 100;
+
+/// Lower bounds of the age histogram buckets, in days, with their labels
+const AGE_BUCKETS: &[(u32, &str)] = &[
+    (0, "Under 30 days"),
+    (30, "30-90 days"),
+    (90, "3-6 months"),
+    (180, "6-12 months"),
+    (365, "1-2 years"),
+    (730, "2-5 years"),
+    (1825, "Over 5 years"),
+];
+
+/// Suffixes longer than this, or made only of digits like the `.0042` of `part.0042`, are not
+/// counted as file types
+const MAX_EXTENSION_LENGTH: usize = 10;
+
+/// Accumulates a usage report one listing page at a time
+pub struct UsageReportBuilder {
+    prefix: String,
+    depth: usize,
+    now: DateTime<Utc>,
+    totals: FolderStatsBuilder,
+    /// Bytes and object count per sub-prefix, down to `depth` levels
+    prefixes: HashMap<String, (i64, u64)>,
+    /// Bytes and object count per storage class, for each age bucket
+    ages: Vec<HashMap<String, (i64, u64)>>,
+    extensions: HashMap<String, (i64, u64)>,
+}
+
+impl UsageReportBuilder {
+    pub fn new(prefix: &str, depth: usize) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            depth,
+            now: Utc::now(),
+            totals: FolderStatsBuilder::with_largest(LARGEST_COUNT),
+            prefixes: HashMap::new(),
+            ages: vec![HashMap::new(); AGE_BUCKETS.len()],
+            extensions: HashMap::new(),
+        }
+    }
+
+    pub fn add_page(&mut self, page: &[ObjectSummary]) {
+        self.totals.add_page(page);
+
+        for object in page {
+            let relative = object.key.strip_prefix(&self.prefix).unwrap_or(&object.key);
+
+            // Every folder on the way to the object, down to the report depth
+            let folder_ends = relative.match_indices('/').take(self.depth);
+            for (position, _) in folder_ends {
+                let folder = &object.key[..self.prefix.len() + position + 1];
+                add(
+                    self.prefixes.entry(folder.to_string()).or_default(),
+                    object.size,
+                );
+            }
+
+            if let Some(modified) = object.last_modified {
+                let age_days = (self.now - modified).num_days().max(0) as u32;
+                let bucket = AGE_BUCKETS
+                    .iter()
+                    .rposition(|(min_days, _)| age_days >= *min_days)
+                    .unwrap_or(0);
+                let class = object.storage_class.as_deref().unwrap_or("STANDARD");
+                add(
+                    self.ages[bucket].entry(class.to_string()).or_default(),
+                    object.size,
+                );
+            }
+
+            add(
+                self.extensions.entry(extension(relative)).or_default(),
+                object.size,
+            );
+        }
+    }
+
+    pub fn progress(&self) -> FolderStatsProgress {
+        self.totals.progress()
+    }
+
+    pub fn finish(self, indexed_at: Option<DateTime<Utc>>) -> UsageReport {
+        let stats = self.totals.finish(&self.prefix);
+
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for folder in self.prefixes.keys() {
+            children
+                .entry(parent(folder, &self.prefix))
+                .or_default()
+                .push(folder);
+        }
+        let tree = PrefixUsage {
+            prefix: self.prefix.clone(),
+            name: name(&self.prefix).to_string(),
+            bytes: stats.total_bytes,
+            object_count: stats.object_count,
+            children: prefix_nodes(&self.prefix, &children, &self.prefixes),
+        };
+
+        let age_histogram = AGE_BUCKETS
+            .iter()
+            .zip(self.ages)
+            .enumerate()
+            .map(|(i, ((min_days, label), classes))| {
+                let storage_classes = storage_class_usage(classes);
+                AgeBucketUsage {
+                    label: label.to_string(),
+                    min_days: *min_days,
+                    max_days: AGE_BUCKETS.get(i + 1).map(|(next, _)| *next),
+                    bytes: storage_classes.iter().map(|usage| usage.bytes).sum(),
+                    object_count: storage_classes.iter().map(|usage| usage.object_count).sum(),
+                    storage_classes,
+                }
+            })
+            .collect();
+
+        let mut extensions: Vec<ExtensionUsage> = self
+            .extensions
+            .into_iter()
+            .map(|(extension, (bytes, object_count))| ExtensionUsage {
+                extension,
+                bytes,
+                object_count,
+            })
+            .collect();
+        extensions.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
+
+        UsageReport {
+            prefix: self.prefix,
+            depth: self.depth,
+            total_bytes: stats.total_bytes,
+            object_count: stats.object_count,
+            tree,
+            largest: stats.largest,
+            age_histogram,
+            extensions,
+            storage_classes: stats.storage_classes,
+            generated_at: self.now,
+            indexed_at,
+            inventory_date: None,
+        }
+    }
+}
+
+fn add(usage: &mut (i64, u64), size: i64) {
+    usage.0 += size;
+    usage.1 += 1;
+}
+
+fn prefix_nodes(
+    parent: &str,
+    children: &HashMap<&str, Vec<&str>>,
+    usage: &HashMap<String, (i64, u64)>,
+) -> Vec<PrefixUsage> {
+    let mut nodes: Vec<PrefixUsage> = children
+        .get(parent)
+        .into_iter()
+        .flatten()
+        .map(|folder| {
+            let (bytes, object_count) = usage[*folder];
+            PrefixUsage {
+                prefix: folder.to_string(),
+                name: name(folder).to_string(),
+                bytes,
+                object_count,
+                children: prefix_nodes(folder, children, usage),
+            }
+        })
+        .collect();
+    nodes.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.prefix.cmp(&b.prefix)));
+    nodes
+}
+
+/// The folder a sub-prefix sits in, never above the report prefix
+fn parent<'a>(folder: &'a str, root: &'a str) -> &'a str {
+    match folder.trim_end_matches('/').rfind('/') {
+        Some(position) if position + 1 > root.len() => &folder[..position + 1],
+        _ => root,
+    }
+}
+
+fn name(folder: &str) -> &str {
+    let trimmed = folder.trim_end_matches('/');
+    trimmed.rsplit('/').next().unwrap_or(trimmed)
+}
+
+/// Lower-cased extension of a key's file name, or `""` when it has none
+fn extension(key: &str) -> String {
+    let file_name = key.rsplit('/').next().unwrap_or(key);
+    match file_name.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty()
+                && !extension.is_empty()
+                && extension.len() <= MAX_EXTENSION_LENGTH
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+                && !extension.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            extension.to_ascii_lowercase()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Write a report to `destination`, as CSV when it ends in `.csv` and as JSON otherwise. The
+/// CSV has one `section,name,bytes,objectCount` row per prefix, extension, storage class, age
+/// bucket and large object.
+pub fn export(report: &UsageReport, destination: &Path) -> Result<()> {
+    let is_csv = destination
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let content = if is_csv {
+        to_csv(report)
+    } else {
+        serde_json::to_string_pretty(report)?
+    };
+
+    fs::write(destination, content).map_err(|e| {
+        S3DeckError::Io(format!(
+            "Failed to write usage report '{}': {}",
+            destination.display(),
+            e
+        ))
+    })
+}
+
+fn to_csv(report: &UsageReport) -> String {
+    let mut csv = String::from("section,name,bytes,objectCount\n");
+    let mut push = |section: &str, name: &str, bytes: i64, object_count: u64| {
+        csv.push_str(&csv_format::row(&[
+            section,
+            name,
+            &bytes.to_string(),
+            &object_count.to_string(),
+        ]));
+    };
+
+    let mut nodes = vec![&report.tree];
+    while let Some(node) = nodes.pop() {
+        push("prefix", &node.prefix, node.bytes, node.object_count);
+        nodes.extend(node.children.iter().rev());
+    }
+    for usage in &report.extensions {
+        push(
+            "extension",
+            &usage.extension,
+            usage.bytes,
+            usage.object_count,
+        );
+    }
+    for usage in &report.storage_classes {
+        push(
+            "storageClass",
+            &usage.storage_class,
+            usage.bytes,
+            usage.object_count,
+        );
+    }
+    for usage in &report.age_histogram {
+        push("age", &usage.label, usage.bytes, usage.object_count);
+    }
+    for object in &report.largest {
+        push("largest", &object.key, object.size, 1);
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn object(key: &str, size: i64, age_days: i64) -> ObjectSummary {
+        ObjectSummary {
+            key: key.to_string(),
+            size,
+            last_modified: Some(Utc::now() - TimeDelta::days(age_days)),
+            etag: None,
+            storage_class: None,
+        }
+    }
+
+    fn report() -> UsageReport {
+        let mut builder = UsageReportBuilder::new("data/", 2);
+        builder.add_page(&[
+            object("data/ml/train/a.parquet", 500, 400),
+            object("data/ml/train/b.parquet", 300, 10),
+            object("data/ml/labels.CSV", 50, 100),
+            object("data/logs/2024/01/app.log", 100, 2000),
+            object("data/readme", 5, 45),
+        ]);
+        builder.finish(None)
+    }
+
+    #[test]
+    fn test_prefix_tree_down_to_depth() {
+        let report = report();
+        assert_eq!(report.total_bytes, 955);
+        assert_eq!(report.tree.prefix, "data/");
+
+        let ml = &report.tree.children[0];
+        assert_eq!(
+            (ml.prefix.as_str(), ml.bytes, ml.object_count),
+            ("data/ml/", 850, 3)
+        );
+        assert_eq!(ml.children[0].name, "train");
+        assert_eq!(ml.children[0].bytes, 800);
+
+        // Depth 2 stops at data/logs/2024/
+        let logs = &report.tree.children[1];
+        assert_eq!(logs.children[0].prefix, "data/logs/2024/");
+        assert!(logs.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_ages_and_extensions() {
+        let report = report();
+
+        let ages: Vec<(u32, i64)> = report
+            .age_histogram
+            .iter()
+            .map(|bucket| (bucket.min_days, bucket.bytes))
+            .collect();
+        assert_eq!(
+            ages,
+            vec![
+                (0, 300),
+                (30, 5),
+                (90, 50),
+                (180, 0),
+                (365, 500),
+                (730, 0),
+                (1825, 100)
+            ]
+        );
+
+        let extensions: Vec<(&str, i64)> = report
+            .extensions
+            .iter()
+            .map(|usage| (usage.extension.as_str(), usage.bytes))
+            .collect();
+        assert_eq!(
+            extensions,
+            vec![("parquet", 800), ("log", 100), ("csv", 50), ("", 5)]
+        );
+        assert_eq!(extension("part.0042"), "");
+        assert_eq!(extension(".gitignore"), "");
+    }
+
+    #[test]
+    fn test_export_as_csv() {
+        let dir = std::env::temp_dir().join(format!("s3deck-usage-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usage.csv");
+
+        export(&report(), &path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "section,name,bytes,objectCount");
+        assert_eq!(lines[1], "prefix,data/,955,5");
+        assert_eq!(lines[2], "prefix,data/ml/,850,3");
+        assert!(lines.contains(&"largest,data/ml/train/a.parquet,500,1"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}